@@ -0,0 +1,317 @@
+use crate::{
+    config::SimConfig,
+    console::ConsoleCommand,
+    genes::Genome,
+    intelligence::{
+        brain::MutationNoise,
+        io::{Io, IoBool, IoF32},
+        perception::{BODY_INPUTS, VISION_RANGE},
+        NeuralNetwork,
+    },
+    rng::{RngStream, SimRng},
+    universe::scenario::Scenario,
+};
+use bevy::prelude::*;
+use rand::{distributions::Uniform, prelude::Distribution, rngs::StdRng, Rng, SeedableRng};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    thread,
+};
+
+/// The benchmark scenario is the same for every evaluation.
+const BENCHMARK_SEED: u64 = 0;
+const BENCHMARK_TICKS: usize = 2000;
+const BENCHMARK_FOOD_COUNT: usize = 20;
+const BENCHMARK_SIZE: f32 = 500.0;
+const BENCHMARK_EAT_RADIUS: f32 = 10.0;
+const BENCHMARK_MAX_SPEED: f32 = 5.0;
+/// The body perception of a healthy, half fed adult without load or parasites.
+const BENCHMARK_BODY: [f32; BODY_INPUTS] = [0.5, 1.0, 0.0, 1.0, 0.5, 1.0, 1.0, 0.0, 0.0];
+/// Fitness differences up to this are considered neutral.
+const NEUTRAL_TOLERANCE: f32 = 0.01;
+
+/// Fitness of `network` in a fixed foraging scenario.
+///
+/// A single agent with the `BENCHMARK_BODY` sees the food and the border of the arena
+/// through the vision rays of the config, laid out like the body and vision perceptions
+/// of a corgi. The other perceptions are zero, inputs beyond the network size are dropped.
+/// The movement decision, the first two outputs, is its acceleration.
+/// The fitness is the amount of food eaten.
+pub fn benchmark(network: &NeuralNetwork, config: &SimConfig) -> f32 {
+    let mut rng = StdRng::seed_from_u64(BENCHMARK_SEED);
+    let distr = Uniform::new(0.0, BENCHMARK_SIZE);
+    let mut random_position = || Vec2::new(distr.sample(&mut rng), distr.sample(&mut rng));
+
+    let mut food: Vec<Vec2> = (0..BENCHMARK_FOOD_COUNT)
+        .map(|_| random_position())
+        .collect();
+    let mut pos = Vec2::splat(BENCHMARK_SIZE / 2.0);
+    let mut vel = Vec2::zero();
+    let mut heading = 0.0;
+    let mut eaten = 0;
+
+    let mut input = Vec::with_capacity(network.inputs());
+    for _ in 0..BENCHMARK_TICKS {
+        let (nearest, offset) = food
+            .iter()
+            .map(|f| *f - pos)
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.length().partial_cmp(&b.length()).unwrap())
+            .unwrap();
+        if offset.length() <= BENCHMARK_EAT_RADIUS {
+            eaten += 1;
+            food[nearest] = random_position();
+            continue;
+        }
+
+        if vel.length() > 0.0 {
+            heading = vel.y.atan2(vel.x);
+        }
+        input.clear();
+        input.extend(BENCHMARK_BODY.iter().map(|value| IoF32(*value).encode()));
+        for i in 0..config.vision_rays {
+            // the same rays as `perceive_vision`
+            let offset = if config.vision_rays > 1 {
+                i as f32 / (config.vision_rays - 1) as f32 - 0.5
+            } else {
+                0.0
+            };
+            let angle = heading + offset * config.vision_fov;
+            look(&mut input, pos, Vec2::new(angle.cos(), angle.sin()), &food);
+        }
+        input.resize(network.inputs(), 0.0);
+        let (output, _) = network.feed_finite(&input);
+        let acceleration = Vec2::new(
+            output.get(0).copied().unwrap_or(0.0),
+            output.get(1).copied().unwrap_or(0.0),
+        );
+
+        vel += acceleration;
+        if vel.length() > BENCHMARK_MAX_SPEED {
+            vel = vel.normalize() * BENCHMARK_MAX_SPEED;
+        }
+        pos = (pos + vel)
+            .max(Vec2::zero())
+            .min(Vec2::splat(BENCHMARK_SIZE));
+    }
+    eaten as f32
+}
+
+/// Puts the inputs of a vision ray of the benchmark agent, like `perceive_vision`.
+/// The border of the arena is seen as a wall, the food as circles of the eat radius.
+fn look(input: &mut Vec<f32>, pos: Vec2, direction: Vec2, food: &[Vec2]) {
+    let axis = |pos: f32, dir: f32| {
+        if dir > 0.0 {
+            (BENCHMARK_SIZE - pos) / dir
+        } else if dir < 0.0 {
+            -pos / dir
+        } else {
+            f32::INFINITY
+        }
+    };
+    let border = axis(pos.x, direction.x)
+        .min(axis(pos.y, direction.y))
+        .max(0.0);
+    let wall = Some(border).filter(|distance| *distance <= VISION_RANGE);
+    let closeness = wall.map_or(0.0, |distance| 1.0 - distance / VISION_RANGE);
+    let visible = wall.unwrap_or(VISION_RANGE);
+    let food = food
+        .iter()
+        .filter_map(|f| {
+            let offset = *f - pos;
+            let distance = offset.dot(direction);
+            let miss = (offset - direction * distance).length();
+            Some(distance).filter(|d| *d >= 0.0 && *d <= visible && miss <= BENCHMARK_EAT_RADIUS)
+        })
+        .min_by(|a, b| a.partial_cmp(b).unwrap())
+        .map_or(0.0, |distance| 1.0 - distance / VISION_RANGE);
+    input.push(IoF32(closeness).encode());
+    input.push(IoBool(false).encode());
+    input.push(IoBool(wall.is_some()).encode());
+    input.push(IoF32(food).encode());
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MutationEffect {
+    Beneficial,
+    Neutral,
+    Deleterious,
+}
+
+impl MutationEffect {
+    fn classify(delta: f32) -> Self {
+        if delta > NEUTRAL_TOLERANCE {
+            Self::Beneficial
+        } else if delta < -NEUTRAL_TOLERANCE {
+            Self::Deleterious
+        } else {
+            Self::Neutral
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Beneficial => "beneficial",
+            Self::Neutral => "neutral",
+            Self::Deleterious => "deleterious",
+        }
+    }
+}
+
+/// The fitness of a genome and of its mutational neighbors.
+pub struct Landscape {
+    pub fitness: f32,
+    pub neighbors: Vec<f32>,
+}
+
+impl Landscape {
+    /// The neighbors mutate the weights with the mutation rate and sigma of the genome
+    /// and the noise of the scenario, like the weights of a child.
+    pub fn probe<R: Rng>(
+        network: &NeuralNetwork,
+        genome: &Genome,
+        noise: &MutationNoise,
+        config: &SimConfig,
+        neighbors: usize,
+        rng: &mut R,
+    ) -> Self {
+        let neighbors = (0..neighbors)
+            .map(|_| {
+                let mut neighbor = network.clone();
+                neighbor.mutate(
+                    rng,
+                    genome.brain.mutation_rate,
+                    genome.brain.mutation_sigma,
+                    noise,
+                );
+                benchmark(&neighbor, config)
+            })
+            .collect();
+        Self {
+            fitness: benchmark(network, config),
+            neighbors,
+        }
+    }
+
+    fn effects(&self) -> impl Iterator<Item = (f32, MutationEffect)> + '_ {
+        self.neighbors.iter().map(move |&fitness| {
+            let delta = fitness - self.fitness;
+            (delta, MutationEffect::classify(delta))
+        })
+    }
+
+    /// Fraction of the neighbors with the given effect.
+    pub fn fraction(&self, effect: MutationEffect) -> f32 {
+        let count = self.effects().filter(|(_, e)| *e == effect).count();
+        count as f32 / self.neighbors.len().max(1) as f32
+    }
+
+    pub fn write_csv<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "neighbor,fitness,delta,effect")?;
+        for (i, (fitness, (delta, effect))) in self.neighbors.iter().zip(self.effects()).enumerate()
+        {
+            writeln!(writer, "{},{},{},{}", i, fitness, delta, effect.name())?;
+        }
+        writer.flush()
+    }
+}
+
+pub struct AnalysisPlugin;
+
+impl Plugin for AnalysisPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_to_stage(stage::POST_UPDATE, probe_landscape.system())
+            .add_system_to_stage(stage::POST_UPDATE, diff_genomes.system());
+    }
+
+    fn name(&self) -> &str {
+        "AnalysisPlugin"
+    }
+}
+
+/// The probe runs on its own thread with its own stream, so the simulation doesn't stall.
+fn probe_landscape(
+    mut reader: Local<EventReader<ConsoleCommand>>,
+    console: Res<Events<ConsoleCommand>>,
+    config: Res<SimConfig>,
+    scenario: Res<Scenario>,
+    mut rng: ResMut<SimRng>,
+) {
+    for command in reader.iter(&console) {
+        if let ConsoleCommand::Landscape {
+            genome,
+            neighbors,
+            output,
+        } = command
+        {
+            let (genome, neighbors, output) = (genome.clone(), *neighbors, output.clone());
+            let (noise, config) = (scenario.mutation, config.clone());
+            let mut stream = rng.stream();
+            thread::spawn(move || {
+                run_probe(&genome, neighbors, &output, &noise, &config, &mut stream)
+            });
+        }
+    }
+}
+
+fn run_probe(
+    path: &Path,
+    neighbors: usize,
+    output: &Path,
+    noise: &MutationNoise,
+    config: &SimConfig,
+    rng: &mut RngStream,
+) {
+    let genome = match Genome::load(path) {
+        Ok(genome) => genome,
+        Err(e) => {
+            error!("failed to load `{}`: {}", path.display(), e);
+            return;
+        }
+    };
+    let network = match &genome.brain.network {
+        Some(network) => network,
+        None => {
+            error!("`{}` has no network", path.display());
+            return;
+        }
+    };
+    let landscape = Landscape::probe(network, &genome, noise, config, neighbors, rng);
+    if let Err(e) = landscape.write_csv(output) {
+        error!("failed to write `{}`: {}", output.display(), e);
+    }
+    info!(
+        "landscape of `{}`: fitness {}, {:.1}% beneficial, {:.1}% neutral, {:.1}% deleterious, written to `{}`",
+        path.display(),
+        landscape.fitness,
+        landscape.fraction(MutationEffect::Beneficial) * 100.0,
+        landscape.fraction(MutationEffect::Neutral) * 100.0,
+        landscape.fraction(MutationEffect::Deleterious) * 100.0,
+        output.display()
+    );
+}
+
+fn diff_genomes(
+    mut reader: Local<EventReader<ConsoleCommand>>,
+    console: Res<Events<ConsoleCommand>>,
+) {
+    for command in reader.iter(&console) {
+        if let ConsoleCommand::Diff(a, b) = command {
+            match (Genome::load(a), Genome::load(b)) {
+                (Ok(genome_a), Ok(genome_b)) => {
+                    info!(
+                        "`{}` -> `{}`\n{}",
+                        a.display(),
+                        b.display(),
+                        genome_a.diff(&genome_b)
+                    )
+                }
+                (Err(e), _) => error!("failed to load `{}`: {}", a.display(), e),
+                (_, Err(e)) => error!("failed to load `{}`: {}", b.display(), e),
+            }
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use crate::{
+    console::ConsoleCommand,
+    corgi::{self, Corgi, CORGI_ENERGY_SPAWNED},
+    debug::EnergyLedger,
+    genes::{Alleles, Genome, Genotype, SenseGene},
+    intelligence::Brain,
+    lineage::LineageLog,
+    loader::MyAssets,
+    rng::SimRng,
+    universe::{Tick, Universe},
+};
+use bevy::prelude::*;
+use rand::seq::IteratorRandom;
+use std::{collections::BTreeMap, fs, path::Path};
+
+/// Ticks between two samples.
+const ARCHIVE_INTERVAL: usize = 1000;
+/// Maximum number of genomes per sample.
+const ARCHIVE_SAMPLE_SIZE: usize = 10;
+
+/// Marks corgis spawned from the archive, holds the tick they were archived at.
+pub struct Resurrected(pub usize);
+
+/// The whole genome of an archived corgi, diploid ones keep both their alleles next to it.
+#[derive(Clone, Debug)]
+pub struct ArchivedGenome {
+    pub genome: Genome,
+    pub alleles: Option<Alleles>,
+}
+
+/// Periodic samples of the living genomes, so old genomes can compete against their descendants.
+#[derive(Default)]
+pub struct GenomeArchive {
+    samples: BTreeMap<usize, Vec<ArchivedGenome>>,
+}
+
+impl GenomeArchive {
+    /// Returns the latest sample taken at or before `tick`.
+    pub fn sample_at(&self, tick: usize) -> Option<(usize, &[ArchivedGenome])> {
+        self.samples
+            .range(..=tick)
+            .next_back()
+            .map(|(tick, sample)| (*tick, sample.as_slice()))
+    }
+}
+
+/// Genomes of a previous run, the population is spawned from mutated copies of them.
+/// Empty for a run from scratch.
+#[derive(Default)]
+pub struct SeedGenomes(pub Vec<Genome>);
+
+impl SeedGenomes {
+    /// Loads every `.ron` genome file in `dir`.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Self, String> {
+        let dir = dir.as_ref();
+        let entries =
+            fs::read_dir(dir).map_err(|e| format!("failed to read `{}`: {}", dir.display(), e))?;
+        let mut genomes = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            if path
+                .extension()
+                .map_or(true, |extension| extension != "ron")
+            {
+                continue;
+            }
+            let genome = Genome::load(&path)
+                .map_err(|e| format!("failed to load `{}`: {}", path.display(), e))?;
+            genomes.push(genome);
+        }
+        if genomes.is_empty() {
+            return Err(format!("no genomes in `{}`", dir.display()));
+        }
+        Ok(Self(genomes))
+    }
+}
+
+pub struct ArchivePlugin;
+
+impl Plugin for ArchivePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(GenomeArchive::default())
+            .add_system(archive_genomes.system())
+            .add_system_to_stage(stage::POST_UPDATE, resurrect_genomes.system());
+    }
+
+    fn name(&self) -> &str {
+        "ArchivePlugin"
+    }
+}
+
+fn archive_genomes(
+    tick: Res<Tick>,
+    mut archive: ResMut<GenomeArchive>,
+    mut rng: ResMut<SimRng>,
+    query: Query<(&Brain, &SenseGene, &Genotype, Option<&Alleles>), With<Corgi>>,
+) {
+    if tick.0 % ARCHIVE_INTERVAL != 0 {
+        return;
+    }
+    let sample = query
+        .iter()
+        .filter(|(brain, ..)| !brain.is_dry() && brain.network().is_some())
+        .choose_multiple(&mut *rng, ARCHIVE_SAMPLE_SIZE)
+        .into_iter()
+        .map(|(brain, senses, genotype, alleles)| ArchivedGenome {
+            genome: Genome::from_parts(brain.gene(), senses, genotype),
+            alleles: alleles.cloned(),
+        })
+        .collect::<Vec<_>>();
+    if !sample.is_empty() {
+        archive.samples.insert(tick.0, sample);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn resurrect_genomes(
+    commands: &mut Commands,
+    archive: Res<GenomeArchive>,
+    mut reader: Local<EventReader<ConsoleCommand>>,
+    console: Res<Events<ConsoleCommand>>,
+    universe: Res<Universe>,
+    assets: Res<MyAssets>,
+    ledger: Res<EnergyLedger>,
+    mut log: ResMut<LineageLog>,
+    mut rng: ResMut<SimRng>,
+) {
+    for command in reader.iter(&console) {
+        let tick = match command {
+            ConsoleCommand::Resurrect(tick) => *tick,
+            _ => continue,
+        };
+        match archive.sample_at(tick) {
+            Some((archived, sample)) => {
+                for archived_genome in sample {
+                    let ArchivedGenome {
+                        genome,
+                        mut alleles,
+                    } = archived_genome.clone();
+                    if let (Some(alleles), Some(network)) = (&mut alleles, &genome.brain.network) {
+                        alleles.adopt_network(network);
+                    }
+                    let entity = corgi::spawn_with_genome(
+                        commands, genome, &universe, &assets, &mut log, &mut rng,
+                    );
+                    commands.insert_one(entity, Resurrected(archived));
+                    if let Some(alleles) = alleles {
+                        commands.insert_one(entity, alleles);
+                    }
+                    ledger.source(CORGI_ENERGY_SPAWNED);
+                }
+                info!(
+                    "resurrected {} genomes from tick {}",
+                    sample.len(),
+                    archived
+                );
+            }
+            None => warn!("no genomes archived at or before tick {}", tick),
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use crate::{
+    console::ConsoleCommand,
+    corgi::{Corgi, Energy, Load},
+    corpse::{self, DeathCause, Died},
+    debug::EnergyLedger,
+    loader::MyAssets,
+    rng::SimRng,
+    universe::{scenario::Scenario, tile::TileGrid, Tick, Universe},
+};
+use bevy::prelude::*;
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+/// Radius in tiles of the random meteors.
+const RANDOM_METEOR_RADIUS: f32 = 3.0;
+/// Fraction of the corgis a random plague kills.
+const RANDOM_PLAGUE_FRACTION: f32 = 0.3;
+
+/// A disaster which resets the local equilibrium.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Catastrophe {
+    /// Sterilizes the tiles in a disc and kills the corgis in it.
+    /// The center is in tiles, a random one if `None`.
+    Meteor {
+        center: Option<(f32, f32)>,
+        radius: f32,
+    },
+    /// Kills a random fraction of all corgis.
+    Plague { fraction: f32 },
+}
+
+impl Catastrophe {
+    fn random<R: Rng>(rng: &mut R) -> Self {
+        if rng.gen() {
+            Self::Meteor {
+                center: None,
+                radius: RANDOM_METEOR_RADIUS,
+            }
+        } else {
+            Self::Plague {
+                fraction: RANDOM_PLAGUE_FRACTION,
+            }
+        }
+    }
+}
+
+/// A catastrophe striking at a fixed tick, for reproducible experiments.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledCatastrophe {
+    pub tick: usize,
+    pub catastrophe: Catastrophe,
+}
+
+pub struct CatastrophePlugin;
+
+impl Plugin for CatastrophePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(strike_catastrophes.system());
+    }
+
+    fn name(&self) -> &str {
+        "CatastrophePlugin"
+    }
+}
+
+/// Strikes the scheduled catastrophes of the scenario, the random ones and those from the console.
+#[allow(clippy::too_many_arguments)]
+fn strike_catastrophes(
+    commands: &mut Commands,
+    tick: Res<Tick>,
+    assets: Res<MyAssets>,
+    scenario: Res<Scenario>,
+    universe: Res<Universe>,
+    mut grid: ResMut<TileGrid>,
+    mut deaths: ResMut<Events<Died>>,
+    mut reader: Local<EventReader<ConsoleCommand>>,
+    console: Res<Events<ConsoleCommand>>,
+    ledger: Res<EnergyLedger>,
+    corgis: Query<(Entity, &Transform, &Energy, &Load), With<Corgi>>,
+    mut rng: ResMut<SimRng>,
+) {
+    let rng = &mut *rng;
+    let mut catastrophes: Vec<Catastrophe> = scenario
+        .catastrophes
+        .iter()
+        .filter(|scheduled| scheduled.tick == tick.0)
+        .map(|scheduled| scheduled.catastrophe)
+        .collect();
+    if let Some(interval) = scenario.catastrophe_interval {
+        if rng.gen_bool(1.0 / interval.max(1) as f64) {
+            catastrophes.push(Catastrophe::random(rng));
+        }
+    }
+    catastrophes.extend(reader.iter(&console).filter_map(|command| match command {
+        ConsoleCommand::Catastrophe(catastrophe) => Some(*catastrophe),
+        _ => None,
+    }));
+
+    for catastrophe in catastrophes {
+        match catastrophe {
+            Catastrophe::Meteor { center, radius } => {
+                let center = match center {
+                    Some((x, y)) => Vec2::new(x, y) * universe.tile_size,
+                    None => Vec2::new(rng.gen(), rng.gen()) * universe.size(),
+                };
+                let radius = radius * universe.tile_size;
+                let hit = |pos: Vec2| universe.offset(center, pos).length() <= radius;
+                for pos in universe.tiles_in_radius(center, radius) {
+                    if let Some(tile) = grid.get_mut(pos) {
+                        ledger.sink(tile.energy);
+                        tile.sterilize();
+                    }
+                }
+                let mut killed = 0;
+                for (entity, transform, energy, load) in corgis.iter() {
+                    let pos = transform.translation.truncate();
+                    if hit(pos) {
+                        corpse::kill(
+                            commands,
+                            &assets,
+                            &mut deaths,
+                            entity,
+                            pos,
+                            corpse::remains(energy, load),
+                            DeathCause::Meteor,
+                        );
+                        killed += 1;
+                    }
+                }
+                info!(
+                    "meteor struck at ({:.1}, {:.1}), {} corgis killed",
+                    center.x / universe.tile_size,
+                    center.y / universe.tile_size,
+                    killed
+                );
+            }
+            Catastrophe::Plague { fraction } => {
+                let victims: Vec<_> = corgis.iter().collect();
+                let count = (victims.len() as f32 * fraction.max(0.0).min(1.0)).round() as usize;
+                for (entity, transform, energy, load) in victims.choose_multiple(rng, count) {
+                    let pos = transform.translation.truncate();
+                    corpse::kill(
+                        commands,
+                        &assets,
+                        &mut deaths,
+                        *entity,
+                        pos,
+                        corpse::remains(energy, load),
+                        DeathCause::Plague,
+                    );
+                }
+                info!("plague struck, {} corgis killed", count);
+            }
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{
+    config::SimConfig,
+    genes::Genome,
+    intelligence::{self, perception::Oscillators},
+    universe::scenario::Scenario,
+};
+use std::path::PathBuf;
+
+/// The files to check with `corgis check`.
+#[derive(Clone, Debug, Default)]
+pub struct CheckOptions {
+    pub config: Option<PathBuf>,
+    pub scenario: Option<PathBuf>,
+    pub genomes: Vec<PathBuf>,
+}
+
+/// Validates the files and prints the effective configuration, without starting a simulation.
+/// Returns whether everything is fine.
+pub fn run(options: &CheckOptions) -> bool {
+    let mut ok = true;
+    let mut fail = |message: String| {
+        eprintln!("error: {}", message);
+        ok = false;
+    };
+
+    let config = match &options.config {
+        Some(path) => SimConfig::load(path).unwrap_or_else(|e| {
+            fail(e);
+            SimConfig::default()
+        }),
+        None => SimConfig::default(),
+    };
+    for problem in config.problems() {
+        fail(problem);
+    }
+
+    let scenario = match &options.scenario {
+        Some(path) => Scenario::load(path).unwrap_or_else(|e| {
+            fail(e);
+            Scenario::default()
+        }),
+        None => Scenario::default(),
+    };
+    for problem in scenario.problems() {
+        fail(problem);
+    }
+
+    let layout = intelligence::expected_layout(&Oscillators::default(), &config);
+    for path in &options.genomes {
+        let genome = match Genome::load(path) {
+            Ok(genome) => genome,
+            Err(e) => {
+                fail(format!("failed to load `{}`: {}", path.display(), e));
+                continue;
+            }
+        };
+        match &genome.brain.network {
+            Some(network)
+                if network.inputs() != layout.inputs
+                    || network.outputs() != layout.output_len() =>
+            {
+                fail(format!(
+                    "`{}` has shape {:?}, but the brains need {} inputs and {} outputs",
+                    path.display(),
+                    network.shape(),
+                    layout.inputs,
+                    layout.output_len()
+                ))
+            }
+            Some(network) => println!("genome `{}`: shape {:?}", path.display(), network.shape()),
+            None => println!(
+                "genome `{}`: no network, a random one will be created",
+                path.display()
+            ),
+        }
+    }
+
+    println!(
+        "brain layout: {} inputs, outputs {:?}",
+        layout.inputs, layout.outputs
+    );
+    match ron::ser::to_string_pretty(&config, ron::ser::PrettyConfig::default()) {
+        Ok(string) => println!("config:\n{}", string),
+        Err(e) => fail(format!("failed to serialize the config: {}", e)),
+    }
+    match ron::ser::to_string_pretty(&scenario, ron::ser::PrettyConfig::default()) {
+        Ok(string) => println!("scenario:\n{}", string),
+        Err(e) => fail(format!("failed to serialize the scenario: {}", e)),
+    }
+    ok
+}
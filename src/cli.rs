@@ -0,0 +1,81 @@
+use corgis::{check::CheckOptions, headless::HeadlessRun};
+use std::{env, path::PathBuf, str::FromStr};
+
+const USAGE: &str = "usage:
+    corgis [--seed-genomes <dir>] [--config <file>] [--seed <n>]
+           [--headless [--ticks <n>] [--stats <file>]]
+    corgis check [--config <file>] [--scenario <file>] [--genome <file>]...";
+
+/// What to do.
+#[derive(Clone, Debug)]
+pub enum Command {
+    Run,
+    /// Validates the files and exits.
+    Check(CheckOptions),
+}
+
+impl Default for Command {
+    fn default() -> Self {
+        Self::Run
+    }
+}
+
+/// The command line options.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub command: Command,
+    /// Directory with genomes of a previous run to seed the population with.
+    pub seed_genomes: Option<PathBuf>,
+    /// RON file with the `SimConfig`, the defaults if `None`.
+    pub config: Option<PathBuf>,
+    /// Seed of the random number generator, overrides the one of the config.
+    pub seed: Option<u64>,
+    /// Runs without a window if `Some`.
+    pub headless: Option<HeadlessRun>,
+}
+
+impl Options {
+    pub fn from_args() -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut args = env::args().skip(1).peekable();
+        if args.peek().map(String::as_str) == Some("check") {
+            args.next();
+            options.command = Command::Check(CheckOptions::default());
+        }
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .map(PathBuf::from)
+                    .ok_or_else(|| USAGE.to_string())
+            };
+            match (&mut options.command, arg.as_str()) {
+                (Command::Run, "--seed-genomes") => options.seed_genomes = Some(value()?),
+                (Command::Run, "--config") => options.config = Some(value()?),
+                (Command::Run, "--seed") => options.seed = Some(number(value()?)?),
+                (Command::Run, "--headless") => options.headless = Some(HeadlessRun::default()),
+                (Command::Run, "--ticks") => {
+                    headless(&mut options.headless)?.ticks = Some(number(value()?)?);
+                }
+                (Command::Run, "--stats") => {
+                    headless(&mut options.headless)?.stats = Some(value()?);
+                }
+                (Command::Check(check), "--config") => check.config = Some(value()?),
+                (Command::Check(check), "--scenario") => check.scenario = Some(value()?),
+                (Command::Check(check), "--genome") => check.genomes.push(value()?),
+                _ => return Err(format!("unknown argument `{}`\n{}", arg, USAGE)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// The headless run the option belongs to, which has to come after `--headless`.
+fn headless(run: &mut Option<HeadlessRun>) -> Result<&mut HeadlessRun, String> {
+    run.as_mut()
+        .ok_or_else(|| format!("`--headless` is missing\n{}", USAGE))
+}
+
+fn number<T: FromStr>(value: PathBuf) -> Result<T, String> {
+    let number = value.to_str().and_then(|value| value.parse().ok());
+    number.ok_or_else(|| format!("invalid number `{}`\n{}", value.display(), USAGE))
+}
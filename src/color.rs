@@ -0,0 +1,125 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Detection probability of a corgi with exactly the hue of its tile.
+const CAMOUFLAGE_MIN_VISIBILITY: f32 = 0.1;
+
+/// The color a corgi currently shows, decided by its brain.
+#[derive(Clone, Copy, Debug)]
+pub struct DisplayColor(pub Color);
+
+impl Default for DisplayColor {
+    fn default() -> Self {
+        Self(Color::BLACK)
+    }
+}
+
+/// What the color of a corgi means to the others.
+/// Several mechanics compete for the single color output of the brain,
+/// the scenario picks one of them.
+pub trait ColorSemantics: Send + Sync {
+    /// The color of `color` as perceived by a corgi showing `observer`.
+    fn perceived(&self, color: Color, _observer: Color) -> Color {
+        color
+    }
+
+    /// Probability of a corgi showing `color` on a tile of color `background` to be detected.
+    fn visibility(&self, _color: Color, _background: Color) -> f32 {
+        1.0
+    }
+}
+
+/// The color has no effect, others just see it.
+pub struct PureDisplay;
+
+impl ColorSemantics for PureDisplay {}
+
+/// The color marks membership in a team.
+/// Every channel is perceived as its similarity to the one of the observer,
+/// so white means the same team.
+pub struct TeamSignal;
+
+impl ColorSemantics for TeamSignal {
+    fn perceived(&self, color: Color, observer: Color) -> Color {
+        let similarity = |a: f32, b: f32| 1.0 - (a - b).abs().min(1.0);
+        Color::rgb_linear(
+            similarity(color.r(), observer.r()),
+            similarity(color.g(), observer.g()),
+            similarity(color.b(), observer.b()),
+        )
+    }
+}
+
+/// Corgis with a hue close to the one of the tile beneath them are hard to detect.
+pub struct Camouflage;
+
+impl ColorSemantics for Camouflage {
+    fn visibility(&self, color: Color, background: Color) -> f32 {
+        let distance = (hue(color) - hue(background)).abs();
+        // the hue is circular, so the maximum distance is one half
+        let distance = distance.min(1.0 - distance) * 2.0;
+        CAMOUFLAGE_MIN_VISIBILITY + (1.0 - CAMOUFLAGE_MIN_VISIBILITY) * distance
+    }
+}
+
+/// The hue of the color, between 0 and 1.
+/// Grays have a hue of 0.
+pub fn hue(color: Color) -> f32 {
+    let (r, g, b) = (color.r(), color.g(), color.b());
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    if delta <= 0.0 {
+        return 0.0;
+    }
+    let sector = if max == r {
+        ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / delta + 2.0
+    } else {
+        (r - g) / delta + 4.0
+    };
+    sector / 6.0
+}
+
+/// Hue, saturation and value of the color, all between 0 and 1.
+pub fn hsv(color: Color) -> [f32; 3] {
+    let (r, g, b) = (color.r(), color.g(), color.b());
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let saturation = if max > 0.0 { (max - min) / max } else { 0.0 };
+    [hue(color), saturation, max]
+}
+
+/// The choice of the color semantics in a scenario file.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ColorSemanticsKind {
+    PureDisplay,
+    TeamSignal,
+    Camouflage,
+}
+
+impl Default for ColorSemanticsKind {
+    fn default() -> Self {
+        Self::PureDisplay
+    }
+}
+
+impl ColorSemanticsKind {
+    pub fn build(self) -> Box<dyn ColorSemantics> {
+        match self {
+            Self::PureDisplay => Box::new(PureDisplay),
+            Self::TeamSignal => Box::new(TeamSignal),
+            Self::Camouflage => Box::new(Camouflage),
+        }
+    }
+}
+
+/// Holds the color semantics of the current scenario.
+pub struct ActiveColorSemantics(pub Box<dyn ColorSemantics>);
+
+impl Default for ActiveColorSemantics {
+    fn default() -> Self {
+        Self(ColorSemanticsKind::default().build())
+    }
+}
@@ -0,0 +1,144 @@
+use crate::{state::SimulationSpeed, universe::DEFAULT_TILE_SIZE};
+use serde::{Deserialize, Serialize};
+use std::{f32::consts::TAU, fs, path::Path};
+
+/// The constants experiments tune, loaded from a RON file at startup with `--config`.
+/// Unlike the scenario, the config can't be switched while running.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimConfig {
+    /// The brains only run their network every `n` ticks and reuse their last decisions in between.
+    /// This trades reaction time for speed with big populations.
+    pub think_interval: usize,
+    /// A corgi needs at least this much energy to reproduce.
+    pub reproduction_min_energy: f32,
+    /// Maximum distance between two mates.
+    pub mating_range: f32,
+    /// Ticks from the conception to laying the egg.
+    pub gestation_ticks: usize,
+    /// Ticks from laying the egg to hatching.
+    pub incubation_ticks: usize,
+    pub dash_energy_cost: f32,
+    /// Energy per tick of a full photosynthesis gene at noon.
+    pub photosynthesis_energy: f32,
+    /// Food energy a corgi eats per tick from the tile it stands on.
+    pub food_bite: f32,
+    /// Growth rate of the logistic regrowth of the food per tick.
+    pub food_regrowth_rate: f32,
+    /// Side length of a tile in pixels of the initial universe, later ones keep it.
+    pub tile_size: f32,
+    /// Size of the initial universe in tiles, the one of the scenario if `None`.
+    pub width_tiles: Option<usize>,
+    pub height_tiles: Option<usize>,
+    /// Number of memory cells the brain writes and reads back in the next tick.
+    /// Changes the brain layout, so genomes only fit configs with the same memory size.
+    pub memory_size: usize,
+    /// Number of rays of the vision, spread evenly over the field of view.
+    /// Changes the brain layout like the memory size.
+    pub vision_rays: usize,
+    /// Field of view of the vision in radians, centered around the heading.
+    pub vision_fov: f32,
+    /// Ticks per rendered frame at the start, changed with the number keys.
+    pub speed: SimulationSpeed,
+    /// Seed of the random number generator, a random one if `None`.
+    pub seed: Option<u64>,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            think_interval: 1,
+            reproduction_min_energy: 150.0,
+            mating_range: 40.0,
+            gestation_ticks: 300,
+            incubation_ticks: 600,
+            dash_energy_cost: 20.0,
+            photosynthesis_energy: 0.2,
+            food_bite: 0.5,
+            food_regrowth_rate: 0.01,
+            tile_size: DEFAULT_TILE_SIZE,
+            width_tiles: None,
+            height_tiles: None,
+            memory_size: 0,
+            vision_rays: 5,
+            vision_fov: std::f32::consts::FRAC_PI_2,
+            speed: SimulationSpeed::default(),
+            seed: None,
+        }
+    }
+}
+
+impl SimConfig {
+    /// Values which load fine, but make no sense.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.think_interval == 0 {
+            problems.push("think_interval has to be at least 1".to_string());
+        }
+        if self.speed == SimulationSpeed::Times(0) {
+            problems.push("speed has to be at least 1 tick per frame".to_string());
+        }
+        if self.width_tiles == Some(0) || self.height_tiles == Some(0) {
+            problems.push("the universe needs at least one tile in each direction".to_string());
+        }
+        if !(self.tile_size.is_finite() && self.tile_size > 0.0) {
+            problems.push(format!(
+                "tile_size {} is not a positive number",
+                self.tile_size
+            ));
+        }
+        if !(0.0..=TAU).contains(&self.vision_fov) {
+            problems.push(format!(
+                "vision_fov {} is not between 0 and a full turn",
+                self.vision_fov
+            ));
+        }
+        let values = [
+            ("reproduction_min_energy", self.reproduction_min_energy),
+            ("mating_range", self.mating_range),
+            ("dash_energy_cost", self.dash_energy_cost),
+            ("photosynthesis_energy", self.photosynthesis_energy),
+            ("food_bite", self.food_bite),
+            ("food_regrowth_rate", self.food_regrowth_rate),
+        ];
+        for (name, value) in values.iter() {
+            if value.is_nan() || *value < 0.0 {
+                problems.push(format!("{} {} is not a non-negative number", name, value));
+            }
+        }
+        problems
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let string = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read `{}`: {}", path.display(), e))?;
+        ron::de::from_str(&string)
+            .map_err(|e| format!("invalid config `{}`: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_default_config_has_no_problems() {
+        assert!(SimConfig::default().problems().is_empty());
+    }
+
+    #[test]
+    fn every_nonsensical_value_is_a_problem() {
+        let config = SimConfig {
+            think_interval: 0,
+            speed: SimulationSpeed::Times(0),
+            width_tiles: Some(0),
+            tile_size: f32::NAN,
+            vision_fov: -1.0,
+            mating_range: -5.0,
+            food_bite: f32::NAN,
+            ..Default::default()
+        };
+        assert_eq!(config.problems().len(), 7);
+    }
+}
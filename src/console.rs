@@ -0,0 +1,302 @@
+use crate::{
+    catastrophe::Catastrophe, lineage::LineageId, selection::PolicyKind, universe::Boundary,
+};
+use bevy::prelude::*;
+use std::{
+    io::{self, BufRead},
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        mpsc::{self, Receiver},
+        Mutex,
+    },
+    thread,
+};
+
+const DEFAULT_LANDSCAPE_OUTPUT: &str = "landscape.csv";
+
+/// Reads commands from stdin and sends them as `ConsoleCommand` events.
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let stdin = io::stdin();
+            for line in stdin.lock().lines() {
+                match line {
+                    Ok(line) if sender.send(line).is_ok() => {}
+                    _ => break,
+                }
+            }
+        });
+
+        app.add_event::<ConsoleCommand>()
+            .add_resource(ConsoleInput(Mutex::new(receiver)))
+            .add_system_to_stage(stage::POST_UPDATE, read_console.system());
+    }
+
+    fn name(&self) -> &str {
+        "ConsolePlugin"
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ConsoleCommand {
+    /// Spawns a corgi with the weights from the given file.
+    Import(PathBuf),
+    /// Finds the most recent common ancestor of two lineages,
+    /// or of the two corgis selected in the inspector.
+    Mrca(Option<(LineageId, LineageId)>),
+    /// Lists the living descendants of a lineage.
+    Descendants(LineageId),
+    /// Spawns the genomes archived at the given tick.
+    Resurrect(usize),
+    /// Evaluates mutational neighbors of the genome and writes their fitness to `output`.
+    Landscape {
+        genome: PathBuf,
+        neighbors: usize,
+        output: PathBuf,
+    },
+    /// Replaces the universe with the scenario from the given file.
+    Scenario(PathBuf),
+    /// Saves the whole world, including the tiles and the scent, to a RON file.
+    SaveSnapshot(PathBuf),
+    /// Replaces the world with a saved snapshot.
+    LoadSnapshot(PathBuf),
+    /// Regenerates the universe with the given size in tiles.
+    Resize { width: usize, height: usize },
+    /// Makes the universe bounded with the given boundary, or toroidal if `None`.
+    Boundary(Option<Boundary>),
+    /// Exports the tile fields every `interval` ticks, stops the export if `None`.
+    Export {
+        interval: Option<usize>,
+        dir: Option<PathBuf>,
+    },
+    /// Prints the differences between two genome files.
+    Diff(PathBuf, PathBuf),
+    /// Writes the region statistics as CSV.
+    Regions(PathBuf),
+    /// Replaces the selection policy.
+    Selection(PolicyKind),
+    /// Strikes a catastrophe immediately.
+    Catastrophe(Catastrophe),
+    /// Prints the number of tiles owned by each team.
+    Territory,
+    /// Renders the corgis in the color of their species.
+    SpeciesColors(bool),
+    /// Toggles the debug checks and optionally sets the ticks between their runs.
+    Debug {
+        enabled: bool,
+        interval: Option<usize>,
+    },
+}
+
+impl FromStr for ConsoleCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let command = words.next().ok_or_else(|| "empty command".to_string())?;
+        let args: Vec<&str> = words.collect();
+        match (command, args.as_slice()) {
+            ("import", [path]) => Ok(Self::Import(PathBuf::from(path))),
+            ("import", _) => Err("usage: import <path>".to_string()),
+            ("mrca", []) => Ok(Self::Mrca(None)),
+            ("mrca", [a, b]) => Ok(Self::Mrca(Some((parse_lineage(a)?, parse_lineage(b)?)))),
+            ("mrca", _) => Err("usage: mrca [<lineage> <lineage>]".to_string()),
+            ("descendants", [id]) => parse_lineage(id).map(Self::Descendants),
+            ("descendants", _) => Err("usage: descendants <lineage>".to_string()),
+            ("resurrect", [tick]) => tick
+                .parse()
+                .map(Self::Resurrect)
+                .map_err(|_| format!("invalid tick `{}`", tick)),
+            ("resurrect", _) => Err("usage: resurrect <tick>".to_string()),
+            ("landscape", [genome, neighbors, rest @ ..]) if rest.len() <= 1 => {
+                Ok(Self::Landscape {
+                    genome: PathBuf::from(genome),
+                    neighbors: neighbors
+                        .parse()
+                        .map_err(|_| format!("invalid neighbor count `{}`", neighbors))?,
+                    output: PathBuf::from(rest.first().unwrap_or(&DEFAULT_LANDSCAPE_OUTPUT)),
+                })
+            }
+            ("landscape", _) => Err("usage: landscape <genome> <neighbors> [<csv>]".to_string()),
+            ("scenario", [path]) => Ok(Self::Scenario(PathBuf::from(path))),
+            ("scenario", _) => Err("usage: scenario <path>".to_string()),
+            ("snapshot", ["save", path]) => Ok(Self::SaveSnapshot(PathBuf::from(path))),
+            ("snapshot", ["load", path]) => Ok(Self::LoadSnapshot(PathBuf::from(path))),
+            ("snapshot", _) => Err("usage: snapshot save|load <path>".to_string()),
+            ("resize", [width, height]) => {
+                let size = |s: &str| match s.parse() {
+                    Ok(size) if size > 0 => Ok(size),
+                    _ => Err(format!("invalid size `{}`", s)),
+                };
+                Ok(Self::Resize {
+                    width: size(width)?,
+                    height: size(height)?,
+                })
+            }
+            ("resize", _) => Err("usage: resize <width> <height>".to_string()),
+            ("boundary", ["bounce"]) => Ok(Self::Boundary(Some(Boundary::Bounce))),
+            ("boundary", ["repel"]) => Ok(Self::Boundary(Some(Boundary::Repel))),
+            ("boundary", ["death"]) => Ok(Self::Boundary(Some(Boundary::Death))),
+            ("boundary", ["wrap"]) => Ok(Self::Boundary(None)),
+            ("boundary", _) => Err("usage: boundary bounce|repel|death|wrap".to_string()),
+            ("debug", [state, rest @ ..]) if rest.len() <= 1 => Ok(Self::Debug {
+                enabled: match *state {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err(format!("invalid debug state `{}`", state)),
+                },
+                interval: rest
+                    .first()
+                    .map(|interval| {
+                        interval
+                            .parse()
+                            .map_err(|_| format!("invalid interval `{}`", interval))
+                    })
+                    .transpose()?,
+            }),
+            ("debug", _) => Err("usage: debug on|off [<interval>]".to_string()),
+            ("meteor", [radius, rest @ ..]) if rest.is_empty() || rest.len() == 2 => {
+                let number = |s: &str| s.parse().map_err(|_| format!("invalid number `{}`", s));
+                Ok(Self::Catastrophe(Catastrophe::Meteor {
+                    center: match rest {
+                        [x, y] => Some((number(x)?, number(y)?)),
+                        _ => None,
+                    },
+                    radius: number(radius)?,
+                }))
+            }
+            ("meteor", _) => Err("usage: meteor <radius> [<x> <y>]".to_string()),
+            ("plague", [fraction]) => fraction
+                .parse()
+                .map(|fraction| Self::Catastrophe(Catastrophe::Plague { fraction }))
+                .map_err(|_| format!("invalid fraction `{}`", fraction)),
+            ("plague", _) => Err("usage: plague <fraction>".to_string()),
+            ("territory", []) => Ok(Self::Territory),
+            ("territory", _) => Err("usage: territory".to_string()),
+            ("species", ["on"]) => Ok(Self::SpeciesColors(true)),
+            ("species", ["off"]) => Ok(Self::SpeciesColors(false)),
+            ("species", _) => Err("usage: species on|off".to_string()),
+            ("export", ["off"]) => Ok(Self::Export {
+                interval: None,
+                dir: None,
+            }),
+            ("export", [interval, rest @ ..]) if rest.len() <= 1 => Ok(Self::Export {
+                interval: Some(
+                    interval
+                        .parse()
+                        .map_err(|_| format!("invalid interval `{}`", interval))?,
+                ),
+                dir: rest.first().map(PathBuf::from),
+            }),
+            ("export", _) => Err("usage: export <interval> [<dir>] | export off".to_string()),
+            ("diff", [a, b]) => Ok(Self::Diff(PathBuf::from(a), PathBuf::from(b))),
+            ("diff", _) => Err("usage: diff <genome> <genome>".to_string()),
+            ("regions", [path]) => Ok(Self::Regions(PathBuf::from(path))),
+            ("regions", _) => Err("usage: regions <csv>".to_string()),
+            ("selection", ["natural"]) => Ok(Self::Selection(PolicyKind::Natural)),
+            ("selection", ["tournament", size]) => size
+                .parse()
+                .map(|size| Self::Selection(PolicyKind::Tournament(size)))
+                .map_err(|_| format!("invalid tournament size `{}`", size)),
+            ("selection", ["elitism", fraction]) => fraction
+                .parse()
+                .map(|fraction| Self::Selection(PolicyKind::Elitism(fraction)))
+                .map_err(|_| format!("invalid elite fraction `{}`", fraction)),
+            ("selection", _) => {
+                Err("usage: selection natural|tournament <size>|elitism <fraction>".to_string())
+            }
+            _ => Err(format!("unknown command `{}`", command)),
+        }
+    }
+}
+
+fn parse_lineage(s: &str) -> Result<LineageId, String> {
+    s.trim_start_matches('#')
+        .parse()
+        .map(LineageId)
+        .map_err(|_| format!("invalid lineage id `{}`", s))
+}
+
+// the receiver isn't `Sync`
+struct ConsoleInput(Mutex<Receiver<String>>);
+
+fn read_console(input: Res<ConsoleInput>, mut commands: ResMut<Events<ConsoleCommand>>) {
+    let receiver = input.0.lock().unwrap();
+    for line in receiver.try_iter() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match line.parse() {
+            Ok(command) => commands.send(command),
+            Err(e) => warn!("{}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<ConsoleCommand, String> {
+        s.parse()
+    }
+
+    #[test]
+    fn commands_are_parsed_with_their_arguments() {
+        assert!(matches!(
+            parse("mrca #3 4"),
+            Ok(ConsoleCommand::Mrca(Some((LineageId(3), LineageId(4)))))
+        ));
+        assert!(matches!(parse("mrca"), Ok(ConsoleCommand::Mrca(None))));
+        assert!(matches!(
+            parse("  resize 40   30 "),
+            Ok(ConsoleCommand::Resize {
+                width: 40,
+                height: 30
+            })
+        ));
+        assert!(matches!(
+            parse("boundary wrap"),
+            Ok(ConsoleCommand::Boundary(None))
+        ));
+        assert!(matches!(
+            parse("debug on 10"),
+            Ok(ConsoleCommand::Debug {
+                enabled: true,
+                interval: Some(10)
+            })
+        ));
+        assert!(matches!(
+            parse("meteor 5 1 2"),
+            Ok(ConsoleCommand::Catastrophe(Catastrophe::Meteor {
+                center: Some(_),
+                ..
+            }))
+        ));
+        match parse("landscape genome.ron 20") {
+            Ok(ConsoleCommand::Landscape {
+                neighbors, output, ..
+            }) => {
+                assert_eq!(neighbors, 20);
+                assert_eq!(output, PathBuf::from(DEFAULT_LANDSCAPE_OUTPUT));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn invalid_commands_are_rejected() {
+        assert!(parse("").is_err());
+        assert!(parse("bark").is_err());
+        assert!(parse("import").is_err());
+        assert!(parse("resize 0 10").is_err());
+        assert!(parse("resurrect soon").is_err());
+        assert!(parse("debug maybe").is_err());
+        assert!(parse("meteor 5 1").is_err());
+        assert!(parse("descendants #x").is_err());
+    }
+}
@@ -1,19 +1,244 @@
 use crate::{
-    intelligence::IntelligenceBundle,
+    archive::SeedGenomes,
+    color::DisplayColor,
+    console::ConsoleCommand,
+    corpse::DeathCause,
+    debug::EnergyLedger,
+    genes::{Alleles, BodyGene, Environment, Genome, Genotype, MetabolismGene, SenseGene},
+    intelligence::{decision::DashCooldown, Brain, IntelligenceBundle},
+    lineage::{Lineage, LineageLog},
     loader::MyAssets,
-    universe::{UNIVERSE_HEIGHT, UNIVERSE_WIDTH},
+    parasite::Infestation,
+    rng::{RngStream, SimRng},
+    stats::RegionStats,
+    universe::{
+        scenario::{Genetics, Scenario},
+        tile::OnTile,
+        Daylight, Universe,
+    },
 };
 use bevy::prelude::*;
-use bevy_rapier2d::rapier::{dynamics::RigidBodyBuilder, geometry::ColliderBuilder};
-use rand::{distributions::Uniform, prelude::Distribution};
+use bevy_rapier2d::{
+    physics::RigidBodyHandleComponent,
+    rapier::{
+        dynamics::{RigidBodyBuilder, RigidBodySet},
+        geometry::ColliderBuilder,
+    },
+};
+use rand::{distributions::Uniform, prelude::Distribution, seq::SliceRandom, Rng};
+use std::{
+    f32::consts::{PI, TAU},
+    path::Path,
+};
 
-const MIN_CORGI_COUNT: usize = 1;
-const CORGI_ENERGY_SPAWNED: f32 = 100.0;
-const CORGI_ENERGY_BORN: f32 = 100.0;
+/// The energy of corgis spawned without parents.
+pub const CORGI_ENERGY_SPAWNED: f32 = 100.0;
+/// The energy a parent passes on to its child.
+pub const CORGI_ENERGY_BORN: f32 = 100.0;
+/// The energy of a corgi which isn't hungry at all, it can't store more.
+pub const CORGI_ENERGY_CAPACITY: f32 = 200.0;
+/// Food energy a corgi can carry at most.
+pub const CARRY_CAPACITY: f32 = 50.0;
+/// The health of an unharmed corgi.
+pub const MAX_HEALTH: f32 = 100.0;
+/// Radius of the circular body of an adult corgi of base size.
+const CORGI_HALF_SIZE: f32 = 10.0;
+/// Mass of a corgi of base size without any mass increasing genes.
+pub const CORGI_BASE_MASS: f32 = 400.0;
+const CORGI_MIN_MASS: f32 = 100.0;
+const CORGI_MAX_MASS: f32 = 2000.0;
+/// Drag force per unit of radius and squared speed.
+const DRAG_COEFFICIENT: f32 = 0.2;
+/// Fraction of the recent damage remembered after one tick.
+const DAMAGE_MEMORY: f32 = 0.95;
+/// Corgis in a region at which the environment counts as fully crowded.
+const CROWDING_SATURATION: f32 = 10.0;
+/// Mass of a newborn relative to its adult mass.
+const BIRTH_MASS_FRACTION: f32 = 0.3;
+/// Maximum distance between the parent and its newborn child.
+const BIRTH_DISTANCE: f32 = 20.0;
+
+/// Weight file imported with the import key.
+const IMPORT_PATH: &str = "brains/import.ron";
+const IMPORT_KEY: KeyCode = KeyCode::I;
 
 pub struct Corgi;
-pub struct Energy(pub f32);
+
+/// Between 0 and `CORGI_ENERGY_CAPACITY`.
+/// All arithmetic saturates, so the bookkeeping can't go negative or overflow the capacity.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct Energy(f32);
+
+impl Energy {
+    pub fn new(value: f32) -> Self {
+        debug_assert!(value.is_finite(), "non-finite energy {}", value);
+        Self(value.max(0.0).min(CORGI_ENERGY_CAPACITY))
+    }
+
+    pub fn get(self) -> f32 {
+        self.0
+    }
+
+    /// Adds up to the capacity, returns the amount actually added.
+    pub fn add(&mut self, amount: f32) -> f32 {
+        debug_assert!(
+            amount.is_finite() && amount >= 0.0,
+            "invalid energy gain {}",
+            amount
+        );
+        let before = self.0;
+        *self = Self::new(self.0 + amount);
+        self.0 - before
+    }
+
+    /// Removes down to zero, returns the amount actually removed.
+    pub fn sub(&mut self, amount: f32) -> f32 {
+        debug_assert!(
+            amount.is_finite() && amount >= 0.0,
+            "invalid energy loss {}",
+            amount
+        );
+        let before = self.0;
+        *self = Self::new(self.0 - amount);
+        before - self.0
+    }
+
+    /// Removes `amount` only if it is fully available.
+    pub fn spend(&mut self, amount: f32) -> bool {
+        if self.0 < amount {
+            return false;
+        }
+        self.sub(amount);
+        true
+    }
+
+    /// Between 0 and 1.
+    pub fn fraction(self) -> f32 {
+        self.0 / CORGI_ENERGY_CAPACITY
+    }
+}
+
+/// Between `CORGI_MIN_MASS` and `CORGI_MAX_MASS`, out of range values saturate like `Energy`.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct Mass(f32);
+
+impl Mass {
+    pub fn new(value: f32) -> Self {
+        debug_assert!(value.is_finite(), "non-finite mass {}", value);
+        Self(value.max(CORGI_MIN_MASS).min(CORGI_MAX_MASS))
+    }
+
+    pub fn get(self) -> f32 {
+        self.0
+    }
+}
+
+/// Between 0 and `MAX_HEALTH`, separate from the energy, the corgi dies without any.
+#[derive(Clone, Copy, Debug)]
+pub struct Health {
+    value: f32,
+    cause: Option<DeathCause>,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self {
+            value: MAX_HEALTH,
+            cause: None,
+        }
+    }
+}
+
+impl Health {
+    /// Clamped to `MAX_HEALTH`, without a cause of damage.
+    pub fn new(value: f32) -> Self {
+        debug_assert!(value.is_finite(), "non-finite health {}", value);
+        Self {
+            value: value.max(0.0).min(MAX_HEALTH),
+            cause: None,
+        }
+    }
+
+    pub fn get(self) -> f32 {
+        self.value
+    }
+
+    /// Between 0 and 1.
+    pub fn fraction(self) -> f32 {
+        self.value / MAX_HEALTH
+    }
+
+    pub fn is_dead(self) -> bool {
+        self.value <= 0.0
+    }
+
+    /// What caused the latest damage, the corgi dies of it when it reaches zero health.
+    pub fn cause(self) -> Option<DeathCause> {
+        self.cause
+    }
+
+    /// Removes down to zero, returns the amount actually removed.
+    pub fn hurt(&mut self, amount: f32, cause: DeathCause) -> f32 {
+        debug_assert!(
+            amount.is_finite() && amount >= 0.0,
+            "invalid damage {}",
+            amount
+        );
+        let before = self.value;
+        self.value = (self.value - amount).max(0.0);
+        self.cause = Some(cause);
+        before - self.value
+    }
+
+    /// Adds up to `MAX_HEALTH`, returns the amount actually added.
+    pub fn heal(&mut self, amount: f32) -> f32 {
+        let before = self.value;
+        self.value = (self.value + amount).min(MAX_HEALTH);
+        self.value - before
+    }
+}
+
+/// The mass the corgi grows up to, set by its genes.
+/// The rigid body has the current `Mass`, which approaches this one while the corgi grows.
+pub struct AdultMass(pub f32);
+
+impl AdultMass {
+    /// The current mass relative to the adult one, between 0 and 1.
+    pub fn maturity(&self, mass: Mass) -> f32 {
+        (mass.get() / self.0).min(1.0)
+    }
+}
+
+/// Between 0 and 1, drained by strong forces and regenerated at rest.
+pub struct Stamina(pub f32);
+/// Food energy the corgi carries around, at most `CARRY_CAPACITY`.
+#[derive(Default)]
+pub struct Load(pub f32);
+
+/// Whether the corgi decided to rest: it doesn't move, burns less energy and thinks less often.
+#[derive(Default)]
+pub struct Resting(pub bool);
+
+/// Direction the corgi faces in radians, only used by the heading locomotion.
+pub struct Heading(pub f32);
+
+/// Work done by the movement force in the last tick, the force times the velocity along it.
+#[derive(Default)]
+pub struct Exertion(pub f32);
+/// Damage taken recently, fading over time.
+#[derive(Default)]
+pub struct RecentDamage(pub f32);
+
+impl RecentDamage {
+    /// Every damage source should report here, so the corgi can feel it.
+    pub fn hurt(&mut self, damage: f32) {
+        self.0 += damage;
+    }
+}
+
 pub struct Age(pub usize);
+/// Where the corgi was born.
+pub struct Home(pub Vec2);
 pub struct Generation(pub usize);
 //struct Gene;
 //struct Brain;
@@ -22,46 +247,367 @@ pub struct Generation(pub usize);
 pub struct CorgiBundle {
     pub _tag: Corgi,
     pub energy: Energy,
+    pub health: Health,
+    pub mass: Mass,
+    pub adult_mass: AdultMass,
+    pub stamina: Stamina,
+    pub exertion: Exertion,
+    pub heading: Heading,
+    pub load: Load,
+    pub infestation: Infestation,
+    pub resting: Resting,
+    pub recent_damage: RecentDamage,
     pub age: Age,
     pub generation: Generation,
+    pub home: Home,
+    pub body: BodyGene,
+    pub color: DisplayColor,
+    pub senses: SenseGene,
+    pub metabolism: MetabolismGene,
+    pub genotype: Genotype,
+    pub environment: Environment,
+    pub lineage: Lineage,
+    pub dash_cooldown: DashCooldown,
+    pub on_tile: OnTile,
     pub rigid_body: RigidBodyBuilder,
     pub collider: ColliderBuilder,
     pub pbr: PbrBundle,
     pub intelligence: IntelligenceBundle,
+    pub rng: RngStream,
+}
+
+/// A parent at the time of birth.
+pub struct Inheritance<'a> {
+    pub generation: &'a Generation,
+    pub lineage: &'a Lineage,
 }
 
 impl CorgiBundle {
-    fn new_spawned(pos: Vec2, mesh: Handle<Mesh>, material: Handle<StandardMaterial>) -> Self {
+    /// The genome gets developed in `environment` first.
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        pos: Vec2,
+        energy: f32,
+        genome: Genome,
+        environment: &Environment,
+        generation: Generation,
+        lineage: Lineage,
+        assets: &MyAssets,
+        rng: &mut SimRng,
+    ) -> Self {
         let pbr = PbrBundle {
-            mesh,
-            material,
+            mesh: assets.corgi_mesh.clone(),
+            material: assets.corgi_material.clone(),
             ..Default::default()
         };
+        let (body, metabolism) = genome.develop(environment);
+        let genotype = genome.genotype();
+        let Genome { brain, senses, .. } = genome;
+        let [r, g, b] = body.color;
+        let color = DisplayColor(Color::rgb_linear(r, g, b));
+        let half_size = CORGI_HALF_SIZE * body.size;
+        let area = (2.0 * half_size).powi(2);
+        let base_area = (2.0 * CORGI_HALF_SIZE).powi(2);
+        let density = body.mass * body.muscle_mass_factor() * metabolism.mass_factor();
+        let mass = Mass::new(CORGI_BASE_MASS * area / base_area * density);
+        let adult_mass = AdultMass(mass.get());
+        let collider = collider(&body, &adult_mass, mass);
 
         Self {
             _tag: Corgi,
-            energy: Energy(CORGI_ENERGY_SPAWNED),
+            energy: Energy::new(energy),
+            health: Health::default(),
+            mass,
+            adult_mass,
+            stamina: Stamina(1.0),
+            exertion: Exertion::default(),
+            heading: Heading(rng.gen_range(0.0..TAU)),
+            load: Load::default(),
+            infestation: Infestation::default(),
+            resting: Resting::default(),
+            recent_damage: RecentDamage::default(),
             age: Age(0),
-            generation: Generation(0),
+            generation,
+            home: Home(pos),
+            body,
+            color,
+            senses,
+            metabolism,
+            genotype,
+            environment: *environment,
+            lineage,
+            dash_cooldown: DashCooldown::default(),
+            on_tile: OnTile::default(),
             rigid_body: RigidBodyBuilder::new_dynamic().translation(pos.x, pos.y),
-            collider: ColliderBuilder::cuboid(10.0, 10.0).density(1.0),
+            // rapier resolves the collisions between the corgis, seen as circles like by the vision
+            collider,
             pbr,
-            intelligence: IntelligenceBundle::default(),
+            intelligence: IntelligenceBundle::with_brain(Brain::from_gene(brain, rng)),
+            rng: rng.stream(),
+        }
+    }
+
+    fn new_spawned(
+        pos: Vec2,
+        genome: Genome,
+        environment: &Environment,
+        assets: &MyAssets,
+        log: &mut LineageLog,
+        rng: &mut SimRng,
+    ) -> Self {
+        Self::new(
+            pos,
+            CORGI_ENERGY_SPAWNED,
+            genome,
+            environment,
+            Generation(0),
+            Lineage::new_root(log),
+            assets,
+            rng,
+        )
+    }
+
+    /// A small child with the already inherited `genome` next to the first parent.
+    pub fn new_born(
+        parent_pos: Vec2,
+        genome: Genome,
+        parents: &[Inheritance],
+        environment: &Environment,
+        assets: &MyAssets,
+        log: &mut LineageLog,
+        rng: &mut SimRng,
+    ) -> Self {
+        let generation = parents
+            .iter()
+            .map(|parent| parent.generation.0)
+            .max()
+            .unwrap_or(0);
+        let lineages: Vec<&Lineage> = parents.iter().map(|parent| parent.lineage).collect();
+        let offset = Vec2::new(
+            rng.gen_range(-BIRTH_DISTANCE..BIRTH_DISTANCE),
+            rng.gen_range(-BIRTH_DISTANCE..BIRTH_DISTANCE),
+        );
+
+        let mut child = Self::new(
+            parent_pos + offset,
+            CORGI_ENERGY_BORN,
+            genome,
+            environment,
+            Generation(generation + 1),
+            Lineage::new_child(log, &lineages),
+            assets,
+            rng,
+        );
+        child.mass = Mass::new(child.adult_mass.0 * BIRTH_MASS_FRACTION);
+        child.collider = collider(&child.body, &child.adult_mass, child.mass);
+        child
+    }
+}
+
+/// Radius of the corgi, it grows with the square root of the maturity like the sprite.
+pub fn half_size(body: &BodyGene, adult_mass: &AdultMass, mass: Mass) -> f32 {
+    CORGI_HALF_SIZE * body.size * adult_mass.maturity(mass).sqrt()
+}
+
+/// A circle of the current size, dense enough for the rigid body to have the current mass.
+pub fn collider(body: &BodyGene, adult_mass: &AdultMass, mass: Mass) -> ColliderBuilder {
+    let half_size = half_size(body, adult_mass, mass);
+    ColliderBuilder::ball(half_size).density(mass.get() / (PI * half_size * half_size))
+}
+
+/// The conditions a corgi born at `pos` develops in.
+pub fn environment_at(pos: Vec2, daylight: &Daylight, stats: &RegionStats) -> Environment {
+    let population = stats.get(pos).map_or(0, |region| region.population);
+    Environment {
+        light: daylight.0,
+        crowding: (population as f32 / CROWDING_SATURATION).min(1.0),
+    }
+}
+
+fn random_position<R: Rng>(rng: &mut R, universe: &Universe) -> Vec2 {
+    let x_pos_distr = Uniform::new(0.0, universe.width());
+    let y_pos_distr = Uniform::new(0.0, universe.height());
+    Vec2::new(x_pos_distr.sample(rng), y_pos_distr.sample(rng))
+}
+
+/// Keeps the population at the minimum.
+/// New corgis are mutated copies of the seed genomes, if there are any.
+/// Diploid ones get a mutated copy of it as second allele.
+#[allow(clippy::too_many_arguments)]
+pub fn corgi_spawner(
+    commands: &mut Commands,
+    scenario: Res<Scenario>,
+    universe: Res<Universe>,
+    seeds: Res<SeedGenomes>,
+    daylight: Res<Daylight>,
+    stats: Res<RegionStats>,
+    query: Query<&Corgi>,
+    assets: Res<MyAssets>,
+    ledger: Res<EnergyLedger>,
+    mut log: ResMut<LineageLog>,
+    mut rng: ResMut<SimRng>,
+) {
+    let rng = &mut *rng;
+    for _ in query.iter().len()..scenario.min_corgi_count {
+        ledger.source(CORGI_ENERGY_SPAWNED);
+        let genome = match seeds.0.choose(rng) {
+            Some(seed) => seed.child(rng, &scenario.mutation),
+            None => Genome::new_random(rng),
+        };
+        let pos = random_position(rng, &universe);
+        let environment = environment_at(pos, &daylight, &stats);
+        match scenario.genetics {
+            Genetics::Haploid => commands.spawn(CorgiBundle::new_spawned(
+                pos,
+                genome,
+                &environment,
+                &assets,
+                &mut log,
+                rng,
+            )),
+            Genetics::Diploid => {
+                let alleles = Alleles {
+                    second: genome.child(rng, &scenario.mutation),
+                    first: genome,
+                };
+                let expressed = alleles.express();
+                commands
+                    .spawn(CorgiBundle::new_spawned(
+                        pos,
+                        expressed,
+                        &environment,
+                        &assets,
+                        &mut log,
+                        rng,
+                    ))
+                    .with(alleles)
+            }
+        };
+    }
+}
+
+/// Spawns a new corgi at a random position with `genome`, developed in a neutral environment.
+pub fn spawn_with_genome(
+    commands: &mut Commands,
+    genome: Genome,
+    universe: &Universe,
+    assets: &MyAssets,
+    log: &mut LineageLog,
+    rng: &mut SimRng,
+) -> Entity {
+    let pos = random_position(rng, universe);
+    commands.spawn(CorgiBundle::new_spawned(
+        pos,
+        genome,
+        &Environment::default(),
+        assets,
+        log,
+        rng,
+    ));
+    commands.current_entity().unwrap()
+}
+
+/// Spawns a corgi of a snapshot at `pos` with `genome`, developed in its saved `environment`.
+/// It starts a new lineage, the lineages aren't part of snapshots.
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_restored(
+    commands: &mut Commands,
+    genome: Genome,
+    environment: &Environment,
+    pos: Vec2,
+    energy: f32,
+    generation: usize,
+    assets: &MyAssets,
+    log: &mut LineageLog,
+    rng: &mut SimRng,
+) -> Entity {
+    commands.spawn(CorgiBundle::new(
+        pos,
+        energy,
+        genome,
+        environment,
+        Generation(generation),
+        Lineage::new_root(log),
+        assets,
+        rng,
+    ));
+    commands.current_entity().unwrap()
+}
+
+/// Spawns a new corgi thinking with the weights from the file at `path`.
+pub fn import_corgi<P: AsRef<Path>>(
+    commands: &mut Commands,
+    path: P,
+    universe: &Universe,
+    assets: &MyAssets,
+    log: &mut LineageLog,
+    rng: &mut SimRng,
+) -> Result<Entity, String> {
+    let path = path.as_ref();
+    let genome =
+        Genome::load(path).map_err(|e| format!("failed to import `{}`: {}", path.display(), e))?;
+    Ok(spawn_with_genome(
+        commands, genome, universe, assets, log, rng,
+    ))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn corgi_importer(
+    commands: &mut Commands,
+    keys: Res<Input<KeyCode>>,
+    mut reader: Local<EventReader<ConsoleCommand>>,
+    console: Res<Events<ConsoleCommand>>,
+    universe: Res<Universe>,
+    assets: Res<MyAssets>,
+    ledger: Res<EnergyLedger>,
+    mut log: ResMut<LineageLog>,
+    mut rng: ResMut<SimRng>,
+) {
+    let mut paths: Vec<&Path> = reader
+        .iter(&console)
+        .filter_map(|command| match command {
+            ConsoleCommand::Import(path) => Some(path.as_path()),
+            _ => None,
+        })
+        .collect();
+    if keys.just_pressed(IMPORT_KEY) {
+        paths.push(Path::new(IMPORT_PATH));
+    }
+
+    for path in paths {
+        match import_corgi(commands, path, &universe, &assets, &mut log, &mut rng) {
+            Ok(entity) => {
+                ledger.source(CORGI_ENERGY_SPAWNED);
+                info!("imported `{}` as {:?}", path.display(), entity);
+            }
+            Err(e) => error!("{}", e),
         }
     }
 }
 
-pub fn corgi_spawner(commands: &mut Commands, query: Query<&Corgi>, assets: Res<MyAssets>) {
-    let mut rng = rand::thread_rng();
-    let x_pos_distr = Uniform::new(0.0, UNIVERSE_WIDTH);
-    let y_pos_distr = Uniform::new(0.0, UNIVERSE_HEIGHT);
-    for _ in query.iter().len()..MIN_CORGI_COUNT {
-        let x = x_pos_distr.sample(&mut rng);
-        let y = y_pos_distr.sample(&mut rng);
-        commands.spawn(CorgiBundle::new_spawned(
-            Vec2::new(x, y),
-            assets.corgi_mesh.clone(),
-            assets.corgi_material.clone(),
-        ));
+pub fn age_corgis(mut query: Query<&mut Age>) {
+    for mut age in query.iter_mut() {
+        age.0 += 1;
+    }
+}
+
+pub fn fade_damage(mut query: Query<&mut RecentDamage>) {
+    for mut damage in query.iter_mut() {
+        damage.0 *= DAMAGE_MEMORY;
+    }
+}
+
+/// Slows the corgis down with a drag quadratic in their speed and growing with their size.
+pub fn apply_drag(
+    mut bodies: ResMut<RigidBodySet>,
+    query: Query<(&RigidBodyHandleComponent, &BodyGene, &Mass, &AdultMass)>,
+) {
+    for (handle, body_gene, mass, adult_mass) in query.iter() {
+        if let Some(body) = bodies.get_mut(handle.handle()) {
+            let half_size = half_size(body_gene, adult_mass, *mass);
+            let velocity = *body.linvel();
+            let drag = -velocity * velocity.norm() * DRAG_COEFFICIENT * half_size;
+            body.apply_force(drag, true);
+        }
     }
 }
@@ -0,0 +1,122 @@
+use crate::{
+    corgi::{Corgi, Energy, Load, CORGI_ENERGY_CAPACITY},
+    debug::EnergyLedger,
+    loader::MyAssets,
+    universe::{tile::TileGrid, Universe},
+};
+use bevy::prelude::*;
+
+/// Energy of the body itself, added to the remaining energy of the corgi.
+pub const CORPSE_BODY_ENERGY: f32 = 30.0;
+/// Fraction of its energy a corpse loses to the tile below it every tick.
+const CORPSE_DECAY: f32 = 0.002;
+/// Corpses with less energy are gone.
+const CORPSE_MIN_ENERGY: f32 = 0.5;
+/// Energy a corgi with the default appetite eats per tick from a corpse in range.
+pub const CORPSE_BITE: f32 = 1.0;
+/// Maximum distance between a corgi and the corpse it eats.
+pub const CORPSE_EAT_RANGE: f32 = 15.0;
+/// Side length of the sprite of a full corpse.
+const CORPSE_SIZE: f32 = 12.0;
+
+/// The remains of a dead corgi, edible until it decayed into the tile below.
+pub struct Corpse {
+    pub energy: f32,
+}
+
+/// Why a corgi died.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeathCause {
+    Starvation,
+    OldAge,
+    Predation,
+    Meteor,
+    Plague,
+    Poison,
+    /// Left a universe with the `Boundary::Death`.
+    Boundary,
+}
+
+/// Sent for every corgi which died, the entity is already despawned.
+#[derive(Clone, Copy, Debug)]
+pub struct Died {
+    pub entity: Entity,
+    pub pos: Vec2,
+    pub cause: DeathCause,
+}
+
+pub struct CorpsePlugin;
+
+impl Plugin for CorpsePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<Died>().add_system(decay_corpses.system());
+    }
+
+    fn name(&self) -> &str {
+        "CorpsePlugin"
+    }
+}
+
+/// The energy of the corpse a corgi with `energy` would leave, the carried food falls onto it.
+pub fn remains(energy: &Energy, load: &Load) -> f32 {
+    energy.get() + load.0 + CORPSE_BODY_ENERGY
+}
+
+/// Spawns a corpse with `energy` at `pos`, sized by its energy.
+pub fn spawn_corpse(commands: &mut Commands, assets: &MyAssets, pos: Vec2, energy: f32) {
+    commands
+        .spawn(SpriteBundle {
+            sprite: Sprite::new(Vec2::splat(CORPSE_SIZE)),
+            material: assets.corpse_material.clone(),
+            transform: Transform {
+                translation: pos.extend(0.0),
+                scale: scale(energy),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .with(Corpse { energy });
+}
+
+/// The sprite shrinks with the energy, down from the size of a full corgi.
+fn scale(energy: f32) -> Vec3 {
+    let size = (energy / CORGI_ENERGY_CAPACITY).sqrt().min(1.0);
+    Vec3::new(size, size, 1.0)
+}
+
+/// Despawns the corgi, leaves a corpse with the energy `remains` at `pos`
+/// and reports the death.
+pub fn kill(
+    commands: &mut Commands,
+    assets: &MyAssets,
+    deaths: &mut Events<Died>,
+    entity: Entity,
+    pos: Vec2,
+    remains: f32,
+    cause: DeathCause,
+) {
+    commands.despawn(entity);
+    deaths.send(Died { entity, pos, cause });
+    spawn_corpse(commands, assets, pos, remains);
+}
+
+/// Corpses pass their energy on to the food of the tile below them and shrink.
+/// Energy beyond the capacity of the tile is lost.
+fn decay_corpses(
+    commands: &mut Commands,
+    ledger: Res<EnergyLedger>,
+    mut grid: ResMut<TileGrid>,
+    mut corpses: Query<(Entity, &mut Corpse, &mut Transform)>,
+) {
+    for (entity, mut corpse, mut transform) in corpses.iter_mut() {
+        let decayed = corpse.energy * CORPSE_DECAY;
+        corpse.energy -= decayed;
+        ledger.sink(grid.fertilize(transform.translation.truncate(), decayed));
+        if corpse.energy < CORPSE_MIN_ENERGY {
+            ledger.sink(corpse.energy);
+            commands.despawn(entity);
+        } else {
+            transform.scale = scale(corpse.energy);
+        }
+    }
+}
@@ -0,0 +1,334 @@
+use crate::{
+    console::ConsoleCommand,
+    corgi::{Corgi, Energy, Load, Mass},
+    corpse::{Corpse, Died, CORPSE_BODY_ENERGY},
+    genes::{Genome, Genotype, SenseGene},
+    intelligence::{decision::reproduction::Egg, Brain},
+    lineage::Lineage,
+    parasite::Parasite,
+    spatial::SpatialGrid,
+    universe::{tile::TileGrid, Tick},
+};
+use bevy::prelude::*;
+use std::{collections::HashMap, fs, path::Path, sync::Mutex};
+
+/// Runs after the spatial grid has been rebuilt.
+const DEBUG_STAGE: &str = "debug";
+/// Ticks between two runs of the checks.
+const DEFAULT_CHECK_INTERVAL: usize = 100;
+/// Maximum distance between a grid entry and the position of its corgi.
+const GRID_POSITION_TOLERANCE: f32 = 0.01;
+/// Genomes of quarantined corgis are dumped here.
+pub const QUARANTINE_DIR: &str = "quarantine";
+/// Corgis are quarantined once their brain produced this many non-finite outputs.
+const NON_FINITE_THRESHOLD: usize = 10;
+/// Drift of the total energy tolerated for rounding, relative to the total.
+const ENERGY_TOLERANCE: f64 = 1e-4;
+
+/// Expensive invariant checks, toggled with the `debug` console command.
+/// They only run every `interval` ticks.
+pub struct DebugChecks {
+    pub enabled: bool,
+    pub interval: usize,
+}
+
+impl Default for DebugChecks {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval: DEFAULT_CHECK_INTERVAL,
+        }
+    }
+}
+
+impl DebugChecks {
+    fn due(&self, tick: usize) -> bool {
+        self.enabled && tick % self.interval.max(1) == 0
+    }
+}
+
+/// Counts the non-finite outputs zeroed by the brains.
+/// Unlike the debug checks this guard is always active.
+#[derive(Default)]
+pub struct NonFiniteStats {
+    pub outputs: usize,
+    pub quarantined: usize,
+}
+
+/// Energy entering and leaving the world, recorded where it happens.
+/// Moving energy between corgis, carried loads, tiles, corpses, eggs and parasites isn't recorded,
+/// only what gets lost or gained on the way.
+/// The conception moves the energy of the egg out of the world until it is laid.
+/// Holds the sources minus the sinks since the last check.
+#[derive(Default)]
+pub struct EnergyLedger(Mutex<f64>);
+
+impl EnergyLedger {
+    /// Energy added to the world.
+    pub fn source(&self, amount: f32) {
+        *self.0.lock().unwrap() += amount as f64;
+    }
+
+    /// Energy removed from the world.
+    pub fn sink(&self, amount: f32) {
+        *self.0.lock().unwrap() -= amount as f64;
+    }
+
+    /// The balance since the last call.
+    fn take(&self) -> f64 {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+pub struct DebugPlugin;
+
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(DebugChecks::default())
+            .add_resource(NonFiniteStats::default())
+            .add_resource(EnergyLedger::default())
+            .add_stage_after(stage::POST_UPDATE, DEBUG_STAGE, SystemStage::parallel())
+            .add_system_to_stage(stage::POST_UPDATE, toggle_debug_checks.system())
+            .add_system(quarantine_non_finite.system())
+            .add_system_to_stage(DEBUG_STAGE, check_energy.system())
+            .add_system_to_stage(DEBUG_STAGE, check_spatial_grid.system())
+            .add_system_to_stage(DEBUG_STAGE, check_brains.system());
+    }
+
+    fn name(&self) -> &str {
+        "DebugPlugin"
+    }
+}
+
+/// Dumps the genome of the corgi to the quarantine directory and despawns it,
+/// so the faulty genome can be reproduced with `import`.
+pub fn quarantine(
+    commands: &mut Commands,
+    entity: Entity,
+    lineage: &Lineage,
+    genome: &Genome,
+    reason: &str,
+) {
+    commands.despawn(entity);
+    let path = Path::new(QUARANTINE_DIR).join(format!("{}.ron", lineage.id.0));
+    let saved = fs::create_dir_all(QUARANTINE_DIR)
+        .map_err(|e| e.to_string())
+        .and_then(|_| genome.save(&path).map_err(|e| e.to_string()));
+    match saved {
+        Ok(()) => warn!(
+            "quarantined {}: {}, genome dumped to `{}`",
+            lineage.id,
+            reason,
+            path.display()
+        ),
+        Err(e) => warn!(
+            "quarantined {}: {}, failed to dump genome: {}",
+            lineage.id, reason, e
+        ),
+    }
+}
+
+fn toggle_debug_checks(
+    mut checks: ResMut<DebugChecks>,
+    mut reader: Local<EventReader<ConsoleCommand>>,
+    console: Res<Events<ConsoleCommand>>,
+) {
+    for command in reader.iter(&console) {
+        if let ConsoleCommand::Debug { enabled, interval } = command {
+            checks.enabled = *enabled;
+            if let Some(interval) = interval {
+                checks.interval = *interval;
+            }
+            info!(
+                "debug checks {} (every {} ticks)",
+                if checks.enabled {
+                    "enabled"
+                } else {
+                    "disabled"
+                },
+                checks.interval
+            );
+        }
+    }
+}
+
+/// The energy of all corgis, carried loads, tiles, corpses, eggs and parasites.
+fn total_energy(
+    grid: &TileGrid,
+    corgis: &Query<(&Lineage, &Energy, &Load, &Mass)>,
+    corpses: &Query<&Corpse>,
+    eggs: &Query<&Egg>,
+    parasites: &Query<&Parasite>,
+) -> f64 {
+    let tiles: f64 = grid.iter().map(|tile| tile.energy as f64).sum();
+    let corgis: f64 = corgis
+        .iter()
+        .map(|(_, energy, load, _)| (energy.get() + load.0) as f64)
+        .sum();
+    let corpses: f64 = corpses.iter().map(|corpse| corpse.energy as f64).sum();
+    let eggs: f64 = eggs.iter().map(|egg| egg.energy as f64).sum();
+    let parasites: f64 = parasites
+        .iter()
+        .map(|parasite| parasite.energy as f64)
+        .sum();
+    tiles + corgis + corpses + eggs + parasites
+}
+
+/// Energy and mass saturate, so they can never leave their bounds or become non-finite.
+/// The total energy only changes by what the `EnergyLedger` recorded since the last check.
+/// The body energy of every corpse is counted from the deaths.
+/// Replacing or resizing the world starts the counting anew.
+#[allow(clippy::too_many_arguments)]
+fn check_energy(
+    checks: Res<DebugChecks>,
+    tick: Res<Tick>,
+    // mutable to run before the quarantine of `check_brains`, which records its energy
+    ledger: ResMut<EnergyLedger>,
+    grid: Res<TileGrid>,
+    mut reader: Local<EventReader<Died>>,
+    deaths: Res<Events<Died>>,
+    mut console_reader: Local<EventReader<ConsoleCommand>>,
+    console: Res<Events<ConsoleCommand>>,
+    mut last_total: Local<Option<f64>>,
+    query: Query<(&Lineage, &Energy, &Load, &Mass)>,
+    corpses: Query<&Corpse>,
+    eggs: Query<&Egg>,
+    parasites: Query<&Parasite>,
+) {
+    // the events only live for two frames, so they are counted every frame
+    for _ in reader.iter(&deaths) {
+        ledger.source(CORPSE_BODY_ENERGY);
+    }
+    let replaced = console_reader.iter(&console).filter(|command| {
+        matches!(
+            command,
+            ConsoleCommand::Scenario(_)
+                | ConsoleCommand::LoadSnapshot(_)
+                | ConsoleCommand::Resize { .. }
+        )
+    });
+    if replaced.count() > 0 {
+        *last_total = None;
+    }
+    if !checks.enabled {
+        *last_total = None;
+        return;
+    }
+    if !checks.due(tick.0) {
+        return;
+    }
+    let total = total_energy(&grid, &query, &corpses, &eggs, &parasites);
+    let balance = ledger.take();
+    if let Some(last) = *last_total {
+        let drift = total - (last + balance);
+        if drift.abs() > ENERGY_TOLERANCE * total.max(1.0) {
+            error!(
+                "energy isn't conserved: total {:.3}, expected {:.3}",
+                total,
+                last + balance
+            );
+        }
+    }
+    *last_total = Some(total);
+    for (lineage, energy, _, mass) in query.iter() {
+        if !energy.get().is_finite() || !(0.0..=1.0).contains(&energy.fraction()) {
+            error!("energy of {} is {}", lineage.id, energy.get());
+        }
+        if !mass.get().is_finite() || mass.get() <= 0.0 {
+            error!("mass of {} is {}", lineage.id, mass.get());
+        }
+    }
+}
+
+/// Every corgi has to be in the grid exactly once, at its current position.
+fn check_spatial_grid(
+    checks: Res<DebugChecks>,
+    tick: Res<Tick>,
+    grid: Res<SpatialGrid>,
+    query: Query<(Entity, &Transform), With<Corgi>>,
+) {
+    if !checks.due(tick.0) {
+        return;
+    }
+    let mut entries: HashMap<Entity, Vec<Vec2>> = HashMap::new();
+    for (entity, pos) in grid.entries() {
+        entries.entry(entity).or_default().push(pos);
+    }
+    let universe = grid.universe();
+    for (entity, transform) in query.iter() {
+        let pos = transform.translation.truncate();
+        match entries.remove(&entity).as_deref() {
+            Some([grid_pos])
+                if universe.offset(pos, *grid_pos).length() <= GRID_POSITION_TOLERANCE => {}
+            Some([grid_pos]) => {
+                error!("{:?} is at {} but in the grid at {}", entity, pos, grid_pos)
+            }
+            Some(positions) => error!("{:?} is {} times in the grid", entity, positions.len()),
+            None => error!("{:?} is missing in the grid", entity),
+        }
+    }
+    for entity in entries.keys() {
+        error!("{:?} is in the grid but isn't a corgi", entity);
+    }
+}
+
+/// Corgis with non-finite weights or decisions are quarantined.
+fn check_brains(
+    commands: &mut Commands,
+    checks: Res<DebugChecks>,
+    tick: Res<Tick>,
+    ledger: Res<EnergyLedger>,
+    query: Query<(
+        Entity,
+        &Lineage,
+        (&Energy, &Load),
+        (&Brain, &SenseGene, &Genotype),
+    )>,
+) {
+    if !checks.due(tick.0) {
+        return;
+    }
+    for (entity, lineage, (energy, load), (brain, senses, genotype)) in query.iter() {
+        let weights_finite = brain.network().map_or(true, |network| network.is_finite());
+        let decisions_finite = brain
+            .output()
+            .map_or(true, |output| output.iter().all(|v| v.is_finite()));
+        let reason = if !weights_finite {
+            "non-finite weights"
+        } else if !decisions_finite {
+            "non-finite decisions"
+        } else {
+            continue;
+        };
+        let genome = Genome::from_parts(brain.gene(), senses, genotype);
+        ledger.sink(energy.get() + load.0);
+        quarantine(commands, entity, lineage, &genome, reason);
+    }
+}
+
+fn quarantine_non_finite(
+    commands: &mut Commands,
+    mut stats: ResMut<NonFiniteStats>,
+    ledger: Res<EnergyLedger>,
+    query: Query<(
+        Entity,
+        &Lineage,
+        (&Energy, &Load),
+        (&Brain, &SenseGene, &Genotype),
+    )>,
+) {
+    for (entity, lineage, (energy, load), (brain, senses, genotype)) in query.iter() {
+        if brain.non_finite() >= NON_FINITE_THRESHOLD {
+            stats.quarantined += 1;
+            let reason = format!(
+                "{} non-finite outputs ({} in total, {} corgis quarantined)",
+                brain.non_finite(),
+                stats.outputs,
+                stats.quarantined
+            );
+            let genome = Genome::from_parts(brain.gene(), senses, genotype);
+            ledger.sink(energy.get() + load.0);
+            quarantine(commands, entity, lineage, &genome, &reason);
+        }
+    }
+}
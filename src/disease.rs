@@ -0,0 +1,134 @@
+use crate::{
+    corgi::{Corgi, Energy},
+    debug::EnergyLedger,
+    genes::MetabolismGene,
+    rng::SimRng,
+    spatial::SpatialGrid,
+};
+use bevy::prelude::*;
+use rand::{seq::IteratorRandom, Rng};
+use rand_distr::{Distribution, Normal};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Chance per tick that a random corgi catches a new pathogen.
+const OUTBREAK_CHANCE: f64 = 0.001;
+/// Virulence of the pathogens of new outbreaks.
+const OUTBREAK_VIRULENCE: f32 = 0.3;
+/// Corgis this close to an infected one can catch its pathogen.
+const CONTACT_RANGE: f32 = 25.0;
+/// Chance per tick and contact of the most virulent pathogen to spread to a corgi without immunity.
+const MAX_TRANSMISSION_CHANCE: f32 = 0.05;
+/// Energy the most virulent pathogen drains per tick.
+const MAX_DISEASE_DRAIN: f32 = 0.1;
+/// Chance per tick of a corgi with full immunity to recover.
+const MAX_RECOVERY_CHANCE: f32 = 0.01;
+/// Standard deviation of the noise added to the virulence on every transmission.
+const VIRULENCE_MUTATION_SIGMA: f32 = 0.05;
+
+/// The pathogen a corgi is infected with.
+/// Virulent pathogens spread more easily, but drain their hosts faster.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Infection {
+    /// Between 0 and 1.
+    pub virulence: f32,
+}
+
+impl Infection {
+    /// The pathogen passed on to another host, with a mutated virulence.
+    fn transmitted<R: Rng>(self, rng: &mut R) -> Self {
+        let noise = Normal::new(0.0, VIRULENCE_MUTATION_SIGMA).unwrap();
+        Self {
+            virulence: (self.virulence + noise.sample(rng)).max(0.0).min(1.0),
+        }
+    }
+}
+
+/// Pathogens breaking out, spreading on contact and draining their hosts,
+/// resisted by the immunity gene.
+pub struct DiseasePlugin;
+
+impl Plugin for DiseasePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(break_out.system())
+            .add_system(spread_infections.system())
+            .add_system(drain_hosts.system())
+            .add_system(recover.system());
+    }
+
+    fn name(&self) -> &str {
+        "DiseasePlugin"
+    }
+}
+
+/// Occasionally infects a random healthy corgi with a new pathogen.
+fn break_out(
+    commands: &mut Commands,
+    mut rng: ResMut<SimRng>,
+    query: Query<Entity, (With<Corgi>, Without<Infection>)>,
+) {
+    let rng = &mut *rng;
+    if !rng.gen_bool(OUTBREAK_CHANCE) {
+        return;
+    }
+    if let Some(entity) = query.iter().choose(rng) {
+        let infection = Infection {
+            virulence: OUTBREAK_VIRULENCE,
+        };
+        commands.insert_one(entity, infection);
+    }
+}
+
+/// Infected corgis pass their pathogen on to the healthy corgis in contact with them,
+/// the immunity of the healthy corgi lowers the chance.
+fn spread_infections(
+    commands: &mut Commands,
+    grid: Res<SpatialGrid>,
+    infected: Query<(Entity, &Transform, &Infection)>,
+    healthy: Query<&MetabolismGene, (With<Corgi>, Without<Infection>)>,
+    mut rng: ResMut<SimRng>,
+) {
+    let rng = &mut *rng;
+    let mut caught = HashMap::new();
+    for (entity, transform, infection) in infected.iter() {
+        let chance = MAX_TRANSMISSION_CHANCE * infection.virulence;
+        for (other, _) in grid.in_radius(transform.translation.truncate(), CONTACT_RANGE) {
+            if other == entity || caught.contains_key(&other) {
+                continue;
+            }
+            let metabolism = match healthy.get(other) {
+                Ok(metabolism) => metabolism,
+                Err(_) => continue,
+            };
+            if rng.gen::<f32>() < chance * (1.0 - metabolism.immunity) {
+                caught.insert(other, infection.transmitted(rng));
+            }
+        }
+    }
+    for (entity, infection) in caught {
+        commands.insert_one(entity, infection);
+    }
+}
+
+/// Pathogens drain the energy of their hosts.
+fn drain_hosts(ledger: Res<EnergyLedger>, mut query: Query<(&Infection, &mut Energy)>) {
+    let mut drained = 0.0;
+    for (infection, mut energy) in query.iter_mut() {
+        drained += energy.sub(infection.virulence * MAX_DISEASE_DRAIN);
+    }
+    ledger.sink(drained);
+}
+
+/// Corgis get rid of their pathogen depending on their immunity.
+fn recover(
+    commands: &mut Commands,
+    mut rng: ResMut<SimRng>,
+    query: Query<(Entity, &MetabolismGene), With<Infection>>,
+) {
+    let rng = &mut *rng;
+    for (entity, metabolism) in query.iter() {
+        if rng.gen::<f32>() < MAX_RECOVERY_CHANCE * metabolism.immunity {
+            commands.remove_one::<Infection>(entity);
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use crate::{
+    console::ConsoleCommand,
+    pheromone::PheromoneField,
+    scent::ScentField,
+    territory::Team,
+    universe::{
+        tile::{TileGrid, TilePos, TileType},
+        Season, Tick, Universe,
+    },
+};
+use bevy::prelude::*;
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+const DEFAULT_EXPORT_DIR: &str = "fields";
+const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+/// The header of a version 1.0 file is padded to a multiple of this.
+const NPY_HEADER_ALIGNMENT: usize = 64;
+
+/// Writes `values` as a row major little endian `f32` array of the given shape
+/// in the NumPy `.npy` format.
+pub fn write_npy<P: AsRef<Path>>(path: P, shape: (usize, usize), values: &[f32]) -> io::Result<()> {
+    assert_eq!(shape.0 * shape.1, values.len(), "invalid array shape");
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}",
+        shape.0, shape.1
+    );
+    // magic, version and header length take 10 bytes, the header ends with a newline
+    let unpadded = NPY_MAGIC.len() + 4 + header.len() + 1;
+    let padding = (NPY_HEADER_ALIGNMENT - unpadded % NPY_HEADER_ALIGNMENT) % NPY_HEADER_ALIGNMENT;
+    header.push_str(&" ".repeat(padding));
+    header.push('\n');
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(NPY_MAGIC)?;
+    writer.write_all(&[1, 0])?;
+    writer.write_all(&(header.len() as u16).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    for value in values {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    writer.flush()
+}
+
+/// Periodic export of the scalar tile fields, one `.npy` file per field and export.
+/// Rows are the y axis of the universe.
+/// The territory field is 0 for unowned tiles, 1 for blue and 2 for red ones.
+pub struct FieldExport {
+    /// Ticks between two exports, disabled if `None`.
+    pub interval: Option<usize>,
+    pub dir: PathBuf,
+}
+
+impl Default for FieldExport {
+    fn default() -> Self {
+        Self {
+            interval: None,
+            dir: PathBuf::from(DEFAULT_EXPORT_DIR),
+        }
+    }
+}
+
+pub struct ExportPlugin;
+
+impl Plugin for ExportPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(FieldExport::default())
+            .add_system_to_stage(stage::POST_UPDATE, configure_export.system())
+            .add_system(export_fields.system());
+    }
+
+    fn name(&self) -> &str {
+        "ExportPlugin"
+    }
+}
+
+fn configure_export(
+    mut export: ResMut<FieldExport>,
+    mut reader: Local<EventReader<ConsoleCommand>>,
+    console: Res<Events<ConsoleCommand>>,
+) {
+    for command in reader.iter(&console) {
+        if let ConsoleCommand::Export { interval, dir } = command {
+            export.interval = *interval;
+            if let Some(dir) = dir {
+                export.dir = dir.clone();
+            }
+            match export.interval {
+                Some(interval) => info!(
+                    "exporting fields to `{}` every {} ticks",
+                    export.dir.display(),
+                    interval
+                ),
+                None => info!("field export disabled"),
+            }
+        }
+    }
+}
+
+/// Samples a field at the center of every tile.
+fn sample(universe: &Universe, field: impl FnMut(TilePos) -> f32) -> Vec<f32> {
+    let width = universe.width_tiles;
+    (0..universe.height_tiles)
+        .flat_map(|y| (0..width).map(move |x| TilePos { x, y }))
+        .map(field)
+        .collect()
+}
+
+fn export_fields(
+    tick: Res<Tick>,
+    mut export: ResMut<FieldExport>,
+    universe: Res<Universe>,
+    scent: Res<ScentField>,
+    pheromones: Res<PheromoneField>,
+    season: Res<Season>,
+    grid: Res<TileGrid>,
+) {
+    match export.interval {
+        Some(interval) if tick.0 % interval.max(1) == 0 => {}
+        _ => return,
+    }
+    let tile = |pos: TilePos| grid.get(pos);
+    let fields = [
+        (
+            "scent",
+            sample(&universe, |pos| {
+                scent.concentration(pos.center(universe.tile_size))
+            }),
+        ),
+        (
+            "pheromone",
+            sample(&universe, |pos| {
+                pheromones.concentration(pos.center(universe.tile_size))
+            }),
+        ),
+        (
+            "spring",
+            sample(&universe, |pos| {
+                (tile(pos).map(|t| t.tile_type) == Some(TileType::Spring)) as u8 as f32
+            }),
+        ),
+        (
+            "food",
+            sample(&universe, |pos| tile(pos).map_or(0.0, |t| t.energy)),
+        ),
+        (
+            "elevation",
+            sample(&universe, |pos| tile(pos).map_or(0.0, |t| t.elevation)),
+        ),
+        (
+            "depletion",
+            sample(&universe, |pos| tile(pos).map_or(0.0, |t| t.depletion)),
+        ),
+        (
+            "temperature",
+            sample(&universe, |pos| {
+                tile(pos).map_or(0.0, |t| t.temperature(&season))
+            }),
+        ),
+        (
+            "territory",
+            sample(&universe, |pos| match tile(pos).and_then(|t| t.owner) {
+                None => 0.0,
+                Some(Team::Blue) => 1.0,
+                Some(Team::Red) => 2.0,
+            }),
+        ),
+    ];
+
+    let result = fs::create_dir_all(&export.dir).and_then(|_| {
+        fields.iter().try_for_each(|(name, values)| {
+            let path = export.dir.join(format!("{}_{:08}.npy", name, tick.0));
+            write_npy(path, (universe.height_tiles, universe.width_tiles), values)
+        })
+    });
+    if let Err(e) = result {
+        error!(
+            "failed to export fields to `{}`, disabling the export: {}",
+            export.dir.display(),
+            e
+        );
+        export.interval = None;
+    }
+}
@@ -0,0 +1,1352 @@
+use crate::intelligence::{brain::WeightsError, NetworkDiff};
+use crate::intelligence::{
+    brain::{MutationNoise, MUTATION_RATE, MUTATION_SIGMA},
+    NeuralNetwork,
+};
+use rand::{prelude::Distribution, Rng};
+use rand_distr::Normal;
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, io, path::Path};
+
+/// First word of a genome file.
+const GENOME_MAGIC: &str = "corgis-genome";
+/// Version of the genome files written by this build.
+/// Files without header are weight files of version 1.
+/// Version 2 contains the whole genome, unknown genes take their default values.
+pub const GENOME_VERSION: u32 = 2;
+/// Number of bits of the species hash.
+pub const SPECIES_HASH_BITS: u32 = 8;
+/// Scales the genes besides the network up,
+/// so they matter against the many weights in the species hash.
+const SPECIES_GENE_WEIGHT: f32 = 4.0;
+
+/// Everything a corgi inherits.
+/// Every gene mutates on its own when a child is born.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Genome {
+    pub brain: BrainGene,
+    pub body: BodyGene,
+    pub senses: SenseGene,
+    pub metabolism: MetabolismGene,
+    pub plasticity: PlasticityGene,
+    /// Only used by the diploid genetics.
+    pub dominance: Dominance,
+}
+
+impl Genome {
+    /// A random genome, the network is created once the brain layout is known.
+    pub fn new_random<R: Rng>(rng: &mut R) -> Self {
+        Self {
+            brain: BrainGene::default(),
+            body: BodyGene::new_random(rng),
+            senses: SenseGene::new_random(rng),
+            metabolism: MetabolismGene::new_random(rng),
+            plasticity: PlasticityGene::default(),
+            dominance: Dominance::new_random(rng),
+        }
+    }
+
+    /// A random genome thinking with `network`.
+    pub fn with_network<R: Rng>(network: NeuralNetwork, rng: &mut R) -> Self {
+        Self {
+            brain: BrainGene::from_network(network),
+            ..Self::new_random(rng)
+        }
+    }
+
+    /// Collects the inherited genes of a living corgi.
+    pub fn from_parts(brain: &BrainGene, senses: &SenseGene, genotype: &Genotype) -> Self {
+        Self {
+            brain: brain.clone(),
+            body: genotype.body.clone(),
+            senses: senses.clone(),
+            metabolism: genotype.metabolism.clone(),
+            plasticity: genotype.plasticity.clone(),
+            dominance: Dominance::default(),
+        }
+    }
+
+    /// The genes which get developed into the phenotype.
+    pub fn genotype(&self) -> Genotype {
+        Genotype {
+            body: self.body.clone(),
+            metabolism: self.metabolism.clone(),
+            plasticity: self.plasticity.clone(),
+        }
+    }
+
+    /// The body and metabolism developed in `environment`, as shaped by the plasticity.
+    pub fn develop(&self, environment: &Environment) -> (BodyGene, MetabolismGene) {
+        // the signals are centered, so a neutral environment changes nothing
+        let crowding = 2.0 * environment.crowding - 1.0;
+        let light = 2.0 * environment.light - 1.0;
+        let mut body = self.body.clone();
+        let growth = PLASTICITY_SIZE_RANGE * self.plasticity.size_by_crowding * crowding;
+        body.size = (body.size * (1.0 + growth))
+            .max(MIN_BODY_SIZE)
+            .min(MAX_BODY_SIZE);
+        let mut metabolism = self.metabolism.clone();
+        metabolism.photosynthesis = (metabolism.photosynthesis
+            + PLASTICITY_PHOTOSYNTHESIS_RANGE * self.plasticity.photosynthesis_by_light * light)
+            .max(0.0)
+            .min(1.0);
+        (body, metabolism)
+    }
+
+    /// Genetic distance with the default coefficients.
+    pub fn distance(&self, other: &Self) -> f32 {
+        self.distance_with(other, &DistanceCoefficients::default())
+    }
+
+    /// Weighted sum of the weight distance, the structural mismatch of the networks
+    /// and the mean difference of the other genes.
+    /// Networks of different shapes only differ structurally.
+    pub fn distance_with(&self, other: &Self, coefficients: &DistanceCoefficients) -> f32 {
+        let (weights, structure) = match (&self.brain.network, &other.brain.network) {
+            (Some(a), Some(b)) => (
+                a.weight_distance(b).unwrap_or(0.0),
+                a.structural_distance(b) as f32,
+            ),
+            (None, None) => (0.0, 0.0),
+            _ => (0.0, 1.0),
+        };
+        let flag = |a: bool, b: bool| if a == b { 0.0 } else { 1.0 };
+        let genes = [
+            (self.brain.smoothing - other.brain.smoothing).abs(),
+            (self.body.mass - other.body.mass).abs(),
+            (self.body.size - other.body.size).abs(),
+            (self.body.color[0] - other.body.color[0]).abs(),
+            (self.body.color[1] - other.body.color[1]).abs(),
+            (self.body.color[2] - other.body.color[2]).abs(),
+            (self.body.swimming - other.body.swimming).abs(),
+            (self.body.weapon - other.body.weapon).abs(),
+            (self.body.strength - other.body.strength).abs(),
+            (self.body.pace - other.body.pace).abs(),
+            flag(self.senses.vision, other.senses.vision),
+            flag(self.senses.smell, other.senses.smell),
+            flag(self.senses.hearing, other.senses.hearing),
+            flag(self.senses.compass, other.senses.compass),
+            (self.metabolism.photosynthesis - other.metabolism.photosynthesis).abs(),
+            (self.metabolism.comfort - other.metabolism.comfort).abs(),
+            (self.metabolism.tolerance - other.metabolism.tolerance).abs(),
+            (self.metabolism.appetite - other.metabolism.appetite).abs(),
+            (self.metabolism.longevity - other.metabolism.longevity).abs(),
+            (self.metabolism.toxin_resistance - other.metabolism.toxin_resistance).abs(),
+            (self.metabolism.maturation - other.metabolism.maturation).abs(),
+            (self.metabolism.diet - other.metabolism.diet).abs(),
+            (self.metabolism.immunity - other.metabolism.immunity).abs(),
+        ];
+        let genes = genes.iter().sum::<f32>() / genes.len() as f32;
+        coefficients.weights * weights
+            + coefficients.structure * structure
+            + coefficients.genes * genes
+    }
+
+    /// A locality-sensitive hash with `SPECIES_HASH_BITS` bits, similar genomes share most of them.
+    /// Every bit is the side of a fixed random hyperplane the genome lies on,
+    /// so small mutations rarely flip a bit.
+    pub fn species_hash(&self) -> u64 {
+        let genes = [
+            self.brain.smoothing,
+            self.body.mass - 1.0,
+            self.body.size - 1.0,
+            self.body.color[0] - 0.5,
+            self.body.color[1] - 0.5,
+            self.body.color[2] - 0.5,
+            self.body.swimming - 0.5,
+            self.body.weapon - 0.5,
+            self.body.strength - 0.5,
+            self.body.pace - 0.5,
+            self.metabolism.photosynthesis - 0.5,
+            self.metabolism.comfort - 0.5,
+            self.metabolism.tolerance - 0.25,
+            self.metabolism.appetite - 0.5,
+            self.metabolism.longevity - 0.5,
+            self.metabolism.toxin_resistance - 0.5,
+            self.metabolism.maturation - 0.5,
+            self.metabolism.diet - 0.5,
+            self.metabolism.immunity - 0.5,
+        ];
+        let senses = [
+            self.senses.vision,
+            self.senses.smell,
+            self.senses.hearing,
+            self.senses.compass,
+        ];
+        let features: Vec<f32> = genes
+            .iter()
+            .copied()
+            .chain(senses.iter().map(|&sense| if sense { 0.5 } else { -0.5 }))
+            .map(|feature| feature * SPECIES_GENE_WEIGHT)
+            .chain(
+                self.brain
+                    .network
+                    .iter()
+                    .flat_map(|network| network.parameters()),
+            )
+            .collect();
+
+        (0..SPECIES_HASH_BITS).fold(0, |hash, bit| {
+            let side: f32 = features
+                .iter()
+                .enumerate()
+                .map(|(i, feature)| {
+                    if splitmix64((u64::from(bit) << 32) | i as u64) & 1 == 0 {
+                        *feature
+                    } else {
+                        -*feature
+                    }
+                })
+                .sum();
+            hash | (u64::from(side > 0.0) << bit)
+        })
+    }
+
+    /// What changed from `self` to `other`.
+    pub fn diff(&self, other: &Self) -> GenomeDiff {
+        let network = match (&self.brain.network, &other.brain.network) {
+            (Some(a), Some(b)) => Some(a.diff(b)),
+            _ => None,
+        };
+        let mut genes = Vec::new();
+        let mut compare = |name: &'static str, from: String, to: String| {
+            if from != to {
+                genes.push(GeneChange { name, from, to });
+            }
+        };
+        compare(
+            "brain.smoothing",
+            self.brain.smoothing.to_string(),
+            other.brain.smoothing.to_string(),
+        );
+        compare(
+            "brain.mutation_rate",
+            self.brain.mutation_rate.to_string(),
+            other.brain.mutation_rate.to_string(),
+        );
+        compare(
+            "brain.mutation_sigma",
+            self.brain.mutation_sigma.to_string(),
+            other.brain.mutation_sigma.to_string(),
+        );
+        compare(
+            "body.mass",
+            self.body.mass.to_string(),
+            other.body.mass.to_string(),
+        );
+        compare(
+            "body.size",
+            self.body.size.to_string(),
+            other.body.size.to_string(),
+        );
+        compare(
+            "body.color",
+            format!("{:?}", self.body.color),
+            format!("{:?}", other.body.color),
+        );
+        compare(
+            "body.swimming",
+            self.body.swimming.to_string(),
+            other.body.swimming.to_string(),
+        );
+        compare(
+            "body.weapon",
+            self.body.weapon.to_string(),
+            other.body.weapon.to_string(),
+        );
+        compare(
+            "body.strength",
+            self.body.strength.to_string(),
+            other.body.strength.to_string(),
+        );
+        compare(
+            "body.pace",
+            self.body.pace.to_string(),
+            other.body.pace.to_string(),
+        );
+        compare(
+            "senses.vision",
+            self.senses.vision.to_string(),
+            other.senses.vision.to_string(),
+        );
+        compare(
+            "senses.smell",
+            self.senses.smell.to_string(),
+            other.senses.smell.to_string(),
+        );
+        compare(
+            "senses.hearing",
+            self.senses.hearing.to_string(),
+            other.senses.hearing.to_string(),
+        );
+        compare(
+            "senses.compass",
+            self.senses.compass.to_string(),
+            other.senses.compass.to_string(),
+        );
+        compare(
+            "metabolism.photosynthesis",
+            self.metabolism.photosynthesis.to_string(),
+            other.metabolism.photosynthesis.to_string(),
+        );
+        compare(
+            "metabolism.comfort",
+            self.metabolism.comfort.to_string(),
+            other.metabolism.comfort.to_string(),
+        );
+        compare(
+            "metabolism.tolerance",
+            self.metabolism.tolerance.to_string(),
+            other.metabolism.tolerance.to_string(),
+        );
+        compare(
+            "metabolism.appetite",
+            self.metabolism.appetite.to_string(),
+            other.metabolism.appetite.to_string(),
+        );
+        compare(
+            "metabolism.longevity",
+            self.metabolism.longevity.to_string(),
+            other.metabolism.longevity.to_string(),
+        );
+        compare(
+            "metabolism.toxin_resistance",
+            self.metabolism.toxin_resistance.to_string(),
+            other.metabolism.toxin_resistance.to_string(),
+        );
+        compare(
+            "metabolism.maturation",
+            self.metabolism.maturation.to_string(),
+            other.metabolism.maturation.to_string(),
+        );
+        compare(
+            "metabolism.diet",
+            self.metabolism.diet.to_string(),
+            other.metabolism.diet.to_string(),
+        );
+        compare(
+            "metabolism.immunity",
+            self.metabolism.immunity.to_string(),
+            other.metabolism.immunity.to_string(),
+        );
+        compare(
+            "plasticity",
+            format!("{:?}", self.plasticity),
+            format!("{:?}", other.plasticity),
+        );
+        compare(
+            "dominance",
+            format!("{:?}", self.dominance),
+            format!("{:?}", other.dominance),
+        );
+        GenomeDiff { network, genes }
+    }
+
+    /// A mutated copy for a child.
+    pub fn child<R: Rng>(&self, rng: &mut R, noise: &MutationNoise) -> Self {
+        let mut child = self.clone();
+        child.brain.mutate(rng, noise);
+        child.body.mutate(rng);
+        child.senses.mutate(rng);
+        child.metabolism.mutate(rng);
+        child.plasticity.mutate(rng);
+        child.dominance.mutate(rng);
+        child
+    }
+
+    /// Recombines two parents, every gene comes from either of them.
+    /// The result still has to be mutated with `child`.
+    pub fn crossover<R: Rng>(&self, other: &Self, rng: &mut R) -> Self {
+        Self {
+            brain: self.brain.crossover(&other.brain, rng),
+            body: self.body.crossover(&other.body, rng),
+            senses: self.senses.crossover(&other.senses, rng),
+            metabolism: pick(rng, &self.metabolism, &other.metabolism),
+            plasticity: pick(rng, &self.plasticity, &other.plasticity),
+            dominance: self.dominance.crossover(&other.dominance, rng),
+        }
+    }
+
+    /// Loads a genome file of any version, older versions are upgraded.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, GenomeError> {
+        let string = fs::read_to_string(path)?;
+        let mut lines = string.splitn(2, '\n');
+        let (version, body) = match (lines.next(), lines.next()) {
+            (Some(header), Some(body)) if header.starts_with(GENOME_MAGIC) => {
+                let version = header[GENOME_MAGIC.len()..]
+                    .trim()
+                    .parse()
+                    .map_err(|_| GenomeError::InvalidHeader(header.to_string()))?;
+                (version, body)
+            }
+            _ => (1, string.as_str()),
+        };
+        let genome = Self::upgrade(version, body)?;
+        if let Some(network) = &genome.brain.network {
+            if !network.is_valid() {
+                return Err(WeightsError::InvalidShape.into());
+            }
+        }
+        Ok(genome)
+    }
+
+    fn upgrade(version: u32, body: &str) -> Result<Self, GenomeError> {
+        match version {
+            1 => {
+                let network: NeuralNetwork = ron::de::from_str(body).map_err(WeightsError::from)?;
+                Ok(Self {
+                    brain: BrainGene::from_network(network),
+                    ..Default::default()
+                })
+            }
+            2 => Ok(ron::de::from_str(body).map_err(WeightsError::from)?),
+            version => Err(GenomeError::UnsupportedVersion(version)),
+        }
+    }
+
+    /// Saves the genome in the current version of the format.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), GenomeError> {
+        let body = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(WeightsError::from)?;
+        fs::write(
+            path,
+            format!("{} {}\n{}", GENOME_MAGIC, GENOME_VERSION, body),
+        )?;
+        Ok(())
+    }
+}
+
+/// A scalar gene which differs between two genomes.
+#[derive(Clone, Debug)]
+pub struct GeneChange {
+    pub name: &'static str,
+    pub from: String,
+    pub to: String,
+}
+
+/// The result of `Genome::diff`, pretty-printed by `Display`.
+#[derive(Clone, Debug)]
+pub struct GenomeDiff {
+    /// `None` if one of the genomes has no network yet.
+    pub network: Option<NetworkDiff>,
+    pub genes: Vec<GeneChange>,
+}
+
+impl fmt::Display for GenomeDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.network {
+            Some(NetworkDiff::Structural { from, to }) => {
+                writeln!(f, "network: shape {:?} -> {:?}", from, to)?
+            }
+            Some(NetworkDiff::Weights(layers)) => {
+                writeln!(f, "network:")?;
+                for (i, layer) in layers.iter().enumerate() {
+                    writeln!(f, "  layer {}: {}", i, layer)?;
+                }
+            }
+            None => writeln!(f, "network: missing")?,
+        }
+        if self.genes.is_empty() {
+            writeln!(f, "genes: unchanged")?;
+        } else {
+            writeln!(f, "genes:")?;
+            for change in &self.genes {
+                writeln!(f, "  {}: {} -> {}", change.name, change.from, change.to)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Weights of the terms of `Genome::distance_with`.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DistanceCoefficients {
+    /// Per unit of mean absolute weight difference.
+    pub weights: f32,
+    /// Per neuron more or less.
+    pub structure: f32,
+    /// Per unit of mean difference of the other genes.
+    pub genes: f32,
+}
+
+impl Default for DistanceCoefficients {
+    fn default() -> Self {
+        Self {
+            weights: 1.0,
+            structure: 0.1,
+            genes: 1.0,
+        }
+    }
+}
+
+/// A fast deterministic mix of the bits, for the hyperplanes of the species hash.
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// One of both values with the same probability.
+fn pick<T: Clone, R: Rng>(rng: &mut R, a: &T, b: &T) -> T {
+    if rng.gen() {
+        a.clone()
+    } else {
+        b.clone()
+    }
+}
+
+#[derive(Debug)]
+pub enum GenomeError {
+    Io(io::Error),
+    Weights(WeightsError),
+    InvalidHeader(String),
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for GenomeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "io error: {}", e),
+            Self::Weights(e) => write!(f, "{}", e),
+            Self::InvalidHeader(header) => write!(f, "invalid genome header `{}`", header),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "genome version {} is newer than the supported version {}",
+                version, GENOME_VERSION
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GenomeError {}
+
+impl From<io::Error> for GenomeError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<WeightsError> for GenomeError {
+    fn from(e: WeightsError) -> Self {
+        Self::Weights(e)
+    }
+}
+
+/// Standard deviation of the noise added to the smoothing coefficient of a child.
+const SMOOTHING_MUTATION_SIGMA: f32 = 0.05;
+/// Upper bound of the smoothing coefficient, so decisions never freeze completely.
+const MAX_SMOOTHING: f32 = 0.95;
+/// Learning rate of the log-normal self-adaptation of the mutation parameters.
+const MUTATION_ADAPTATION_RATE: f32 = 0.2;
+const MUTATION_RATE_BOUNDS: (f32, f32) = (0.001, 1.0);
+const MUTATION_SIGMA_BOUNDS: (f32, f32) = (0.001, 2.0);
+/// Probability of a child to get a block of hidden neurons duplicated.
+const DUPLICATION_PROBABILITY: f64 = 0.01;
+/// Probability of a child to get a hidden layer inserted or removed, each.
+const LAYER_MUTATION_PROBABILITY: f64 = 0.002;
+
+/// The weights of the brain and how it processes its output.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BrainGene {
+    /// `None` until the dry-run determined the layout, then a random network is created.
+    pub network: Option<NeuralNetwork>,
+    /// Coefficient of the exponential smoothing of the smoothed decisions.
+    /// 0 passes the raw network output through.
+    pub smoothing: f32,
+    /// Probability of every weight to be mutated in a child.
+    pub mutation_rate: f32,
+    /// Standard deviation of the noise added to a mutated weight.
+    pub mutation_sigma: f32,
+}
+
+impl Default for BrainGene {
+    fn default() -> Self {
+        Self {
+            network: None,
+            smoothing: 0.0,
+            mutation_rate: MUTATION_RATE,
+            mutation_sigma: MUTATION_SIGMA,
+        }
+    }
+}
+
+impl BrainGene {
+    pub fn from_network(network: NeuralNetwork) -> Self {
+        Self {
+            network: Some(network),
+            ..Default::default()
+        }
+    }
+
+    pub fn crossover<R: Rng>(&self, other: &Self, rng: &mut R) -> Self {
+        let network = match (&self.network, &other.network) {
+            (Some(a), Some(b)) => Some(a.crossover(b, rng)),
+            (a, b) => a.clone().or_else(|| b.clone()),
+        };
+        Self {
+            network,
+            smoothing: pick(rng, &self.smoothing, &other.smoothing),
+            mutation_rate: pick(rng, &self.mutation_rate, &other.mutation_rate),
+            mutation_sigma: pick(rng, &self.mutation_sigma, &other.mutation_sigma),
+        }
+    }
+
+    /// A gene without a network yet passes on nothing.
+    /// The mutation parameters mutate themselves first and then mutate the weights,
+    /// so lineages can tune their own evolvability.
+    /// Rarely a block of hidden neurons gets duplicated before,
+    /// giving evolution redundant copies to specialize,
+    /// and hidden layers get inserted or removed, so the depth can evolve.
+    pub fn mutate<R: Rng>(&mut self, rng: &mut R, noise: &MutationNoise) {
+        let network = match &mut self.network {
+            Some(network) => network,
+            None => {
+                *self = Self::default();
+                return;
+            }
+        };
+        let adapt = |value: f32, (min, max): (f32, f32), rng: &mut R| {
+            let factor =
+                (MUTATION_ADAPTATION_RATE * rng.sample::<f32, _>(rand_distr::StandardNormal)).exp();
+            (value * factor).max(min).min(max)
+        };
+        self.mutation_rate = adapt(self.mutation_rate, MUTATION_RATE_BOUNDS, rng);
+        self.mutation_sigma = adapt(self.mutation_sigma, MUTATION_SIGMA_BOUNDS, rng);
+        if rng.gen_bool(DUPLICATION_PROBABILITY) {
+            network.duplicate_neurons(rng);
+        }
+        if rng.gen_bool(LAYER_MUTATION_PROBABILITY) {
+            network.insert_layer(rng);
+        }
+        if rng.gen_bool(LAYER_MUTATION_PROBABILITY) {
+            network.remove_layer(rng);
+        }
+        network.mutate(rng, self.mutation_rate, self.mutation_sigma, noise);
+        let noise = Normal::new(0.0, SMOOTHING_MUTATION_SIGMA).unwrap();
+        self.smoothing = (self.smoothing + noise.sample(rng))
+            .max(0.0)
+            .min(MAX_SMOOTHING);
+    }
+}
+
+/// Standard deviation of the mutation of the body genes.
+const BODY_MUTATION_SIGMA: f32 = 0.05;
+const MIN_BODY_SIZE: f32 = 0.5;
+const MAX_BODY_SIZE: f32 = 2.0;
+const MIN_BODY_DENSITY: f32 = 0.5;
+const MAX_BODY_DENSITY: f32 = 2.0;
+/// Swimming efficiency of the first corgis is at most this.
+const INITIAL_MAX_SWIMMING: f32 = 0.2;
+/// Fraction of the movement force on land lost by a perfect swimmer.
+const SWIMMING_LAND_SLOWDOWN: f32 = 0.4;
+/// Weapon of the first corgis is at most this.
+const INITIAL_MAX_WEAPON: f32 = 0.2;
+/// Factor of the maximum movement force without any strength.
+const MIN_STRENGTH_FACTOR: f32 = 0.5;
+/// Additional density of the strongest body compared to the default strength.
+const STRENGTH_MASS_FACTOR: f32 = 0.5;
+/// Factor of the maximum movement force with the slowest pace.
+const MIN_PACE_FACTOR: f32 = 0.7;
+/// Factor of the attack damage without any weapon.
+const MIN_WEAPON_FACTOR: f32 = 0.2;
+
+/// The physical build of a corgi.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BodyGene {
+    /// Factor of the density of the body.
+    pub mass: f32,
+    /// Factor of the radius of the body.
+    pub size: f32,
+    /// Base color of the body, in linear RGB.
+    pub color: [f32; 3],
+    /// Between 0 and 1, how efficiently the corgi swims.
+    /// Good swimmers are slower on land.
+    pub swimming: f32,
+    /// Between 0 and 1, how much damage the attacks of the corgi do.
+    /// Costs upkeep.
+    pub weapon: f32,
+    /// Between 0 and 1, caps the movement force.
+    /// Strong muscles make the body heavier.
+    pub strength: f32,
+    /// Between 0 and 1, trades the energy efficiency of the movement for a stronger force.
+    pub pace: f32,
+}
+
+impl Default for BodyGene {
+    fn default() -> Self {
+        Self {
+            mass: 1.0,
+            size: 1.0,
+            color: [0.5; 3],
+            swimming: 0.0,
+            weapon: 0.0,
+            strength: 0.5,
+            pace: 0.5,
+        }
+    }
+}
+
+impl BodyGene {
+    pub fn new_random<R: Rng>(rng: &mut R) -> Self {
+        Self {
+            mass: 1.0,
+            size: 1.0,
+            color: [rng.gen(), rng.gen(), rng.gen()],
+            swimming: rng.gen_range(0.0..INITIAL_MAX_SWIMMING),
+            weapon: rng.gen_range(0.0..INITIAL_MAX_WEAPON),
+            strength: rng.gen_range(0.3..0.7),
+            pace: rng.gen_range(0.3..0.7),
+        }
+    }
+
+    /// Factor of the movement force on land.
+    pub fn land_speed_factor(&self) -> f32 {
+        1.0 - self.swimming * SWIMMING_LAND_SLOWDOWN
+    }
+
+    /// Factor of the maximum movement force, 1 for the default strength.
+    pub fn strength_factor(&self) -> f32 {
+        MIN_STRENGTH_FACTOR + self.strength * (2.0 - 2.0 * MIN_STRENGTH_FACTOR)
+    }
+
+    /// Factor of the density of the body, 1 for the default strength.
+    pub fn muscle_mass_factor(&self) -> f32 {
+        1.0 + (self.strength - 0.5) * STRENGTH_MASS_FACTOR
+    }
+
+    /// Factor of the maximum movement force, 1 for the default pace.
+    pub fn pace_factor(&self) -> f32 {
+        MIN_PACE_FACTOR + self.pace * (2.0 - 2.0 * MIN_PACE_FACTOR)
+    }
+
+    /// Factor of the energy per work of the movement, 1 for the default pace.
+    /// Grows quadratically with the force, so a fast pace is less efficient.
+    pub fn pace_cost_factor(&self) -> f32 {
+        self.pace_factor().powi(2)
+    }
+
+    /// Factor of the attack damage.
+    pub fn weapon_factor(&self) -> f32 {
+        MIN_WEAPON_FACTOR + self.weapon * (1.0 - MIN_WEAPON_FACTOR)
+    }
+
+    pub fn crossover<R: Rng>(&self, other: &Self, rng: &mut R) -> Self {
+        let mut color = self.color;
+        for (channel, other) in color.iter_mut().zip(other.color.iter()) {
+            *channel = pick(rng, channel, other);
+        }
+        Self {
+            mass: pick(rng, &self.mass, &other.mass),
+            size: pick(rng, &self.size, &other.size),
+            color,
+            swimming: pick(rng, &self.swimming, &other.swimming),
+            weapon: pick(rng, &self.weapon, &other.weapon),
+            strength: pick(rng, &self.strength, &other.strength),
+            pace: pick(rng, &self.pace, &other.pace),
+        }
+    }
+
+    pub fn mutate<R: Rng>(&mut self, rng: &mut R) {
+        let noise = Normal::new(0.0, BODY_MUTATION_SIGMA).unwrap();
+        self.mass = (self.mass + noise.sample(rng))
+            .max(MIN_BODY_DENSITY)
+            .min(MAX_BODY_DENSITY);
+        self.size = (self.size + noise.sample(rng))
+            .max(MIN_BODY_SIZE)
+            .min(MAX_BODY_SIZE);
+        for channel in self.color.iter_mut() {
+            *channel = (*channel + noise.sample(rng)).max(0.0).min(1.0);
+        }
+        self.swimming = (self.swimming + noise.sample(rng)).max(0.0).min(1.0);
+        self.weapon = (self.weapon + noise.sample(rng)).max(0.0).min(1.0);
+        self.strength = (self.strength + noise.sample(rng)).max(0.0).min(1.0);
+        self.pace = (self.pace + noise.sample(rng)).max(0.0).min(1.0);
+    }
+}
+
+/// Probability of a sense gene to flip when mutating.
+const SENSE_FLIP_PROBABILITY: f64 = 0.02;
+/// Energy every enabled sense costs per tick.
+const SENSE_UPKEEP: f32 = 0.01;
+
+/// Which optional senses a corgi has.
+/// Disabled senses perceive zeros, so the brain layout stays the same,
+/// but they don't cost any energy.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SenseGene {
+    pub vision: bool,
+    pub smell: bool,
+    pub hearing: bool,
+    pub compass: bool,
+}
+
+/// The senses every corgi had before they became genes.
+impl Default for SenseGene {
+    fn default() -> Self {
+        Self {
+            vision: true,
+            smell: true,
+            hearing: true,
+            compass: false,
+        }
+    }
+}
+
+impl SenseGene {
+    pub fn new_random<R: Rng>(rng: &mut R) -> Self {
+        Self {
+            vision: rng.gen(),
+            smell: rng.gen(),
+            hearing: rng.gen(),
+            compass: rng.gen(),
+        }
+    }
+
+    pub fn mutate<R: Rng>(&mut self, rng: &mut R) {
+        for sense in self.senses_mut().iter_mut() {
+            if rng.gen_bool(SENSE_FLIP_PROBABILITY) {
+                **sense = !**sense;
+            }
+        }
+    }
+
+    pub fn crossover<R: Rng>(&self, other: &Self, rng: &mut R) -> Self {
+        Self {
+            vision: pick(rng, &self.vision, &other.vision),
+            smell: pick(rng, &self.smell, &other.smell),
+            hearing: pick(rng, &self.hearing, &other.hearing),
+            compass: pick(rng, &self.compass, &other.compass),
+        }
+    }
+
+    fn senses_mut(&mut self) -> [&mut bool; 4] {
+        [
+            &mut self.vision,
+            &mut self.smell,
+            &mut self.hearing,
+            &mut self.compass,
+        ]
+    }
+
+    pub fn enabled_count(&self) -> usize {
+        [self.vision, self.smell, self.hearing, self.compass]
+            .iter()
+            .filter(|enabled| **enabled)
+            .count()
+    }
+
+    /// Energy the senses cost per tick.
+    pub fn upkeep(&self) -> f32 {
+        self.enabled_count() as f32 * SENSE_UPKEEP
+    }
+}
+
+/// Standard deviation of the mutation of the metabolism genes.
+const METABOLISM_MUTATION_SIGMA: f32 = 0.05;
+/// Photosynthesis of the first corgis is at most this.
+const INITIAL_MAX_PHOTOSYNTHESIS: f32 = 0.2;
+/// Additional mass of a corgi with full photosynthesis.
+const PHOTOSYNTHESIS_MASS_FACTOR: f32 = 1.0;
+/// Fraction of the movement force lost with full photosynthesis.
+const PHOTOSYNTHESIS_SLOWDOWN: f32 = 0.5;
+/// Factor of the bite size of a corgi without any appetite.
+const MIN_APPETITE_FACTOR: f32 = 0.5;
+/// Maximum age in ticks of a corgi with the default longevity.
+pub const BASE_LIFESPAN: usize = 30_000;
+/// Factor of the lifespan of a corgi without any longevity.
+const MIN_LIFESPAN_FACTOR: f32 = 0.5;
+/// Age in ticks at which a corgi with the default maturation grows up.
+pub const BASE_MATURATION_AGE: usize = 3_000;
+/// Factor of the maturation age of a corgi maturing as early as possible.
+const MIN_MATURATION_FACTOR: f32 = 0.5;
+/// Random corgis start with at most this diet, they are mostly herbivores.
+const INITIAL_MAX_DIET: f32 = 0.2;
+/// Fraction of the energy of its food a corgi with the opposite diet digests.
+const MIN_DIET_EFFICIENCY: f32 = 0.2;
+/// Random corgis start with at most this immunity.
+const INITIAL_MAX_IMMUNITY: f32 = 0.2;
+/// Random corgis start with at most this toxin resistance.
+const INITIAL_MAX_TOXIN_RESISTANCE: f32 = 0.2;
+/// The comfort range is at most this far around the comfort temperature.
+pub const MAX_TEMPERATURE_TOLERANCE: f32 = 0.5;
+
+/// How the corgi gains and spends energy.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetabolismGene {
+    /// Between 0 and 1, how much energy the corgi gains from light.
+    /// Makes it heavier and slower.
+    pub photosynthesis: f32,
+    /// The preferred temperature, between 0 and 1.
+    pub comfort: f32,
+    /// How far the temperature can be from `comfort` without costing energy.
+    /// A wider range costs upkeep.
+    pub tolerance: f32,
+    /// Between 0 and 1, how fast the corgi eats.
+    /// A bigger appetite raises the basal metabolism.
+    pub appetite: f32,
+    /// Between 0 and 1, how long the corgi can live at most.
+    /// A longer life costs upkeep.
+    pub longevity: f32,
+    /// Between 0 and 1, how much of the damage of toxic tiles the corgi resists.
+    /// Costs upkeep.
+    pub toxin_resistance: f32,
+    /// Between 0 and 1, how late the corgi grows up.
+    /// Juveniles are slower, eat less and can't reproduce, maturing early costs upkeep.
+    pub maturation: f32,
+    /// Between 0 for herbivores and 1 for carnivores,
+    /// how efficiently the corgi digests meat instead of plants.
+    pub diet: f32,
+    /// Between 0 and 1, how well the corgi resists and recovers from infections.
+    /// Costs upkeep.
+    pub immunity: f32,
+}
+
+impl Default for MetabolismGene {
+    fn default() -> Self {
+        Self {
+            photosynthesis: 0.0,
+            comfort: 0.5,
+            tolerance: 0.2,
+            appetite: 0.5,
+            longevity: 0.5,
+            toxin_resistance: 0.0,
+            maturation: 0.5,
+            diet: 0.0,
+            immunity: 0.0,
+        }
+    }
+}
+
+impl MetabolismGene {
+    pub fn new_random<R: Rng>(rng: &mut R) -> Self {
+        Self {
+            photosynthesis: rng.gen_range(0.0..INITIAL_MAX_PHOTOSYNTHESIS),
+            comfort: rng.gen_range(0.3..0.7),
+            tolerance: rng.gen_range(0.1..0.3),
+            appetite: rng.gen_range(0.3..0.7),
+            longevity: rng.gen_range(0.3..0.7),
+            toxin_resistance: rng.gen_range(0.0..INITIAL_MAX_TOXIN_RESISTANCE),
+            maturation: rng.gen_range(0.3..0.7),
+            diet: rng.gen_range(0.0..INITIAL_MAX_DIET),
+            immunity: rng.gen_range(0.0..INITIAL_MAX_IMMUNITY),
+        }
+    }
+
+    pub fn mutate<R: Rng>(&mut self, rng: &mut R) {
+        let noise = Normal::new(0.0, METABOLISM_MUTATION_SIGMA).unwrap();
+        self.photosynthesis = (self.photosynthesis + noise.sample(rng)).max(0.0).min(1.0);
+        self.comfort = (self.comfort + noise.sample(rng)).max(0.0).min(1.0);
+        self.tolerance = (self.tolerance + noise.sample(rng))
+            .max(0.0)
+            .min(MAX_TEMPERATURE_TOLERANCE);
+        self.appetite = (self.appetite + noise.sample(rng)).max(0.0).min(1.0);
+        self.longevity = (self.longevity + noise.sample(rng)).max(0.0).min(1.0);
+        self.toxin_resistance = (self.toxin_resistance + noise.sample(rng))
+            .max(0.0)
+            .min(1.0);
+        self.maturation = (self.maturation + noise.sample(rng)).max(0.0).min(1.0);
+        self.diet = (self.diet + noise.sample(rng)).max(0.0).min(1.0);
+        self.immunity = (self.immunity + noise.sample(rng)).max(0.0).min(1.0);
+    }
+
+    /// How far `temperature` is outside of the comfort range, 0 inside of it.
+    pub fn discomfort(&self, temperature: f32) -> f32 {
+        ((temperature - self.comfort).abs() - self.tolerance).max(0.0)
+    }
+
+    /// Factor of the density of the body.
+    pub fn mass_factor(&self) -> f32 {
+        1.0 + self.photosynthesis * PHOTOSYNTHESIS_MASS_FACTOR
+    }
+
+    /// Factor of the movement force.
+    pub fn speed_factor(&self) -> f32 {
+        1.0 - self.photosynthesis * PHOTOSYNTHESIS_SLOWDOWN
+    }
+
+    /// Factor of the bite size and the basal metabolism, 1 for the default appetite.
+    pub fn appetite_factor(&self) -> f32 {
+        MIN_APPETITE_FACTOR + self.appetite * (2.0 - 2.0 * MIN_APPETITE_FACTOR)
+    }
+
+    /// The maximum age in ticks, `BASE_LIFESPAN` for the default longevity.
+    pub fn lifespan(&self) -> usize {
+        let factor = MIN_LIFESPAN_FACTOR + self.longevity * (2.0 - 2.0 * MIN_LIFESPAN_FACTOR);
+        (BASE_LIFESPAN as f32 * factor) as usize
+    }
+
+    /// The age in ticks at which the corgi grows up, `BASE_MATURATION_AGE` for the default.
+    pub fn maturation_age(&self) -> usize {
+        let factor = MIN_MATURATION_FACTOR + self.maturation * (2.0 - 2.0 * MIN_MATURATION_FACTOR);
+        (BASE_MATURATION_AGE as f32 * factor) as usize
+    }
+
+    /// Fraction of the energy gained from grazed food, 1 for herbivores.
+    pub fn plant_efficiency(&self) -> f32 {
+        MIN_DIET_EFFICIENCY + (1.0 - self.diet) * (1.0 - MIN_DIET_EFFICIENCY)
+    }
+
+    /// Fraction of the energy gained from corpses, prey and eggs, 1 for carnivores.
+    pub fn meat_efficiency(&self) -> f32 {
+        MIN_DIET_EFFICIENCY + self.diet * (1.0 - MIN_DIET_EFFICIENCY)
+    }
+
+    /// Whether the corgi of `age` is grown up.
+    pub fn is_adult(&self, age: usize) -> bool {
+        age >= self.maturation_age()
+    }
+}
+
+/// Standard deviation of the noise added to the dominance of a child.
+const DOMINANCE_MUTATION_SIGMA: f32 = 0.05;
+/// Alleles with a smaller difference in dominance are both expressed.
+const CODOMINANCE_MARGIN: f32 = 0.1;
+
+/// How strongly each gene of a haploid genome is expressed
+/// against the other copy of a diploid corgi, between 0 and 1.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Dominance {
+    pub brain: f32,
+    pub body: f32,
+    pub senses: f32,
+    pub metabolism: f32,
+}
+
+impl Default for Dominance {
+    fn default() -> Self {
+        Self {
+            brain: 0.5,
+            body: 0.5,
+            senses: 0.5,
+            metabolism: 0.5,
+        }
+    }
+}
+
+impl Dominance {
+    pub fn new_random<R: Rng>(rng: &mut R) -> Self {
+        Self {
+            brain: rng.gen(),
+            body: rng.gen(),
+            senses: rng.gen(),
+            metabolism: rng.gen(),
+        }
+    }
+
+    fn crossover<R: Rng>(&self, other: &Self, rng: &mut R) -> Self {
+        Self {
+            brain: pick(rng, &self.brain, &other.brain),
+            body: pick(rng, &self.body, &other.body),
+            senses: pick(rng, &self.senses, &other.senses),
+            metabolism: pick(rng, &self.metabolism, &other.metabolism),
+        }
+    }
+
+    fn mutate<R: Rng>(&mut self, rng: &mut R) {
+        let noise = Normal::new(0.0, DOMINANCE_MUTATION_SIGMA).unwrap();
+        for value in [
+            &mut self.brain,
+            &mut self.body,
+            &mut self.senses,
+            &mut self.metabolism,
+        ]
+        .iter_mut()
+        {
+            **value = (**value + noise.sample(rng)).max(0.0).min(1.0);
+        }
+    }
+}
+
+/// Which allele is expressed.
+enum Expression {
+    First,
+    Second,
+    /// Numeric genes are averaged.
+    Both,
+}
+
+impl Expression {
+    fn of(a: f32, b: f32) -> Self {
+        if (a - b).abs() < CODOMINANCE_MARGIN {
+            Self::Both
+        } else if a > b {
+            Self::First
+        } else {
+            Self::Second
+        }
+    }
+}
+
+/// Both copies of the genome of a diploid corgi.
+/// The expressed phenotype lives in the usual gene components,
+/// the alleles are only needed for reproduction.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Alleles {
+    pub first: Genome,
+    pub second: Genome,
+}
+
+impl Alleles {
+    /// A homozygous corgi, both copies are the same.
+    pub fn homozygous(genome: Genome) -> Self {
+        Self {
+            first: genome.clone(),
+            second: genome,
+        }
+    }
+
+    /// The phenotype: the more dominant allele of every gene, codominant ones are averaged.
+    pub fn express(&self) -> Genome {
+        let (a, b) = (&self.first, &self.second);
+        let mean = |a: f32, b: f32| (a + b) / 2.0;
+
+        let brain = match Expression::of(a.dominance.brain, b.dominance.brain) {
+            Expression::First => a.brain.clone(),
+            Expression::Second => b.brain.clone(),
+            Expression::Both => BrainGene {
+                network: match (&a.brain.network, &b.brain.network) {
+                    (Some(x), Some(y)) => Some(x.average(y).unwrap_or_else(|| x.clone())),
+                    (x, y) => x.clone().or_else(|| y.clone()),
+                },
+                smoothing: mean(a.brain.smoothing, b.brain.smoothing),
+                mutation_rate: mean(a.brain.mutation_rate, b.brain.mutation_rate),
+                mutation_sigma: mean(a.brain.mutation_sigma, b.brain.mutation_sigma),
+            },
+        };
+        let body = match Expression::of(a.dominance.body, b.dominance.body) {
+            Expression::First => a.body.clone(),
+            Expression::Second => b.body.clone(),
+            Expression::Both => BodyGene {
+                mass: mean(a.body.mass, b.body.mass),
+                size: mean(a.body.size, b.body.size),
+                color: [
+                    mean(a.body.color[0], b.body.color[0]),
+                    mean(a.body.color[1], b.body.color[1]),
+                    mean(a.body.color[2], b.body.color[2]),
+                ],
+                swimming: mean(a.body.swimming, b.body.swimming),
+                weapon: mean(a.body.weapon, b.body.weapon),
+                strength: mean(a.body.strength, b.body.strength),
+                pace: mean(a.body.pace, b.body.pace),
+            },
+        };
+        // a sense works if any codominant copy of it works
+        let senses = match Expression::of(a.dominance.senses, b.dominance.senses) {
+            Expression::First => a.senses.clone(),
+            Expression::Second => b.senses.clone(),
+            Expression::Both => SenseGene {
+                vision: a.senses.vision || b.senses.vision,
+                smell: a.senses.smell || b.senses.smell,
+                hearing: a.senses.hearing || b.senses.hearing,
+                compass: a.senses.compass || b.senses.compass,
+            },
+        };
+        let metabolism = match Expression::of(a.dominance.metabolism, b.dominance.metabolism) {
+            Expression::First => a.metabolism.clone(),
+            Expression::Second => b.metabolism.clone(),
+            Expression::Both => MetabolismGene {
+                photosynthesis: mean(a.metabolism.photosynthesis, b.metabolism.photosynthesis),
+                comfort: mean(a.metabolism.comfort, b.metabolism.comfort),
+                tolerance: mean(a.metabolism.tolerance, b.metabolism.tolerance),
+                appetite: mean(a.metabolism.appetite, b.metabolism.appetite),
+                longevity: mean(a.metabolism.longevity, b.metabolism.longevity),
+                toxin_resistance: mean(
+                    a.metabolism.toxin_resistance,
+                    b.metabolism.toxin_resistance,
+                ),
+                maturation: mean(a.metabolism.maturation, b.metabolism.maturation),
+                diet: mean(a.metabolism.diet, b.metabolism.diet),
+                immunity: mean(a.metabolism.immunity, b.metabolism.immunity),
+            },
+        };
+        // the plasticity follows the body
+        let plasticity = match Expression::of(a.dominance.body, b.dominance.body) {
+            Expression::First => a.plasticity.clone(),
+            Expression::Second => b.plasticity.clone(),
+            Expression::Both => PlasticityGene {
+                size_by_crowding: mean(
+                    a.plasticity.size_by_crowding,
+                    b.plasticity.size_by_crowding,
+                ),
+                photosynthesis_by_light: mean(
+                    a.plasticity.photosynthesis_by_light,
+                    b.plasticity.photosynthesis_by_light,
+                ),
+            },
+        };
+        Genome {
+            brain,
+            body,
+            senses,
+            metabolism,
+            plasticity,
+            dominance: Dominance::default(),
+        }
+    }
+
+    /// Gives both alleles a copy of `network` unless theirs already has its inputs and outputs.
+    /// Spawned corgis only get their network from the dry-run of the expressed brain,
+    /// without it their children would start with a blank brain.
+    pub fn adopt_network(&mut self, network: &NeuralNetwork) {
+        for allele in [&mut self.first, &mut self.second].iter_mut() {
+            let fits = allele.brain.network.as_ref().map_or(false, |own| {
+                own.inputs() == network.inputs() && own.outputs() == network.outputs()
+            });
+            if !fits {
+                allele.brain.network = Some(network.clone());
+            }
+        }
+    }
+
+    /// A recombined haploid copy passed on to a child, already mutated.
+    pub fn gamete<R: Rng>(&self, rng: &mut R, noise: &MutationNoise) -> Genome {
+        self.first.crossover(&self.second, rng).child(rng, noise)
+    }
+}
+
+/// Standard deviation of the mutation of the reaction norms.
+const PLASTICITY_MUTATION_SIGMA: f32 = 0.05;
+/// Maximum relative change of the body size by the environment.
+const PLASTICITY_SIZE_RANGE: f32 = 0.5;
+/// Maximum change of the photosynthesis by the environment.
+const PLASTICITY_PHOTOSYNTHESIS_RANGE: f32 = 0.2;
+
+/// The conditions at the birth place of a corgi, each between 0 and 1.
+/// Kept as a component, so snapshots develop the corgis the same way again.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Environment {
+    pub light: f32,
+    /// How many corgis live nearby, relative to a crowded region.
+    pub crowding: f32,
+}
+
+impl Default for Environment {
+    /// Neutral conditions, the genes develop unchanged.
+    fn default() -> Self {
+        Self {
+            light: 0.5,
+            crowding: 0.5,
+        }
+    }
+}
+
+/// Reaction norms, how strongly the development responds to the environment at birth.
+/// Between -1 and 1, 0 develops the same phenotype everywhere.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlasticityGene {
+    /// Positive grows bigger bodies in crowded regions.
+    pub size_by_crowding: f32,
+    /// Positive grows more photosynthesis when born in light.
+    pub photosynthesis_by_light: f32,
+}
+
+impl PlasticityGene {
+    pub fn mutate<R: Rng>(&mut self, rng: &mut R) {
+        let noise = Normal::new(0.0, PLASTICITY_MUTATION_SIGMA).unwrap();
+        for norm in [
+            &mut self.size_by_crowding,
+            &mut self.photosynthesis_by_light,
+        ]
+        .iter_mut()
+        {
+            **norm = (**norm + noise.sample(rng)).max(-1.0).min(1.0);
+        }
+    }
+}
+
+/// The inherited body, metabolism and plasticity of a corgi.
+/// The `BodyGene` and `MetabolismGene` components hold the developed phenotype,
+/// so children inherit these instead.
+#[derive(Clone, Debug)]
+pub struct Genotype {
+    pub body: BodyGene,
+    pub metabolism: MetabolismGene,
+    pub plasticity: PlasticityGene,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn diploid_child_inherits_the_network() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let network = NeuralNetwork::new_random(&[4, 3, 2], &mut rng);
+        let mut parent = Alleles::homozygous(Genome::new_random(&mut rng));
+        assert!(parent
+            .gamete(&mut rng, &MutationNoise::default())
+            .brain
+            .network
+            .is_none());
+
+        parent.adopt_network(&network);
+        let child = parent.gamete(&mut rng, &MutationNoise::default());
+        let inherited = child.brain.network.expect("the child has no network");
+        assert_eq!(inherited.inputs(), network.inputs());
+        assert_eq!(inherited.outputs(), network.outputs());
+    }
+
+    #[test]
+    fn adopting_keeps_fitting_networks() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let own = NeuralNetwork::new_random(&[4, 3, 2], &mut rng);
+        let expressed = NeuralNetwork::new_random(&[4, 5, 2], &mut rng);
+        let mut alleles = Alleles::homozygous(Genome::with_network(own.clone(), &mut rng));
+        alleles.adopt_network(&expressed);
+        assert_eq!(alleles.first.brain.network.unwrap().shape(), own.shape());
+    }
+
+    #[test]
+    fn species_hashes_are_stable() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let network = NeuralNetwork::new_random(&[4, 3, 2], &mut rng);
+        let genome = Genome::with_network(network, &mut rng);
+        let hash = genome.species_hash();
+        assert!(hash < 1 << SPECIES_HASH_BITS);
+        assert_eq!(genome.clone().species_hash(), hash);
+
+        let path = std::env::temp_dir().join("corgis-species-hash.genome");
+        genome.save(&path).unwrap();
+        let loaded = Genome::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.species_hash(), hash);
+    }
+
+    #[test]
+    fn old_genome_versions_are_upgraded() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let network = NeuralNetwork::new_random(&[4, 3, 2], &mut rng);
+        let path = std::env::temp_dir().join("corgis-genome-upgrade.genome");
+
+        // version 1 files are plain weight files
+        network.save(&path).unwrap();
+        let genome = Genome::load(&path).unwrap();
+        assert_eq!(genome.brain.network.unwrap().shape(), network.shape());
+        assert_eq!(genome.body.size, BodyGene::default().size);
+
+        // missing genes of version 2 take their default values
+        fs::write(&path, format!("{} 2\n(body: (size: 1.5))", GENOME_MAGIC)).unwrap();
+        let genome = Genome::load(&path).unwrap();
+        assert_eq!(genome.body.size, 1.5);
+        assert!(genome.brain.network.is_none());
+
+        fs::write(
+            &path,
+            format!("{} {}\n()", GENOME_MAGIC, GENOME_VERSION + 1),
+        )
+        .unwrap();
+        let result = Genome::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(GenomeError::UnsupportedVersion(_))));
+    }
+}
@@ -0,0 +1,90 @@
+use crate::{
+    corgi::{Corgi, Energy, Generation},
+    species::Species,
+    stats::RegionStats,
+    universe::Tick,
+};
+use bevy::{app::AppExit, prelude::*};
+use std::{collections::HashSet, path::PathBuf};
+
+/// Ticks between two progress lines.
+const REPORT_INTERVAL: usize = 1_000;
+
+/// A run without a window, as fast as possible.
+#[derive(Clone, Debug, Default)]
+pub struct HeadlessRun {
+    /// The run exits after this many ticks, runs until interrupted if `None`.
+    pub ticks: Option<usize>,
+    /// CSV file the region stats are written to at the end of the run.
+    pub stats: Option<PathBuf>,
+}
+
+/// Prints the progress of a `HeadlessRun` and ends it.
+/// Needs the schedule runner plugin instead of the window.
+pub struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_to_stage(stage::POST_UPDATE, report_progress.system())
+            .add_system_to_stage(stage::POST_UPDATE, finish_run.system());
+    }
+
+    fn name(&self) -> &str {
+        "HeadlessPlugin"
+    }
+}
+
+fn report(tick: usize, query: &Query<(&Energy, &Generation, Option<&Species>), With<Corgi>>) {
+    let mut species = HashSet::new();
+    let (population, best, generation) = query.iter().fold(
+        (0, 0.0f32, 0),
+        |(count, best, generation), (energy, corgi_generation, corgi_species)| {
+            species.extend(corgi_species);
+            (
+                count + 1,
+                best.max(energy.get()),
+                generation.max(corgi_generation.0),
+            )
+        },
+    );
+    println!(
+        "tick {}: {} corgis, {} species, best fitness {:.1}, generation {}",
+        tick,
+        population,
+        species.len(),
+        best,
+        generation
+    );
+}
+
+fn report_progress(
+    tick: Res<Tick>,
+    query: Query<(&Energy, &Generation, Option<&Species>), With<Corgi>>,
+) {
+    if tick.0 > 0 && tick.0 % REPORT_INTERVAL == 0 {
+        report(tick.0, &query);
+    }
+}
+
+/// Reports the final stats and exits once the run reached its ticks.
+fn finish_run(
+    run: Res<HeadlessRun>,
+    tick: Res<Tick>,
+    stats: Res<RegionStats>,
+    mut exit: ResMut<Events<AppExit>>,
+    mut finished: Local<bool>,
+    query: Query<(&Energy, &Generation, Option<&Species>), With<Corgi>>,
+) {
+    if *finished || run.ticks.map_or(true, |ticks| tick.0 < ticks) {
+        return;
+    }
+    *finished = true;
+    report(tick.0, &query);
+    if let Some(path) = &run.stats {
+        match stats.write_csv(path) {
+            Ok(()) => println!("region stats written to `{}`", path.display()),
+            Err(e) => eprintln!("failed to write `{}`: {}", path.display(), e),
+        }
+    }
+    exit.send(AppExit);
+}
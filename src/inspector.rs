@@ -0,0 +1,174 @@
+use crate::{
+    console::ConsoleCommand,
+    corgi::Corgi,
+    lineage::{Lineage, LineageId, LineageLog},
+};
+use bevy::prelude::*;
+use std::collections::HashSet;
+
+/// Distance from the center of a corgi in which a click selects it.
+const SELECTION_RADIUS: f32 = 10.0;
+const MRCA_KEY: KeyCode = KeyCode::M;
+
+/// Left click selects a corgi, shift + left click adds a second one.
+#[derive(Default)]
+pub struct Inspector {
+    pub selection: Vec<Entity>,
+}
+
+pub struct InspectorPlugin;
+
+impl Plugin for InspectorPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(Inspector::default())
+            // selecting also works while paused
+            .add_system_to_stage(stage::POST_UPDATE, select_corgis.system())
+            .add_system_to_stage(stage::POST_UPDATE, inspect_ancestry.system())
+            .add_system_to_stage(stage::POST_UPDATE, inspect_descendants.system());
+    }
+
+    fn name(&self) -> &str {
+        "InspectorPlugin"
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn select_corgis(
+    mut inspector: ResMut<Inspector>,
+    mut cursor_reader: Local<EventReader<CursorMoved>>,
+    cursor_events: Res<Events<CursorMoved>>,
+    mut cursor_position: Local<Vec2>,
+    buttons: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    windows: Res<Windows>,
+    cameras: Query<&Transform, With<Camera>>,
+    corgis: Query<(Entity, &Transform), With<Corgi>>,
+) {
+    if let Some(event) = cursor_reader.latest(&cursor_events) {
+        *cursor_position = event.position;
+    }
+    if !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    let (window, camera) = match (windows.get_primary(), cameras.iter().next()) {
+        (Some(window), Some(camera)) => (window, camera),
+        _ => return,
+    };
+    // the origin of the window is in the bottom left corner
+    let screen = *cursor_position - Vec2::new(window.width(), window.height()) / 2.0;
+    let world = camera.compute_matrix() * screen.extend(0.0).extend(1.0);
+    let world = Vec2::new(world.x, world.y);
+
+    let clicked = corgis
+        .iter()
+        .map(|(entity, transform)| {
+            let pos = Vec2::new(transform.translation.x, transform.translation.y);
+            (entity, (pos - world).length())
+        })
+        .filter(|(_, distance)| *distance <= SELECTION_RADIUS)
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+        .map(|(entity, _)| entity);
+
+    let adding = keys.pressed(KeyCode::LShift) || keys.pressed(KeyCode::RShift);
+    if !adding {
+        inspector.selection.clear();
+    }
+    if let Some(entity) = clicked {
+        if !inspector.selection.contains(&entity) {
+            inspector.selection.push(entity);
+        }
+        // only the two most recent selections are kept
+        while inspector.selection.len() > 2 {
+            inspector.selection.remove(0);
+        }
+    }
+}
+
+fn inspect_ancestry(
+    inspector: Res<Inspector>,
+    log: Res<LineageLog>,
+    keys: Res<Input<KeyCode>>,
+    mut reader: Local<EventReader<ConsoleCommand>>,
+    console: Res<Events<ConsoleCommand>>,
+    lineages: Query<&Lineage>,
+) {
+    let mut requests: Vec<Option<(LineageId, LineageId)>> = reader
+        .iter(&console)
+        .filter_map(|command| match command {
+            ConsoleCommand::Mrca(ids) => Some(*ids),
+            _ => None,
+        })
+        .collect();
+    if keys.just_pressed(MRCA_KEY) {
+        requests.push(None);
+    }
+
+    for request in requests {
+        let pair = request.or_else(|| match inspector.selection.as_slice() {
+            [a, b] => match (lineages.get(*a), lineages.get(*b)) {
+                (Ok(a), Ok(b)) => Some((a.id, b.id)),
+                _ => None,
+            },
+            _ => None,
+        });
+        match pair {
+            Some((a, b)) => report_common_ancestor(&log, a, b),
+            None => warn!("select two living corgis to find their most recent common ancestor"),
+        }
+    }
+}
+
+fn report_common_ancestor(log: &LineageLog, a: LineageId, b: LineageId) {
+    let ancestor = match log.most_recent_common_ancestor(a, b) {
+        Some(ancestor) => ancestor,
+        None => {
+            info!("{} and {} have no common ancestor", a, b);
+            return;
+        }
+    };
+    let entry = log.get(ancestor.id);
+    let genome = entry
+        .and_then(|e| e.genome.as_ref())
+        .map_or_else(|| "unknown".to_string(), |g| g.to_string());
+    info!(
+        "most recent common ancestor of {} and {} is {} (generation {}), {} and {} generations back, genome: {}",
+        a,
+        b,
+        ancestor.id,
+        entry.map_or(0, |e| e.generation),
+        ancestor.distance_a,
+        ancestor.distance_b,
+        genome
+    );
+}
+
+fn inspect_descendants(
+    log: Res<LineageLog>,
+    mut reader: Local<EventReader<ConsoleCommand>>,
+    console: Res<Events<ConsoleCommand>>,
+    lineages: Query<&Lineage>,
+) {
+    for command in reader.iter(&console) {
+        let id = match command {
+            ConsoleCommand::Descendants(id) => *id,
+            _ => continue,
+        };
+        let living: HashSet<LineageId> = lineages.iter().map(|lineage| lineage.id).collect();
+        let descendants = log.living_descendants(id, &living);
+        if descendants.is_empty() {
+            info!("{} has no living descendants", id);
+            continue;
+        }
+        let list: Vec<String> = descendants
+            .iter()
+            .map(|(descendant, distance)| format!("{} ({} generations)", descendant, distance))
+            .collect();
+        info!(
+            "{} living descendants of {}: {}",
+            descendants.len(),
+            id,
+            list.join(", ")
+        );
+    }
+}
@@ -0,0 +1,780 @@
+use crate::genes::BrainGene;
+use bevy::prelude::*;
+use rand::{distributions::Uniform, prelude::Distribution, Rng};
+use rand_distr::{Cauchy, Normal};
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, io, path::Path};
+
+/// Sizes of the hidden layers of a freshly created random network.
+const HIDDEN_LAYER_SIZES: [usize; 1] = [16];
+
+/// Hidden layers don't grow beyond this by duplication.
+const MAX_HIDDEN_LAYER_SIZE: usize = 64;
+/// Maximum number of neurons duplicated at once.
+const DUPLICATION_MAX_BLOCK: usize = 4;
+/// Networks don't get deeper than this by layer insertion.
+const MAX_HIDDEN_LAYERS: usize = 4;
+/// Inserted layers scale their input down to the almost linear range of `tanh`
+/// and the following layer scales it back up, so the inserted layer is nearly the identity.
+const INSERTED_LAYER_SCALE: f32 = 0.1;
+
+/// Probability of every single weight to be mutated.
+pub const MUTATION_RATE: f32 = 0.1;
+/// Standard deviation of the noise added to a mutated weight.
+pub const MUTATION_SIGMA: f32 = 0.2;
+
+/// The distribution of the noise of a weight mutation, all scaled by the mutation sigma.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum NoiseDistribution {
+    Gaussian,
+    /// Heavy tails, occasional big jumps help escaping local optima.
+    Cauchy,
+    /// With the same standard deviation as the gaussian.
+    Uniform,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MutationMode {
+    /// The noise is added to the weight.
+    Additive,
+    /// The weight is replaced by the noise.
+    Replacement,
+}
+
+/// How the weights of a network mutate, chosen per run.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MutationNoise {
+    pub distribution: NoiseDistribution,
+    pub mode: MutationMode,
+}
+
+impl Default for MutationNoise {
+    fn default() -> Self {
+        Self {
+            distribution: NoiseDistribution::Gaussian,
+            mode: MutationMode::Additive,
+        }
+    }
+}
+
+/// A `NoiseDistribution` with a fixed scale.
+enum Noise {
+    Gaussian(Normal<f32>),
+    Cauchy(Cauchy<f32>),
+    Uniform(Uniform<f32>),
+}
+
+impl Noise {
+    fn new(distribution: NoiseDistribution, sigma: f32) -> Self {
+        match distribution {
+            NoiseDistribution::Gaussian => {
+                Self::Gaussian(Normal::new(0.0, sigma).expect("invalid mutation sigma"))
+            }
+            NoiseDistribution::Cauchy => {
+                Self::Cauchy(Cauchy::new(0.0, sigma).expect("invalid mutation sigma"))
+            }
+            NoiseDistribution::Uniform => {
+                let half_width = sigma * 3.0f32.sqrt();
+                Self::Uniform(Uniform::new_inclusive(-half_width, half_width))
+            }
+        }
+    }
+}
+
+impl Distribution<f32> for Noise {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> f32 {
+        match self {
+            Self::Gaussian(distr) => distr.sample(rng),
+            Self::Cauchy(distr) => distr.sample(rng),
+            Self::Uniform(distr) => distr.sample(rng),
+        }
+    }
+}
+
+/// The shape of the IO of a brain, determined by the dry-run.
+/// `outputs` holds the length of every decision component in consumption order,
+/// `smoothed` whether the component gets low-pass filtered.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BrainLayout {
+    pub inputs: usize,
+    pub outputs: Vec<usize>,
+    pub smoothed: Vec<bool>,
+}
+
+impl BrainLayout {
+    pub fn output_len(&self) -> usize {
+        self.outputs.iter().sum()
+    }
+}
+
+/// The brain of a corgi.
+/// The network is only created (or checked, if it was imported)
+/// once the dry-run has determined the layout.
+pub struct Brain {
+    gene: BrainGene,
+    layout: Option<BrainLayout>,
+    /// The output of the last run of the network.
+    cached: Option<Vec<f32>>,
+    /// Staggers the runs of the brains if they don't think every tick.
+    phase: usize,
+    /// The last output after smoothing.
+    smoothed: Option<Vec<f32>>,
+    /// Number of non-finite outputs which have been zeroed so far.
+    non_finite: usize,
+}
+
+impl Default for Brain {
+    fn default() -> Self {
+        Self {
+            gene: BrainGene::default(),
+            layout: None,
+            cached: None,
+            phase: 0,
+            smoothed: None,
+            non_finite: 0,
+        }
+    }
+}
+
+impl Brain {
+    /// The think phase is random, so the brains don't all think at the same tick.
+    pub fn from_gene<R: Rng>(gene: BrainGene, rng: &mut R) -> Self {
+        Self {
+            gene,
+            phase: rng.gen(),
+            ..Default::default()
+        }
+    }
+
+    pub fn gene(&self) -> &BrainGene {
+        &self.gene
+    }
+
+    pub fn network(&self) -> Option<&NeuralNetwork> {
+        self.gene.network.as_ref()
+    }
+
+    pub fn layout(&self) -> Option<&BrainLayout> {
+        self.layout.as_ref()
+    }
+
+    /// The decisions of the last thought.
+    pub fn output(&self) -> Option<&[f32]> {
+        self.smoothed.as_deref()
+    }
+
+    pub fn non_finite(&self) -> usize {
+        self.non_finite
+    }
+
+    pub fn is_dry(&self) -> bool {
+        self.layout.is_none()
+    }
+
+    /// Finishes the dry-run.
+    /// A network which doesn't fit the layout gets replaced by a random one.
+    pub fn set_layout<R: Rng>(&mut self, layout: BrainLayout, rng: &mut R) {
+        let fits = self.network().map_or(false, |network| {
+            network.inputs() == layout.inputs && network.outputs() == layout.output_len()
+        });
+        if !fits {
+            if let Some(network) = self.network() {
+                warn!(
+                    "brain shape {:?} doesn't match IO layout {:?}, using a random network",
+                    network.shape(),
+                    layout
+                );
+            }
+            let mut shape = vec![layout.inputs];
+            shape.extend_from_slice(&HIDDEN_LAYER_SIZES);
+            shape.push(layout.output_len());
+            self.gene.network = Some(NeuralNetwork::new_random(&shape, rng));
+            self.cached = None;
+            self.smoothed = None;
+        }
+        self.layout = Some(layout);
+    }
+
+    /// Runs the network only every `interval` ticks and
+    /// reuses the cached output of the last run in between.
+    /// The smoothed decision components are low-pass filtered every tick,
+    /// so they keep converging towards the cached output.
+    /// Returns `None` as long as the dry-run isn't done.
+    pub fn think(&mut self, input: &[f32], tick: usize, interval: usize) -> Option<Vec<f32>> {
+        let layout = self.layout.as_ref()?;
+        let network = self.gene.network.as_ref()?;
+        let due = tick.wrapping_add(self.phase) % interval.max(1) == 0;
+        if due || self.cached.is_none() {
+            let (output, non_finite) = network.feed_finite(input);
+            self.non_finite += non_finite;
+            self.cached = Some(output);
+        }
+        let raw = self.cached.as_ref()?;
+
+        let output = match &self.smoothed {
+            Some(previous) => {
+                let mut output = raw.clone();
+                let mut offset = 0;
+                for (len, smoothed) in layout.outputs.iter().zip(layout.smoothed.iter()) {
+                    let range = offset..offset + len;
+                    if *smoothed {
+                        for (value, previous) in
+                            output[range.clone()].iter_mut().zip(&previous[range])
+                        {
+                            let smoothing = self.gene.smoothing;
+                            *value = smoothing * previous + (1.0 - smoothing) * *value;
+                        }
+                    }
+                    offset += len;
+                }
+                output
+            }
+            None => raw.clone(),
+        };
+        self.smoothed = Some(output.clone());
+        Some(output)
+    }
+}
+
+/// A short description of a network for logs and the inspector.
+#[derive(Clone, Debug)]
+pub struct NetworkSummary {
+    pub shape: Vec<usize>,
+    pub parameters: usize,
+    pub mean_abs_weight: f32,
+}
+
+impl fmt::Display for NetworkSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "shape {:?}, {} parameters, mean |w| {:.3}",
+            self.shape, self.parameters, self.mean_abs_weight
+        )
+    }
+}
+
+/// The differences of the weights and biases of two layers of the same shape.
+#[derive(Clone, Debug)]
+pub struct LayerDiff {
+    /// Number of parameters which differ.
+    pub changed: usize,
+    pub parameters: usize,
+    pub mean_abs_delta: f32,
+    pub max_abs_delta: f32,
+}
+
+impl fmt::Display for LayerDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} parameters changed, mean |Δ| {:.4}, max |Δ| {:.4}",
+            self.changed, self.parameters, self.mean_abs_delta, self.max_abs_delta
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum NetworkDiff {
+    /// The networks have different shapes, so the weights can't be compared.
+    Structural { from: Vec<usize>, to: Vec<usize> },
+    /// One entry per layer.
+    Weights(Vec<LayerDiff>),
+}
+
+/// A fully connected feed forward network with `tanh` activations.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NeuralNetwork {
+    layers: Vec<Layer>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Layer {
+    inputs: usize,
+    outputs: usize,
+    // row major, one row per output
+    weights: Vec<f32>,
+    biases: Vec<f32>,
+}
+
+impl Layer {
+    fn new_random<R: Rng>(inputs: usize, outputs: usize, rng: &mut R) -> Self {
+        let distr = Uniform::new_inclusive(-1.0, 1.0);
+        Self {
+            inputs,
+            outputs,
+            weights: distr
+                .sample_iter(&mut *rng)
+                .take(inputs * outputs)
+                .collect(),
+            biases: distr.sample_iter(&mut *rng).take(outputs).collect(),
+        }
+    }
+
+    /// Takes every parameter from either parent with the same probability.
+    fn crossover<R: Rng>(&self, other: &Self, rng: &mut R) -> Self {
+        let mut pick = |a: &[f32], b: &[f32]| -> Vec<f32> {
+            a.iter()
+                .zip(b.iter())
+                .map(|(a, b)| if rng.gen() { *a } else { *b })
+                .collect()
+        };
+        Self {
+            inputs: self.inputs,
+            outputs: self.outputs,
+            weights: pick(&self.weights, &other.weights),
+            biases: pick(&self.biases, &other.biases),
+        }
+    }
+
+    /// Appends copies of the output neurons in `block`.
+    fn duplicate_outputs(&mut self, block: std::ops::Range<usize>) {
+        let rows: Vec<f32> =
+            self.weights[block.start * self.inputs..block.end * self.inputs].to_vec();
+        let biases: Vec<f32> = self.biases[block.clone()].to_vec();
+        self.weights.extend(rows);
+        self.biases.extend(biases);
+        self.outputs += block.len();
+    }
+
+    /// Appends inputs for the copies of the inputs in `block`.
+    /// The weights of the originals are split between them, so the output stays the same.
+    fn split_inputs(&mut self, block: std::ops::Range<usize>) {
+        let inputs = self.inputs + block.len();
+        let mut weights = Vec::with_capacity(inputs * self.outputs);
+        for row in self.weights.chunks_mut(self.inputs) {
+            for weight in &mut row[block.clone()] {
+                *weight /= 2.0;
+            }
+            weights.extend_from_slice(row);
+            weights.extend_from_slice(&row[block.clone()]);
+        }
+        self.weights = weights;
+        self.inputs = inputs;
+    }
+
+    /// `scale` times the identity, without biases.
+    fn new_identity(size: usize, scale: f32) -> Self {
+        let mut weights = vec![0.0; size * size];
+        for diagonal in weights.iter_mut().step_by(size + 1) {
+            *diagonal = scale;
+        }
+        Self {
+            inputs: size,
+            outputs: size,
+            weights,
+            biases: vec![0.0; size],
+        }
+    }
+
+    /// One layer computing `next(self(x))` with the activation of `self` treated as linear.
+    fn fold(&self, next: &Self) -> Self {
+        let mut weights = vec![0.0; self.inputs * next.outputs];
+        let mut biases = next.biases.clone();
+        let rows = weights.chunks_mut(self.inputs).zip(biases.iter_mut());
+        for ((folded, bias), next_row) in rows.zip(next.weights.chunks(next.inputs)) {
+            let inner = self.weights.chunks(self.inputs).zip(self.biases.iter());
+            for (&w, (inner_row, inner_bias)) in next_row.iter().zip(inner) {
+                *bias += w * inner_bias;
+                for (folded, &v) in folded.iter_mut().zip(inner_row) {
+                    *folded += w * v;
+                }
+            }
+        }
+        Self {
+            inputs: self.inputs,
+            outputs: next.outputs,
+            weights,
+            biases,
+        }
+    }
+
+    fn mutate<R: Rng>(&mut self, rng: &mut R, rate: f32, noise: &Noise, mode: MutationMode) {
+        for value in self.weights.iter_mut().chain(self.biases.iter_mut()) {
+            if rng.gen::<f32>() < rate {
+                match mode {
+                    MutationMode::Additive => *value += noise.sample(rng),
+                    MutationMode::Replacement => *value = noise.sample(rng),
+                }
+            }
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        self.weights.len() == self.inputs * self.outputs && self.biases.len() == self.outputs
+    }
+
+    fn feed(&self, input: &[f32]) -> Vec<f32> {
+        self.weights
+            .chunks(self.inputs)
+            .zip(self.biases.iter())
+            .map(|(row, bias)| {
+                let sum: f32 = row.iter().zip(input).map(|(w, i)| w * i).sum();
+                (sum + bias).tanh()
+            })
+            .collect()
+    }
+}
+
+impl NeuralNetwork {
+    /// `shape` contains the size of the input layer followed by the sizes
+    /// of all the following layers.
+    pub fn new_random<R: Rng>(shape: &[usize], rng: &mut R) -> Self {
+        assert!(
+            shape.len() >= 2,
+            "a network needs at least an input and an output layer"
+        );
+        let layers = shape
+            .windows(2)
+            .map(|w| Layer::new_random(w[0], w[1], &mut *rng))
+            .collect();
+        Self { layers }
+    }
+
+    pub fn inputs(&self) -> usize {
+        self.layers.first().map_or(0, |l| l.inputs)
+    }
+
+    pub fn outputs(&self) -> usize {
+        self.layers.last().map_or(0, |l| l.outputs)
+    }
+
+    pub fn shape(&self) -> Vec<usize> {
+        let mut shape = vec![self.inputs()];
+        shape.extend(self.layers.iter().map(|l| l.outputs));
+        shape
+    }
+
+    pub fn summary(&self) -> NetworkSummary {
+        let weights = self.layers.iter().flat_map(|l| l.weights.iter());
+        let weight_count = self.layers.iter().map(|l| l.weights.len()).sum::<usize>();
+        let bias_count = self.layers.iter().map(|l| l.biases.len()).sum::<usize>();
+        NetworkSummary {
+            shape: self.shape(),
+            parameters: weight_count + bias_count,
+            mean_abs_weight: weights.map(|w| w.abs()).sum::<f32>() / weight_count.max(1) as f32,
+        }
+    }
+
+    /// Uniform crossover of the weights and biases.
+    /// Networks of different shapes can't be recombined, then one of them is copied.
+    pub fn crossover<R: Rng>(&self, other: &Self, rng: &mut R) -> Self {
+        if self.shape() != other.shape() {
+            return if rng.gen() {
+                self.clone()
+            } else {
+                other.clone()
+            };
+        }
+        let layers = self
+            .layers
+            .iter()
+            .zip(other.layers.iter())
+            .map(|(a, b)| a.crossover(b, rng))
+            .collect();
+        Self { layers }
+    }
+
+    /// The mean of the weights and biases, `None` if the shapes differ.
+    pub fn average(&self, other: &Self) -> Option<Self> {
+        if self.shape() != other.shape() {
+            return None;
+        }
+        let mut average = self.clone();
+        for (layer, other) in average.layers.iter_mut().zip(other.layers.iter()) {
+            let values = layer.weights.iter_mut().chain(layer.biases.iter_mut());
+            for (value, other) in values.zip(other.weights.iter().chain(other.biases.iter())) {
+                *value = (*value + other) / 2.0;
+            }
+        }
+        Some(average)
+    }
+
+    /// Duplicates a block of neurons of a random hidden layer.
+    /// The copies get half of the outgoing weights of the originals,
+    /// so the network computes the same function until point mutations let them diverge.
+    /// Returns whether there was a hidden layer with room for the copies.
+    pub fn duplicate_neurons<R: Rng>(&mut self, rng: &mut R) -> bool {
+        if self.layers.len() < 2 {
+            return false;
+        }
+        let layer = rng.gen_range(0..self.layers.len() - 1);
+        let size = self.layers[layer].outputs;
+        let len = rng
+            .gen_range(1..=DUPLICATION_MAX_BLOCK)
+            .min(size)
+            .min(MAX_HIDDEN_LAYER_SIZE.saturating_sub(size));
+        if len == 0 {
+            return false;
+        }
+        let start = rng.gen_range(0..=size - len);
+        self.layers[layer].duplicate_outputs(start..start + len);
+        self.layers[layer + 1].split_inputs(start..start + len);
+        true
+    }
+
+    /// Inserts a nearly identical hidden layer at a random depth, so the behavior barely changes.
+    /// Returns whether the network wasn't too deep already.
+    pub fn insert_layer<R: Rng>(&mut self, rng: &mut R) -> bool {
+        if self.layers.len() > MAX_HIDDEN_LAYERS {
+            return false;
+        }
+        let index = rng.gen_range(0..self.layers.len());
+        let size = self.layers[index].inputs;
+        for weight in &mut self.layers[index].weights {
+            *weight /= INSERTED_LAYER_SCALE;
+        }
+        self.layers
+            .insert(index, Layer::new_identity(size, INSERTED_LAYER_SCALE));
+        true
+    }
+
+    /// Removes a random hidden layer by folding it into the next one.
+    /// Returns whether there was a hidden layer.
+    pub fn remove_layer<R: Rng>(&mut self, rng: &mut R) -> bool {
+        if self.layers.len() < 2 {
+            return false;
+        }
+        let index = rng.gen_range(0..self.layers.len() - 1);
+        let next = self.layers.remove(index + 1);
+        self.layers[index] = self.layers[index].fold(&next);
+        true
+    }
+
+    /// Mutates a fraction `rate` of all weights and biases with noise of scale `sigma`.
+    pub fn mutate<R: Rng>(&mut self, rng: &mut R, rate: f32, sigma: f32, kind: &MutationNoise) {
+        let noise = Noise::new(kind.distribution, sigma);
+        for layer in &mut self.layers {
+            layer.mutate(rng, rate, &noise, kind.mode);
+        }
+    }
+
+    pub fn feed(&self, input: &[f32]) -> Vec<f32> {
+        assert_eq!(input.len(), self.inputs(), "invalid input shape");
+        self.layers
+            .iter()
+            .fold(input.to_vec(), |values, layer| layer.feed(&values))
+    }
+
+    /// Like `feed`, but zeroes non-finite outputs and returns their number,
+    /// so a broken genome can't poison the physics.
+    pub fn feed_finite(&self, input: &[f32]) -> (Vec<f32>, usize) {
+        let mut output = self.feed(input);
+        let mut non_finite = 0;
+        for value in output.iter_mut().filter(|v| !v.is_finite()) {
+            *value = 0.0;
+            non_finite += 1;
+        }
+        (output, non_finite)
+    }
+
+    pub fn diff(&self, other: &Self) -> NetworkDiff {
+        if self.shape() != other.shape() {
+            return NetworkDiff::Structural {
+                from: self.shape(),
+                to: other.shape(),
+            };
+        }
+        let layers = self
+            .layers
+            .iter()
+            .zip(other.layers.iter())
+            .map(|(a, b)| {
+                let deltas: Vec<f32> = a
+                    .weights
+                    .iter()
+                    .chain(a.biases.iter())
+                    .zip(b.weights.iter().chain(b.biases.iter()))
+                    .map(|(a, b)| (b - a).abs())
+                    .collect();
+                LayerDiff {
+                    changed: deltas.iter().filter(|d| **d > 0.0).count(),
+                    parameters: deltas.len(),
+                    mean_abs_delta: deltas.iter().sum::<f32>() / deltas.len().max(1) as f32,
+                    max_abs_delta: deltas.iter().copied().fold(0.0, f32::max),
+                }
+            })
+            .collect();
+        NetworkDiff::Weights(layers)
+    }
+
+    /// All weights and biases, layer by layer.
+    pub fn parameters(&self) -> impl Iterator<Item = f32> + '_ {
+        self.layers
+            .iter()
+            .flat_map(|layer| layer.weights.iter().chain(layer.biases.iter()))
+            .copied()
+    }
+
+    /// Mean absolute difference of the weights and biases, `None` if the shapes differ.
+    pub fn weight_distance(&self, other: &Self) -> Option<f32> {
+        if self.shape() != other.shape() {
+            return None;
+        }
+        let (sum, count) = self
+            .layers
+            .iter()
+            .zip(other.layers.iter())
+            .flat_map(|(a, b)| {
+                a.weights
+                    .iter()
+                    .chain(a.biases.iter())
+                    .zip(b.weights.iter().chain(b.biases.iter()))
+            })
+            .fold((0.0, 0), |(sum, count), (a, b)| {
+                (sum + (a - b).abs(), count + 1)
+            });
+        Some(sum / count.max(1) as f32)
+    }
+
+    /// Number of neurons one network has more than the other per layer,
+    /// a missing layer counts with all of its neurons.
+    pub fn structural_distance(&self, other: &Self) -> usize {
+        let (a, b) = (self.shape(), other.shape());
+        (0..a.len().max(b.len()))
+            .map(|i| {
+                let (x, y) = (
+                    a.get(i).copied().unwrap_or(0),
+                    b.get(i).copied().unwrap_or(0),
+                );
+                if x > y {
+                    x - y
+                } else {
+                    y - x
+                }
+            })
+            .sum()
+    }
+
+    /// Whether all weights and biases are finite.
+    pub fn is_finite(&self) -> bool {
+        self.layers
+            .iter()
+            .flat_map(|l| l.weights.iter().chain(l.biases.iter()))
+            .all(|v| v.is_finite())
+    }
+
+    /// Whether the layer shapes are consistent.
+    pub fn is_valid(&self) -> bool {
+        !self.layers.is_empty()
+            && self.layers.iter().all(Layer::is_valid)
+            && self.layers.windows(2).all(|w| w[0].outputs == w[1].inputs)
+    }
+
+    /// Loads the weights from a RON file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, WeightsError> {
+        let file = fs::File::open(path)?;
+        let network: Self = ron::de::from_reader(file)?;
+        if !network.is_valid() {
+            return Err(WeightsError::InvalidShape);
+        }
+        Ok(network)
+    }
+
+    /// Saves the weights as a RON file, which can be imported again with `load`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), WeightsError> {
+        let string = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        fs::write(path, string)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum WeightsError {
+    Io(io::Error),
+    Ron(ron::Error),
+    InvalidShape,
+}
+
+impl fmt::Display for WeightsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "io error: {}", e),
+            Self::Ron(e) => write!(f, "invalid weight file: {}", e),
+            Self::InvalidShape => write!(f, "inconsistent layer shapes"),
+        }
+    }
+}
+
+impl std::error::Error for WeightsError {}
+
+impl From<io::Error> for WeightsError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<ron::Error> for WeightsError {
+    fn from(e: ron::Error) -> Self {
+        Self::Ron(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    /// Asserts that both networks compute nearly the same outputs for random inputs.
+    fn assert_same_function(
+        a: &NeuralNetwork,
+        b: &NeuralNetwork,
+        tolerance: f32,
+        rng: &mut StdRng,
+    ) {
+        let distr = Uniform::new_inclusive(-1.0, 1.0);
+        for _ in 0..100 {
+            let input: Vec<f32> = distr.sample_iter(&mut *rng).take(a.inputs()).collect();
+            for (x, y) in a.feed(&input).iter().zip(b.feed(&input)) {
+                assert!((x - y).abs() <= tolerance, "{} != {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn duplicated_neurons_keep_the_outputs() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let network = NeuralNetwork::new_random(&[6, 5, 3], &mut rng);
+        let mut wider = network.clone();
+        assert!(wider.duplicate_neurons(&mut rng));
+        assert!(wider.shape()[1] > 5);
+        assert_same_function(&network, &wider, 1e-5, &mut rng);
+    }
+
+    #[test]
+    fn inserted_layers_keep_the_outputs() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let network = NeuralNetwork::new_random(&[6, 5, 3], &mut rng);
+        let mut deeper = network.clone();
+        assert!(deeper.insert_layer(&mut rng));
+        assert_eq!(deeper.layers.len(), 3);
+        assert_same_function(&network, &deeper, 0.05, &mut rng);
+    }
+
+    #[test]
+    fn removing_an_inserted_layer_restores_the_outputs() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let network = NeuralNetwork::new_random(&[6, 3], &mut rng);
+        let mut deeper = network.clone();
+        assert!(deeper.insert_layer(&mut rng));
+        assert!(deeper.remove_layer(&mut rng));
+        assert_eq!(deeper.shape(), network.shape());
+        assert_same_function(&network, &deeper, 1e-4, &mut rng);
+    }
+
+    #[test]
+    fn removed_layers_keep_the_outputs_in_the_linear_range() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut network = NeuralNetwork::new_random(&[6, 5, 3], &mut rng);
+        // a hidden layer working in the almost linear range of `tanh`
+        let hidden = &mut network.layers[0];
+        for value in hidden.weights.iter_mut().chain(hidden.biases.iter_mut()) {
+            *value *= 0.01;
+        }
+        let mut shallower = network.clone();
+        assert!(shallower.remove_layer(&mut rng));
+        assert_eq!(shallower.shape(), vec![6, 3]);
+        assert_same_function(&network, &shallower, 0.01, &mut rng);
+    }
+}
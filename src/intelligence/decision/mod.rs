@@ -0,0 +1,564 @@
+pub mod reproduction;
+
+use self::reproduction::Egg;
+use super::{
+    io::{IoBool, IoF32},
+    AttackDecision, BarkDecision, BrainOutputStore, CarryDecision, ColorDecision, DashDecision,
+    EatDecision, GroomDecision, Memory, MemoryDecision, MovementDecision, PheromoneDecision,
+    RestDecision,
+};
+use crate::{
+    color::DisplayColor,
+    config::SimConfig,
+    corgi::{
+        AdultMass, Age, Energy, Exertion, Heading, Health, Load, Mass, RecentDamage, Resting,
+        Stamina, CARRY_CAPACITY, CORGI_ENERGY_CAPACITY,
+    },
+    corpse::{self, Corpse, DeathCause, Died, CORPSE_BITE, CORPSE_EAT_RANGE},
+    debug::EnergyLedger,
+    genes::{BodyGene, MetabolismGene},
+    loader::MyAssets,
+    parasite::{self, Parasite},
+    pheromone::PheromoneField,
+    rng::SimRng,
+    sound::Sounds,
+    spatial::SpatialGrid,
+    universe::{
+        scenario::{Locomotion, Scenario},
+        tile::{TileGrid, TileType},
+        Tick, Universe,
+    },
+};
+use bevy::prelude::*;
+use bevy_rapier2d::{
+    na::{Isometry2, Vector2},
+    physics::RigidBodyHandleComponent,
+    rapier::dynamics::RigidBodySet,
+};
+use rand::Rng;
+use std::{collections::HashSet, f32::consts::TAU};
+
+pub const MAX_MOVEMENT_FORCE: f32 = 20_000.0;
+pub const MAX_DASH_IMPULSE: f32 = 5_000.0;
+/// Ticks until a corgi can dash again.
+pub const DASH_COOLDOWN: usize = 120;
+/// How far the decided color can move the displayed color away from the base color of the body.
+pub const COLOR_RANGE: f32 = 0.5;
+/// Pheromone deposited per tick at the full decision.
+pub const MAX_PHEROMONE_DEPOSIT: f32 = 1.0;
+/// Energy per deposited unit of pheromone.
+pub const PHEROMONE_ENERGY_COST: f32 = 0.02;
+/// Maximum distance between an attacker and its victim.
+pub const ATTACK_RANGE: f32 = 25.0;
+/// Damage per tick of an attacker with the full weapon on a victim of the same mass.
+pub const ATTACK_DAMAGE: f32 = 4.0;
+/// Energy an attack costs per tick.
+pub const ATTACK_ENERGY_COST: f32 = 0.1;
+/// Food energy picked up per tick.
+pub const CARRY_BITE: f32 = 1.0;
+/// Fraction of the movement force lost with a full load.
+pub const CARRY_SLOWDOWN: f32 = 0.3;
+/// Factor of the movement force of juveniles.
+pub const JUVENILE_SPEED_FACTOR: f32 = 0.7;
+/// Factor of the bites of juveniles.
+pub const JUVENILE_BITE_FACTOR: f32 = 0.5;
+/// Energy grooming costs per tick.
+pub const GROOM_ENERGY_COST: f32 = 0.05;
+/// Chance per tick of grooming to remove each parasite.
+pub const GROOM_CHANCE: f32 = 0.02;
+/// Turn per tick in radians at the full decision of the heading locomotion.
+pub const MAX_TURN_RATE: f32 = 0.1;
+/// Quieter barks aren't emitted at all.
+pub const MIN_BARK_LOUDNESS: f32 = 0.1;
+/// Forces above this fraction of the maximum drain stamina, weaker ones regenerate it.
+pub const STAMINA_FORCE_THRESHOLD: f32 = 0.5;
+/// Stamina drained per tick at maximum force.
+pub const STAMINA_DRAIN: f32 = 0.01;
+/// Stamina regenerated per tick at rest.
+pub const STAMINA_REGENERATION: f32 = 0.004;
+
+/// Optionally blends the movement force of the brain with a vector
+/// steering away from nearby corgis, so they don't pile up.
+/// Meant for demos, evolved movement is left untouched by default.
+#[derive(Clone, Copy, Debug)]
+pub struct AvoidanceAssist {
+    pub enabled: bool,
+    /// Fraction of the force replaced by the avoidance, between 0 and 1.
+    pub weight: f32,
+    /// Corgis further away are ignored.
+    pub radius: f32,
+}
+
+impl Default for AvoidanceAssist {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            weight: 0.3,
+            radius: 40.0,
+        }
+    }
+}
+
+impl AvoidanceAssist {
+    /// Points away from the neighbors, weighted by closeness, with a length of at most 1.
+    fn avoidance(&self, grid: &SpatialGrid, entity: Entity, pos: Vec2) -> Vec2 {
+        let away = grid
+            .in_radius(pos, self.radius)
+            .filter(|(other, _)| *other != entity)
+            .map(|(_, other)| {
+                let offset = pos - other;
+                let distance = offset.length().max(f32::EPSILON);
+                offset / distance * (1.0 - distance / self.radius)
+            })
+            .fold(Vec2::zero(), |sum, v| sum + v);
+        if away.length() > 1.0 {
+            away.normalize()
+        } else {
+            away
+        }
+    }
+
+    fn steer(&self, force: Vector2<f32>, avoidance: Vec2) -> Vector2<f32> {
+        let avoidance = Vector2::new(avoidance.x, avoidance.y);
+        force * (1.0 - self.weight) + avoidance * self.weight
+    }
+}
+
+/// The tick from which on the corgi can dash again.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct DashCooldown(pub usize);
+
+/// Applies the movement force and the dash impulse to the rigid body.
+/// The dash bursts in the direction of the movement force
+/// and is only possible with enough energy and no active cooldown.
+/// Exhausted corgis can't apply forces above the stamina threshold.
+/// The strength and pace genes cap the force,
+/// photosynthesis and the movement cost of the biome weaken it,
+/// good swimmers are weaker on land, growing corgis and juveniles are weaker than adults
+/// and carrying food slows down.
+/// The corgis accelerate with the force divided by their current mass.
+/// With the heading locomotion the corgi turns and thrusts forward, facing its heading.
+/// Resting corgis neither move, turn nor dash.
+#[allow(clippy::too_many_arguments)]
+pub fn decide_movement(
+    tick: Res<Tick>,
+    config: Res<SimConfig>,
+    scenario: Res<Scenario>,
+    assist: Res<AvoidanceAssist>,
+    grid: Res<SpatialGrid>,
+    tiles: Res<TileGrid>,
+    ledger: Res<EnergyLedger>,
+    mut bodies: ResMut<RigidBodySet>,
+    mut query: Query<(
+        Entity,
+        &Transform,
+        &RigidBodyHandleComponent,
+        &mut Energy,
+        (&mut Stamina, &mut Exertion, &mut Heading, &mut Resting),
+        &mut DashCooldown,
+        (&BodyGene, &MetabolismGene, &Age, &Mass, &AdultMass, &Load),
+        &mut MovementDecision,
+        &mut DashDecision,
+        &mut RestDecision,
+    )>,
+) {
+    for (
+        entity,
+        transform,
+        handle,
+        mut energy,
+        (mut stamina, mut exertion, mut heading, mut resting),
+        mut cooldown,
+        (body_gene, metabolism, age, mass, adult_mass, load),
+        mut movement,
+        mut dash,
+        mut rest,
+    ) in query.iter_mut()
+    {
+        let IoBool(rest_will) = rest.0.take_io();
+        resting.0 = rest_will;
+        let first = movement.0.take();
+        let second = movement.0.take();
+        let mut direction = match scenario.locomotion {
+            _ if resting.0 => Vector2::zeros(),
+            Locomotion::Force => Vector2::new(first, second),
+            Locomotion::Heading => {
+                let turn = first.max(-1.0).min(1.0);
+                let thrust = second.max(0.0);
+                heading.0 = (heading.0 + turn * MAX_TURN_RATE).rem_euclid(TAU);
+                Vector2::new(heading.0.cos(), heading.0.sin()) * thrust
+            }
+        };
+        if assist.enabled && !resting.0 {
+            let avoidance = assist.avoidance(&grid, entity, transform.translation.truncate());
+            direction = assist.steer(direction, avoidance);
+        }
+        if direction.norm() > 1.0 {
+            direction /= direction.norm();
+        }
+        let IoBool(dash_will) = dash.0.take_io();
+        let IoF32(dash_strength) = dash.0.take_io();
+
+        let body = match bodies.get_mut(handle.handle()) {
+            Some(body) => body,
+            None => continue,
+        };
+        if scenario.locomotion == Locomotion::Heading {
+            let translation = body.position().translation.vector;
+            body.set_position(Isometry2::new(translation, heading.0), false);
+            body.set_angvel(0.0, false);
+        }
+
+        let strength = direction.norm();
+        if strength > STAMINA_FORCE_THRESHOLD {
+            let exertion = (strength - STAMINA_FORCE_THRESHOLD) / (1.0 - STAMINA_FORCE_THRESHOLD);
+            stamina.0 = (stamina.0 - exertion * STAMINA_DRAIN).max(0.0);
+            if stamina.0 == 0.0 {
+                direction *= STAMINA_FORCE_THRESHOLD / strength;
+            }
+        } else {
+            let rest = 1.0 - strength / STAMINA_FORCE_THRESHOLD;
+            stamina.0 = (stamina.0 + rest * STAMINA_REGENERATION).min(1.0);
+        }
+        let tile = tiles.at(transform.translation.truncate());
+        let movement_cost = tile.map_or(1.0, |tile| tile.biome.movement_cost());
+        let terrain_factor = match tile.map(|tile| tile.tile_type) {
+            Some(TileType::Water) => 1.0,
+            _ => body_gene.land_speed_factor(),
+        };
+        let maturity = adult_mass.maturity(*mass);
+        let juvenile = if metabolism.is_adult(age.0) {
+            1.0
+        } else {
+            JUVENILE_SPEED_FACTOR
+        };
+        let max_force = MAX_MOVEMENT_FORCE
+            * body_gene.strength_factor()
+            * body_gene.pace_factor()
+            * metabolism.speed_factor()
+            * terrain_factor
+            * maturity
+            * juvenile
+            * (1.0 - CARRY_SLOWDOWN * load.0 / CARRY_CAPACITY)
+            / movement_cost;
+        let force = direction * max_force;
+        exertion.0 = force.dot(body.linvel()).abs();
+        body.apply_force(force, true);
+
+        let can_dash = tick.0 >= cooldown.0 && energy.get() >= config.dash_energy_cost;
+        if dash_will && can_dash && direction.norm() > 0.0 {
+            let impulse = direction.normalize() * dash_strength * maturity * MAX_DASH_IMPULSE;
+            body.apply_impulse(impulse, true);
+            ledger.sink(energy.sub(config.dash_energy_cost));
+            cooldown.0 = tick.0 + DASH_COOLDOWN;
+        }
+    }
+}
+
+/// Emits a sound with the loudness of the bark at the position of the corgi.
+pub fn decide_bark(
+    mut sounds: ResMut<Sounds>,
+    mut query: Query<(Entity, &Transform, &mut BarkDecision)>,
+) {
+    for (entity, transform, mut bark) in query.iter_mut() {
+        let IoF32(loudness) = bark.0.take_io();
+        if loudness >= MIN_BARK_LOUDNESS {
+            sounds.emit(entity, transform.translation.truncate(), loudness);
+        }
+    }
+}
+
+/// The displayed color is the base color of the body, shifted by the decision.
+/// Every corgi gets its own material the first time.
+pub fn decide_color(
+    assets: Res<MyAssets>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut query: Query<(
+        &BodyGene,
+        &mut DisplayColor,
+        &mut Handle<StandardMaterial>,
+        &mut ColorDecision,
+    )>,
+) {
+    for (body, mut display, mut material, mut decision) in query.iter_mut() {
+        let IoF32(r) = decision.0.take_io();
+        let IoF32(g) = decision.0.take_io();
+        let IoF32(b) = decision.0.take_io();
+        let shift = |base: f32, decided: f32| base + (decided - base) * COLOR_RANGE;
+        display.0 = Color::rgb_linear(
+            shift(body.color[0], r),
+            shift(body.color[1], g),
+            shift(body.color[2], b),
+        );
+
+        if *material == assets.corgi_material {
+            *material = materials.add(StandardMaterial::from(display.0));
+        } else if let Some(material) = materials.get_mut(&*material) {
+            material.albedo = display.0;
+        }
+    }
+}
+
+/// Deposits pheromone on the tile of the corgi, which spreads and decays in the `PheromoneField`.
+pub fn decide_pheromone(
+    mut pheromones: ResMut<PheromoneField>,
+    ledger: Res<EnergyLedger>,
+    mut query: Query<(&Transform, &mut Energy, &mut PheromoneDecision)>,
+) {
+    for (transform, mut energy, mut decision) in query.iter_mut() {
+        let IoF32(amount) = decision.0.take_io();
+        let amount = amount.max(0.0).min(1.0) * MAX_PHEROMONE_DEPOSIT;
+        if amount > 0.0 {
+            // a corgi running out of energy only deposits what it could pay for
+            let paid = energy.sub(amount * PHEROMONE_ENERGY_COST);
+            ledger.sink(paid);
+            pheromones.deposit(
+                transform.translation.truncate(),
+                paid / PHEROMONE_ENERGY_COST,
+            );
+        }
+    }
+}
+
+/// Corgis which want to eat graze the tile they stand on,
+/// or eat from a corpse or else an egg in range if the tile holds no food.
+/// The bite grows with the appetite, is smaller for juveniles
+/// and is limited by how much they can store.
+/// Only the fraction of the food their diet digests turns into energy, the rest is lost.
+pub fn decide_eat(
+    config: Res<SimConfig>,
+    universe: Res<Universe>,
+    ledger: Res<EnergyLedger>,
+    mut tiles: ResMut<TileGrid>,
+    mut corpses: Query<(&Transform, &mut Corpse)>,
+    mut eggs: Query<(&Transform, &mut Egg)>,
+    mut query: Query<(
+        &Transform,
+        &MetabolismGene,
+        &Age,
+        &mut Energy,
+        &mut EatDecision,
+    )>,
+) {
+    for (transform, metabolism, age, mut energy, mut decision) in query.iter_mut() {
+        let IoBool(will) = decision.0.take_io();
+        let room = CORGI_ENERGY_CAPACITY - energy.get();
+        if !will || room <= 0.0 {
+            continue;
+        }
+        let juvenile = if metabolism.is_adult(age.0) {
+            1.0
+        } else {
+            JUVENILE_BITE_FACTOR
+        };
+        let appetite = metabolism.appetite_factor() * juvenile;
+        let pos = transform.translation.truncate();
+        let tile = universe.tile_at(pos);
+        let has_food = tile
+            .and_then(|tile| tiles.get(tile))
+            .map_or(false, |tile| tile.energy > 0.0);
+        if has_food {
+            if let Some(tile) = tile.and_then(|tile| tiles.get_mut(tile)) {
+                let efficiency = metabolism.plant_efficiency();
+                let bite = config.food_bite * appetite;
+                let grazed = tile.graze(bite.min(room / efficiency));
+                ledger.sink(grazed - energy.add(grazed * efficiency));
+            }
+            continue;
+        }
+        let efficiency = metabolism.meat_efficiency();
+        let bite = (CORPSE_BITE * appetite).min(room / efficiency);
+        let in_range = |other: &Transform| {
+            universe.offset(pos, other.translation.truncate()).length() <= CORPSE_EAT_RANGE
+        };
+        let corpse = corpses
+            .iter_mut()
+            .find(|(transform, corpse)| corpse.energy > 0.0 && in_range(transform));
+        if let Some((_, mut corpse)) = corpse {
+            let added = energy.add(bite.min(corpse.energy) * efficiency);
+            corpse.energy -= added / efficiency;
+            ledger.sink(added / efficiency - added);
+            continue;
+        }
+        let egg = eggs
+            .iter_mut()
+            .find(|(transform, egg)| egg.energy > 0.0 && in_range(transform));
+        if let Some((_, mut egg)) = egg {
+            let added = energy.add(bite.min(egg.energy) * efficiency);
+            egg.energy -= added / efficiency;
+            ledger.sink(added / efficiency - added);
+        }
+    }
+}
+
+/// Corgis which want to attack hurt the nearest corgi in `ATTACK_RANGE`.
+/// The damage to the health grows with the weapon and the mass relative to the victim.
+/// Killers eat as much of the remains as they can store and digest,
+/// the rest is left as a corpse.
+pub fn decide_attack(
+    commands: &mut Commands,
+    assets: Res<MyAssets>,
+    grid: Res<SpatialGrid>,
+    ledger: Res<EnergyLedger>,
+    mut deaths: ResMut<Events<Died>>,
+    mut query: Query<(
+        Entity,
+        &Transform,
+        (&Mass, &BodyGene, &MetabolismGene, &Load),
+        &mut Energy,
+        (&mut Health, &mut RecentDamage),
+        &mut AttackDecision,
+    )>,
+) {
+    let mut attacks = Vec::new();
+    for (entity, transform, (mass, body, ..), mut energy, _, mut decision) in query.iter_mut() {
+        let IoBool(will) = decision.0.take_io();
+        if !will {
+            continue;
+        }
+        let pos = transform.translation.truncate();
+        let victim = grid
+            .nearest(pos, 1, ATTACK_RANGE, entity)
+            .first()
+            .map(|(victim, _)| *victim);
+        if let Some(victim) = victim {
+            if energy.spend(ATTACK_ENERGY_COST) {
+                ledger.sink(ATTACK_ENERGY_COST);
+                attacks.push((entity, victim, mass.get(), body.weapon_factor()));
+            }
+        }
+    }
+
+    let mut killed = HashSet::new();
+    for (attacker, victim, attacker_mass, weapon) in attacks {
+        if killed.contains(&attacker) || killed.contains(&victim) {
+            continue;
+        }
+        let (pos, remains) = match query.get_mut(victim) {
+            Ok((_, transform, (mass, _, _, load), energy, (mut health, mut damage), _)) => {
+                let relative_mass = 2.0 * attacker_mass / (attacker_mass + mass.get());
+                let hurt = ATTACK_DAMAGE * weapon * relative_mass;
+                damage.hurt(health.hurt(hurt, DeathCause::Predation));
+                if !health.is_dead() {
+                    continue;
+                }
+                (
+                    transform.translation.truncate(),
+                    corpse::remains(&energy, load),
+                )
+            }
+            Err(_) => continue,
+        };
+        let meal = match query.get_mut(attacker) {
+            Ok((_, _, (_, _, metabolism, _), mut energy, _, _)) => {
+                let efficiency = metabolism.meat_efficiency();
+                let added = energy.add(remains * efficiency);
+                ledger.sink(added / efficiency - added);
+                added / efficiency
+            }
+            Err(_) => 0.0,
+        };
+        killed.insert(victim);
+        corpse::kill(
+            commands,
+            &assets,
+            &mut deaths,
+            victim,
+            pos,
+            remains - meal,
+            DeathCause::Predation,
+        );
+    }
+}
+
+/// Corgis which want to carry pick up food from the tile they stand on,
+/// or else from a corpse in range, until they carry `CARRY_CAPACITY`.
+/// Once they don't want to carry anymore, they drop their load into the food of their tile,
+/// beyond the capacity of the tile it is lost.
+pub fn decide_carry(
+    universe: Res<Universe>,
+    ledger: Res<EnergyLedger>,
+    mut tiles: ResMut<TileGrid>,
+    mut corpses: Query<(&Transform, &mut Corpse)>,
+    mut query: Query<(&Transform, &mut Load, &mut CarryDecision)>,
+) {
+    for (transform, mut load, mut decision) in query.iter_mut() {
+        let IoBool(will) = decision.0.take_io();
+        let pos = transform.translation.truncate();
+        let tile = universe.tile_at(pos);
+        if !will {
+            if load.0 > 0.0 {
+                let dropped = tile
+                    .and_then(|tile| tiles.get_mut(tile))
+                    .map_or(0.0, |tile| {
+                        let before = tile.energy;
+                        tile.energy = (tile.energy + load.0).min(tile.capacity);
+                        tile.energy - before
+                    });
+                ledger.sink(load.0 - dropped);
+                load.0 = 0.0;
+            }
+            continue;
+        }
+        let bite = CARRY_BITE.min(CARRY_CAPACITY - load.0);
+        if bite <= 0.0 {
+            continue;
+        }
+        let has_food = tile
+            .and_then(|tile| tiles.get(tile))
+            .map_or(false, |tile| tile.energy > 0.0);
+        if has_food {
+            if let Some(tile) = tile.and_then(|tile| tiles.get_mut(tile)) {
+                load.0 += tile.graze(bite);
+            }
+            continue;
+        }
+        let corpse = corpses.iter_mut().find(|(corpse_transform, corpse)| {
+            let offset = universe.offset(pos, corpse_transform.translation.truncate());
+            corpse.energy > 0.0 && offset.length() <= CORPSE_EAT_RANGE
+        });
+        if let Some((_, mut corpse)) = corpse {
+            let taken = bite.min(corpse.energy);
+            corpse.energy -= taken;
+            load.0 += taken;
+        }
+    }
+}
+
+/// Corgis which want to groom spend energy to pick off each of their parasites
+/// with `GROOM_CHANCE`.
+pub fn decide_groom(
+    commands: &mut Commands,
+    mut rng: ResMut<SimRng>,
+    ledger: Res<EnergyLedger>,
+    mut tiles: ResMut<TileGrid>,
+    parasites: Query<(Entity, &Transform, &Parasite)>,
+    mut query: Query<(Entity, &mut Energy, &mut GroomDecision)>,
+) {
+    let mut groomers = HashSet::new();
+    for (entity, mut energy, mut decision) in query.iter_mut() {
+        let IoBool(will) = decision.0.take_io();
+        if will && energy.spend(GROOM_ENERGY_COST) {
+            ledger.sink(GROOM_ENERGY_COST);
+            groomers.insert(entity);
+        }
+    }
+    if groomers.is_empty() {
+        return;
+    }
+    for (entity, transform, parasite) in parasites.iter() {
+        let groomed = parasite.host.map_or(false, |host| groomers.contains(&host));
+        if groomed && rng.gen::<f32>() < GROOM_CHANCE {
+            let pos = transform.translation.truncate();
+            parasite::release_energy(&mut tiles, &ledger, pos, parasite.energy);
+            commands.despawn(entity);
+        }
+    }
+}
+
+/// Writes the memory cells, they are perceived again in the next tick.
+pub fn decide_memory(config: Res<SimConfig>, mut query: Query<(&mut Memory, &mut MemoryDecision)>) {
+    for (mut memory, mut decision) in query.iter_mut() {
+        memory.0 = decision.0.take_multiple(config.memory_size);
+    }
+}
@@ -0,0 +1,315 @@
+use crate::{
+    config::SimConfig,
+    corgi::{self, Age, CorgiBundle, Energy, Generation, Inheritance, CORGI_ENERGY_BORN},
+    debug::EnergyLedger,
+    genes::{Alleles, Genome, Genotype, MetabolismGene, SenseGene},
+    intelligence::{io::IoBool, Brain, BrainOutputStore, ReproductionDecision},
+    lineage::{Lineage, LineageLog},
+    loader::MyAssets,
+    rng::SimRng,
+    selection::{Candidate, Selection},
+    spatial::SpatialGrid,
+    stats::RegionStats,
+    universe::{
+        scenario::{Genetics, ReproductionMode, Scenario},
+        Daylight, Tick,
+    },
+};
+use bevy::prelude::*;
+use std::collections::HashSet;
+
+/// Side length of the sprite of an egg.
+const EGG_SIZE: f32 = 8.0;
+/// Eggs with less energy are eaten up.
+const EGG_MIN_ENERGY: f32 = 0.5;
+
+/// A conceived child, the first parent lays it as an egg once it is due.
+/// Pregnant corgis can't conceive again.
+pub struct Pregnancy {
+    /// The tick the egg is laid.
+    pub due: usize,
+    pub genome: Genome,
+    /// Only for diploid scenarios.
+    pub alleles: Option<Alleles>,
+    /// Generation and lineage of every parent at the time of the conception.
+    pub parents: Vec<(Generation, Lineage)>,
+}
+
+/// A laid child, it hatches with the remaining energy unless it gets eaten first.
+pub struct Egg {
+    /// The tick of the hatching.
+    pub hatch: usize,
+    pub energy: f32,
+    pub genome: Genome,
+    /// Only for diploid scenarios.
+    pub alleles: Option<Alleles>,
+    /// Generation and lineage of every parent at the time of the conception.
+    pub parents: Vec<(Generation, Lineage)>,
+}
+
+/// Mating, conception, laying eggs and hatching.
+/// The parameters are part of the `SimConfig`.
+pub struct ReproductionPlugin;
+
+impl Plugin for ReproductionPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        // the decide stage is added by the `IntelligencePlugin`
+        app.add_system_to_stage("decide", adopt_networks.system())
+            .add_system_to_stage("decide", decide_reproduction.system())
+            .add_system(lay_eggs.system())
+            .add_system(hatch_eggs.system());
+    }
+
+    fn name(&self) -> &str {
+        "ReproductionPlugin"
+    }
+}
+
+/// The alleles of diploid corgis take over the network of their brain,
+/// so their children inherit it instead of starting with a blank one.
+fn adopt_networks(mut query: Query<(&Brain, &mut Alleles), Changed<Brain>>) {
+    for (brain, mut alleles) in query.iter_mut() {
+        if let Some(network) = brain.network() {
+            alleles.adopt_network(network);
+        }
+    }
+}
+
+/// Adults with enough energy which want to reproduce and aren't pregnant are candidates,
+/// the selection policy decides which of them conceive.
+/// Asexual parents conceive a mutated copy of themselves,
+/// sexual ones need a willing candidate in the mating range as partner.
+/// Diploid parents pass on a recombination of their two alleles.
+/// The parents split the energy of the egg at the conception.
+#[allow(clippy::too_many_arguments)]
+pub fn decide_reproduction(
+    commands: &mut Commands,
+    tick: Res<Tick>,
+    scenario: Res<Scenario>,
+    config: Res<SimConfig>,
+    grid: Res<SpatialGrid>,
+    ledger: Res<EnergyLedger>,
+    mut selection: ResMut<Selection>,
+    mut rng: ResMut<SimRng>,
+    mut query: Query<(
+        Entity,
+        &Transform,
+        &mut Energy,
+        (&Brain, &SenseGene, &Genotype, Option<&Alleles>),
+        (&Generation, &Lineage, Option<&Pregnancy>),
+        (&Age, &MetabolismGene),
+        &mut ReproductionDecision,
+    )>,
+) {
+    let population: Vec<Candidate> = query
+        .iter_mut()
+        .map(
+            |(entity, _, energy, _, (_, _, pregnancy), (age, metabolism), mut reproduction)| {
+                let IoBool(will) = reproduction.0.take_io();
+                Candidate {
+                    entity,
+                    energy: energy.get(),
+                    willing: will
+                        && metabolism.is_adult(age.0)
+                        && pregnancy.is_none()
+                        && energy.get() >= config.reproduction_min_energy,
+                }
+            },
+        )
+        .collect();
+
+    let mut mated = HashSet::new();
+    let rng = &mut *rng;
+    for entity in selection.0.select_with(&population, rng) {
+        // a selection can pick a corgi several times, it only conceives once per tick
+        if mated.contains(&entity) {
+            continue;
+        }
+        let parents = match scenario.reproduction {
+            ReproductionMode::Asexual => vec![entity],
+            ReproductionMode::Sexual => {
+                let pos = match query.get_mut(entity) {
+                    Ok((_, transform, ..)) => transform.translation.truncate(),
+                    Err(_) => continue,
+                };
+                // both have to be willing
+                let partner = grid
+                    .nearest(pos, population.len(), config.mating_range, entity)
+                    .into_iter()
+                    .map(|(other, _)| other)
+                    .find(|other| {
+                        !mated.contains(other)
+                            && population.iter().any(|c| c.entity == *other && c.willing)
+                    });
+                match partner {
+                    Some(partner) => vec![entity, partner],
+                    None => continue,
+                }
+            }
+        };
+
+        let cost = CORGI_ENERGY_BORN / parents.len() as f32;
+        let affordable = parents.iter().all(|parent| {
+            query
+                .get_mut(*parent)
+                .map_or(false, |(_, _, energy, ..)| energy.get() >= cost)
+        });
+        if !affordable {
+            continue;
+        }
+
+        let mut inherited = Vec::new();
+        for parent in &parents {
+            let (
+                _,
+                _,
+                mut energy,
+                (brain, senses, genotype, alleles),
+                (generation, lineage, _),
+                _,
+                _,
+            ) = match query.get_mut(*parent) {
+                Ok(parent) => parent,
+                Err(_) => continue,
+            };
+            // the pregnancy holds the energy until the egg is laid
+            energy.spend(cost);
+            ledger.sink(cost);
+            // haploid corgis pass on their expressed genes
+            let alleles = alleles.cloned().unwrap_or_else(|| {
+                Alleles::homozygous(Genome::from_parts(brain.gene(), senses, genotype))
+            });
+            inherited.push((alleles, Generation(generation.0), lineage.clone()));
+            mated.insert(*parent);
+        }
+        let (first, last) = match (inherited.first(), inherited.last()) {
+            (Some((first, ..)), Some((last, ..))) => (first, last),
+            _ => continue,
+        };
+
+        let (genome, alleles) = match scenario.genetics {
+            Genetics::Haploid => {
+                let genome = match inherited.as_slice() {
+                    [_] => first.first.child(rng, &scenario.mutation),
+                    _ => first
+                        .first
+                        .crossover(&last.first, rng)
+                        .child(rng, &scenario.mutation),
+                };
+                (genome, None)
+            }
+            Genetics::Diploid => {
+                let alleles = Alleles {
+                    first: first.gamete(rng, &scenario.mutation),
+                    second: last.gamete(rng, &scenario.mutation),
+                };
+                (alleles.express(), Some(alleles))
+            }
+        };
+        let pregnancy = Pregnancy {
+            due: tick.0 + config.gestation_ticks,
+            genome,
+            alleles,
+            parents: inherited
+                .into_iter()
+                .map(|(_, generation, lineage)| (generation, lineage))
+                .collect(),
+        };
+        commands.insert_one(entity, pregnancy);
+    }
+}
+
+/// Pregnant corgis lay their egg where they are once it is due.
+pub fn lay_eggs(
+    commands: &mut Commands,
+    tick: Res<Tick>,
+    config: Res<SimConfig>,
+    assets: Res<MyAssets>,
+    ledger: Res<EnergyLedger>,
+    query: Query<(Entity, &Transform, &Pregnancy)>,
+) {
+    for (entity, transform, pregnancy) in query.iter() {
+        if tick.0 < pregnancy.due {
+            continue;
+        }
+        let pos = transform.translation.truncate();
+        let egg = Egg {
+            hatch: tick.0 + config.incubation_ticks,
+            energy: CORGI_ENERGY_BORN,
+            genome: pregnancy.genome.clone(),
+            alleles: pregnancy.alleles.clone(),
+            parents: pregnancy
+                .parents
+                .iter()
+                .map(|(generation, lineage)| (Generation(generation.0), lineage.clone()))
+                .collect(),
+        };
+        ledger.source(egg.energy);
+        spawn_egg(commands, &assets, pos, egg);
+        commands.remove_one::<Pregnancy>(entity);
+    }
+}
+
+pub fn spawn_egg(commands: &mut Commands, assets: &MyAssets, pos: Vec2, egg: Egg) {
+    commands
+        .spawn(SpriteBundle {
+            sprite: Sprite::new(Vec2::splat(EGG_SIZE)),
+            material: assets.egg_material.clone(),
+            transform: Transform::from_translation(pos.extend(0.0)),
+            ..Default::default()
+        })
+        .with(egg);
+}
+
+/// Due eggs hatch into a child with their remaining energy,
+/// which develops in the environment at the egg. Eaten up eggs are gone.
+#[allow(clippy::too_many_arguments)]
+pub fn hatch_eggs(
+    commands: &mut Commands,
+    tick: Res<Tick>,
+    assets: Res<MyAssets>,
+    daylight: Res<Daylight>,
+    stats: Res<RegionStats>,
+    ledger: Res<EnergyLedger>,
+    mut log: ResMut<LineageLog>,
+    mut rng: ResMut<SimRng>,
+    query: Query<(Entity, &Transform, &Egg)>,
+) {
+    for (entity, transform, egg) in query.iter() {
+        if egg.energy < EGG_MIN_ENERGY {
+            ledger.sink(egg.energy);
+            commands.despawn(entity);
+            continue;
+        }
+        if tick.0 < egg.hatch {
+            continue;
+        }
+        let pos = transform.translation.truncate();
+        let inheritance: Vec<Inheritance> = egg
+            .parents
+            .iter()
+            .map(|(generation, lineage)| Inheritance {
+                generation,
+                lineage,
+            })
+            .collect();
+        let environment = corgi::environment_at(pos, &daylight, &stats);
+        let genome = egg.genome.clone();
+        let mut child = CorgiBundle::new_born(
+            pos,
+            genome,
+            &inheritance,
+            &environment,
+            &assets,
+            &mut log,
+            &mut rng,
+        );
+        child.energy = Energy::new(egg.energy);
+        ledger.sink(egg.energy - child.energy.get());
+        commands.spawn(child);
+        if let Some(alleles) = egg.alleles.clone() {
+            commands.with(alleles);
+        }
+        commands.despawn(entity);
+    }
+}
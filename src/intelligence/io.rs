@@ -0,0 +1,38 @@
+/// Encoding of simulation values as brain inputs and outputs.
+/// The network works with values in `[-1, 1]`.
+pub trait Io: Sized {
+    fn encode(&self) -> f32;
+    fn decode(value: f32) -> Self;
+}
+
+/// `true` for positive outputs.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IoBool(pub bool);
+
+impl Io for IoBool {
+    fn encode(&self) -> f32 {
+        if self.0 {
+            1.0
+        } else {
+            -1.0
+        }
+    }
+
+    fn decode(value: f32) -> Self {
+        Self(value > 0.0)
+    }
+}
+
+/// A value in the unit interval `[0, 1]`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IoF32(pub f32);
+
+impl Io for IoF32 {
+    fn encode(&self) -> f32 {
+        self.0.max(0.0).min(1.0) * 2.0 - 1.0
+    }
+
+    fn decode(value: f32) -> Self {
+        Self((value.max(-1.0).min(1.0) + 1.0) / 2.0)
+    }
+}
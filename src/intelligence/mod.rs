@@ -1,15 +1,44 @@
 pub mod brain;
 pub mod decision;
+pub mod io;
 pub mod perception;
 
 // for testing the IO
+#[cfg(test)]
 mod test;
-use super::test;
 
+use crate::{
+    config::SimConfig, corgi::Resting, debug::NonFiniteStats, genes::SenseGene, rng::RngStream,
+    state::running, universe::Tick,
+};
 use bevy::prelude::*;
-use bevy_rapier2d::{physics::RigidBodyHandleComponent, rapier::dynamics::RigidBodySet};
+use io::Io;
+use perception::{
+    Novelty, NoveltySettings, Oscillators, BODY_INPUTS, NEIGHBOR_COUNT, VISION_RAY_INPUTS,
+};
 
-use crate::corgi::{Corgi, Energy};
+pub use brain::{Brain, BrainLayout, LayerDiff, NetworkDiff, NetworkSummary, NeuralNetwork};
+
+/// Resting corgis only run their network every this many think intervals.
+pub const REST_THINK_INTERVAL_FACTOR: usize = 4;
+
+macro_rules! perception {
+    ($($i:ident), *) => {
+        $(
+            #[derive(Default, Clone, Debug)]
+            pub struct $i(pub Perception);
+        )*
+    };
+}
+
+macro_rules! decision {
+    ($($i:ident), *) => {
+        $(
+            #[derive(Default, Clone, Debug)]
+            pub struct $i(pub Decision);
+        )*
+    }
+}
 
 /// The Brain system needs to have some sort of IO.
 /// Each corgi has his own neural network, which is run once per frame.
@@ -53,8 +82,9 @@ use crate::corgi::{Corgi, Energy};
 /// Any invalid input or output shape can be reported, if it doesn't match the brain shape.
 ///
 /// For the dry-run we need the mock objects. After it we need the actual stores.
-/// How do we switch? Do we replace the components (change archetype) or
-/// does the component change it's state (for instance enum).
+/// The decision components change their state for this:
+/// during the dry-run they only count the retrievals and hand out zeros.
+/// The perceptions are collected like always and only counted afterwards.
 
 /// Perceive => Think => Decide => Transition
 pub struct IntelligencePlugin;
@@ -63,23 +93,55 @@ impl Plugin for IntelligencePlugin {
     fn build(&self, app: &mut AppBuilder) {
         app
             // perception stage (multiple systems) -- fill InputStore values
-            .add_stage("perceive", SystemStage::parallel())
+            .add_stage(
+                "perceive",
+                SystemStage::parallel().with_run_criteria(running.system()),
+            )
             // think / compute stage (one neural network system) -- run nn, take all InputStore values (shape check), provide empty OutputStore
-            .add_stage_after("perceive", "think", SystemStage::parallel())
+            .add_stage_after(
+                "perceive",
+                "think",
+                SystemStage::parallel().with_run_criteria(running.system()),
+            )
             // decide stage (multiple systems) -- use all OutputStore values
-            .add_stage_after("think", "decide", SystemStage::parallel())
-            // transition stage (one system) -- remove OutputStore Comps (empty check), add InputStore Comps (empty)
-            .add_stage_after("decide", "transition", SystemStage::parallel())
-            
+            .add_stage_after(
+                "think",
+                "decide",
+                SystemStage::parallel().with_run_criteria(running.system()),
+            )
+            // transition stage (one system) -- finish dry-run, check and empty all stores
+            .add_stage_after(
+                "decide",
+                "transition",
+                SystemStage::parallel().with_run_criteria(running.system()),
+            )
+            .add_resource(perception::Oscillators::default())
+            .add_resource(NoveltySettings::default())
+            .add_resource(decision::AvoidanceAssist::default())
             // --- default systems ---
-            // dry-run startup system (determine neural network shape)
-            .add_system(dry_run.system());
+            .add_system_to_stage("perceive", perception::perceive_body.system())
+            .add_system_to_stage("perceive", perception::perceive_neighbors.system())
+            .add_system_to_stage("perceive", perception::perceive_flock.system())
+            .add_system_to_stage("perceive", perception::perceive_vision.system())
+            .add_system_to_stage("perceive", perception::perceive_smell.system())
+            .add_system_to_stage("perceive", perception::perceive_hearing.system())
+            .add_system_to_stage("perceive", perception::perceive_clock.system())
+            .add_system_to_stage("perceive", perception::perceive_compass.system())
+            .add_system_to_stage("perceive", perception::perceive_novelty.system())
+            .add_system_to_stage("perceive", perception::perceive_environment.system())
+            .add_system_to_stage("perceive", perception::perceive_memory.system())
             .add_system_to_stage("think", think.system())
+            // also consumes the dash and rest decisions
+            .add_system_to_stage("decide", decision::decide_movement.system())
+            .add_system_to_stage("decide", decision::decide_bark.system())
+            .add_system_to_stage("decide", decision::decide_color.system())
+            .add_system_to_stage("decide", decision::decide_pheromone.system())
+            .add_system_to_stage("decide", decision::decide_eat.system())
+            .add_system_to_stage("decide", decision::decide_attack.system())
+            .add_system_to_stage("decide", decision::decide_carry.system())
+            .add_system_to_stage("decide", decision::decide_groom.system())
+            .add_system_to_stage("decide", decision::decide_memory.system())
             .add_system_to_stage("transition", transition.system());
-
-            // debug systems
-            .add_system_to_stage("perceive", test::perceive.system())
-            .add_system_to_stage("decide", test::decide.system())
     }
 
     fn name(&self) -> &str {
@@ -87,30 +149,57 @@ impl Plugin for IntelligencePlugin {
     }
 }
 
-pub struct Brain;
-
-impl Default for Brain {
-    fn default() -> Self {
-        Self::new_random()
+/// Whether the decision components get low-pass filtered by the brain, in output order.
+/// Only continuous decisions are smoothed, the switches stay responsive.
+const SMOOTHED_DECISIONS: [bool; 12] = [
+    true, false, false, false, true, false, false, false, false, false, false, false,
+];
+
+/// The layout the dry-run finds with the default systems,
+/// so genomes can be checked without running a simulation.
+/// Has to be kept in sync with the perception and decision systems.
+pub fn expected_layout(oscillators: &Oscillators, config: &SimConfig) -> BrainLayout {
+    let perceptions = [
+        BODY_INPUTS,                            // body
+        config.vision_rays * VISION_RAY_INPUTS, // vision
+        NEIGHBOR_COUNT * 10,                    // neighbors
+        5,                                      // flock
+        6,                                      // smell
+        3,                                      // hearing
+        oscillators.periods.len() + 2,          // clock
+        3,                                      // compass
+        1,                                      // novelty
+        5,                                      // environment
+        config.memory_size,                     // memory
+    ];
+    let memory = config.memory_size;
+    BrainLayout {
+        inputs: perceptions.iter().sum(),
+        // movement, dash, bark, reproduction, color, pheromone, eat, attack, carry, rest, groom,
+        // memory
+        outputs: vec![2, 2, 1, 1, 3, 1, 1, 1, 1, 1, 1, memory],
+        smoothed: SMOOTHED_DECISIONS.to_vec(),
     }
 }
 
+/// The cells written by the `MemoryDecision` and read back by the `MemoryPerception`.
+#[derive(Default, Clone, Debug)]
+pub struct Memory(pub Vec<f32>);
+
+#[derive(Bundle, Default)]
 pub struct IntelligenceBundle {
-    brain: Brain,
-    perception: PerceptionBundle,
-    decision: DecisionBundle,
-    perception_dry_run: ThoughtDryRun,
-    decision_dry_run: ThoughtDryRun,
+    pub brain: Brain,
+    pub novelty: Novelty,
+    pub memory: Memory,
+    pub perception: PerceptionBundle,
+    pub decision: DecisionBundle,
 }
-impl Default for IntelligenceBundle {
-    fn default() -> Self {
+
+impl IntelligenceBundle {
+    pub fn with_brain(brain: Brain) -> Self {
         Self {
-            state: IntelligenceState::DryRun,
-            brain: Brain::default(),
-            perception: PerceptionBundle::default(),
-            decision: DecisionBundle::default(),
-            perception_dry_run: ThoughtDryRun::default(),
-            decision_dry_run: ThoughtDryRun::default(),
+            brain,
+            ..Default::default()
         }
     }
 }
@@ -124,11 +213,19 @@ trait BrainInputStore: BrainStore {
     fn extend<I>(&mut self, values: I)
     where
         I: Iterator<Item = f32>;
+
+    fn put_io<T: Io>(&mut self, value: T) {
+        self.put(value.encode());
+    }
 }
 
 trait BrainOutputStore: BrainStore {
     fn take(&mut self) -> f32;
-    fn take_multiple(&mut self, n: usize) -> Box<[f32]>;
+    fn take_multiple(&mut self, n: usize) -> Vec<f32>;
+
+    fn take_io<T: Io>(&mut self) -> T {
+        T::decode(self.take())
+    }
 }
 
 // PerceptionComponent
@@ -136,25 +233,98 @@ trait BrainOutputStore: BrainStore {
 pub struct Perception {
     vec: Vec<f32>,
 }
+
+impl Perception {
+    /// The values fed into the brain, zeros if the sense is disabled.
+    pub fn to_input(&self, enabled: bool) -> impl Iterator<Item = f32> + '_ {
+        self.vec.iter().map(move |v| if enabled { *v } else { 0.0 })
+    }
+
+    fn clear(&mut self) {
+        self.vec.clear();
+    }
+}
+
 // DecisionComponent
 #[derive(Default, Clone, Debug)]
 pub struct Decision {
     vec: Vec<f32>,
+    dry_run: Option<ThoughtDryRun>,
 }
 
-// TODO: write procedural attribute macro
-perception!(BodyPerception, VisionPerception);
+impl Decision {
+    /// Provides the values for the decide stage.
+    fn fill(&mut self, values: &[f32]) {
+        // values get popped from the back
+        self.vec.extend(values.iter().rev());
+    }
+
+    fn start_dry_run(&mut self) {
+        self.dry_run = Some(ThoughtDryRun::default());
+    }
+
+    /// Returns the number of retrieved values.
+    fn end_dry_run(&mut self) -> usize {
+        self.dry_run.take().map_or(0, |dry_run| dry_run.len())
+    }
+}
+
+perception!(
+    BodyPerception,
+    VisionPerception,
+    NeighborPerception,
+    FlockPerception,
+    SmellPerception,
+    HearingPerception,
+    ClockPerception,
+    CompassPerception,
+    NoveltyPerception,
+    EnvironmentPerception,
+    MemoryPerception
+);
 #[derive(Bundle, Default)]
 pub struct PerceptionBundle {
     body: BodyPerception,
     vision: VisionPerception,
+    neighbors: NeighborPerception,
+    flock: FlockPerception,
+    smell: SmellPerception,
+    hearing: HearingPerception,
+    clock: ClockPerception,
+    compass: CompassPerception,
+    novelty: NoveltyPerception,
+    environment: EnvironmentPerception,
+    memory: MemoryPerception,
 }
 
-decision!(MovementDecision, ReproductionDecision);
+decision!(
+    MovementDecision,
+    DashDecision,
+    BarkDecision,
+    ReproductionDecision,
+    ColorDecision,
+    PheromoneDecision,
+    EatDecision,
+    AttackDecision,
+    CarryDecision,
+    RestDecision,
+    GroomDecision,
+    MemoryDecision
+);
 #[derive(Bundle, Default)]
 pub struct DecisionBundle {
     movement: MovementDecision,
+    dash: DashDecision,
+    bark: BarkDecision,
     reproduction: ReproductionDecision,
+    color: ColorDecision,
+    pheromone: PheromoneDecision,
+    eat: EatDecision,
+    attack: AttackDecision,
+    carry: CarryDecision,
+    rest: RestDecision,
+    groom: GroomDecision,
+    memory: MemoryDecision,
 }
 
 impl BrainStore for Perception {
@@ -174,7 +344,7 @@ impl BrainInputStore for Perception {
         I: Iterator<Item = f32>,
     {
         for value in values {
-            self.put(value.to_owned());
+            self.put(value);
         }
     }
 }
@@ -188,7 +358,10 @@ impl BrainStore for Decision {
 // always has to be emptied in the same order
 impl BrainOutputStore for Decision {
     fn take(&mut self) -> f32 {
-        self.vec.pop().expect("No more outputs left")
+        match &mut self.dry_run {
+            Some(dry_run) => dry_run.take(),
+            None => self.vec.pop().expect("No more outputs left"),
+        }
     }
 
     fn take_multiple(&mut self, n: usize) -> Vec<f32> {
@@ -200,28 +373,256 @@ impl BrainOutputStore for Decision {
     }
 }
 
-// initally corgi needs BodyPerception and VisionPerception
-
-fn think(query: Query<(&Brain, &PerceptionBundle, &DecisionBundle)>) {
-    for (brain, perception, decision) in query.iter() {
+fn think(
+    tick: Res<Tick>,
+    config: Res<SimConfig>,
+    novelty_settings: Res<NoveltySettings>,
+    mut non_finite: ResMut<NonFiniteStats>,
+    mut query: Query<(
+        &mut Brain,
+        &mut Novelty,
+        &SenseGene,
+        &Resting,
+        // in input order
+        (
+            &BodyPerception,
+            &VisionPerception,
+            &NeighborPerception,
+            &FlockPerception,
+            &SmellPerception,
+            &HearingPerception,
+            &ClockPerception,
+            &CompassPerception,
+            &NoveltyPerception,
+            &EnvironmentPerception,
+            &MemoryPerception,
+        ),
+        // in output order
+        (
+            &mut MovementDecision,
+            &mut DashDecision,
+            &mut BarkDecision,
+            &mut ReproductionDecision,
+            &mut ColorDecision,
+            &mut PheromoneDecision,
+            &mut EatDecision,
+            &mut AttackDecision,
+            &mut CarryDecision,
+            &mut RestDecision,
+            &mut GroomDecision,
+            &mut MemoryDecision,
+        ),
+    )>,
+) {
+    for (
+        mut brain,
+        mut novelty,
+        senses,
+        resting,
+        (
+            body,
+            vision,
+            neighbors,
+            flock,
+            smell,
+            hearing,
+            clock,
+            compass,
+            novelty_perception,
+            environment,
+            memory_perception,
+        ),
+        (
+            mut movement,
+            mut dash,
+            mut bark,
+            mut reproduction,
+            mut color,
+            mut pheromone,
+            mut eat,
+            mut attack,
+            mut carry,
+            mut rest,
+            mut groom,
+            mut memory,
+        ),
+    ) in query.iter_mut()
+    {
         // collect all BrainInputStores together -> always same ordering of values
-        let perception = perception
-            .body
+        // the sensor mask of the genes zeroes disabled senses
+        let input: Vec<f32> = body
             .0
-            .vec
-            .into_iter()
-            .zip(perception.vision.0.vec.into_iter());
+            .to_input(true)
+            .chain(vision.0.to_input(senses.vision))
+            .chain(neighbors.0.to_input(true))
+            .chain(flock.0.to_input(true))
+            .chain(smell.0.to_input(senses.smell))
+            .chain(hearing.0.to_input(senses.hearing))
+            .chain(clock.0.to_input(true))
+            .chain(compass.0.to_input(senses.compass))
+            .chain(novelty_perception.0.to_input(true))
+            .chain(environment.0.to_input(true))
+            .chain(memory_perception.0.to_input(true))
+            .collect();
+        novelty.observe(&input, &novelty_settings);
+        let mut decisions = [
+            &mut movement.0,
+            &mut dash.0,
+            &mut bark.0,
+            &mut reproduction.0,
+            &mut color.0,
+            &mut pheromone.0,
+            &mut eat.0,
+            &mut attack.0,
+            &mut carry.0,
+            &mut rest.0,
+            &mut groom.0,
+            &mut memory.0,
+        ];
+
+        let non_finite_before = brain.non_finite();
+        let interval = if resting.0 {
+            config.think_interval * REST_THINK_INTERVAL_FACTOR
+        } else {
+            config.think_interval
+        };
+        let output = brain.think(&input, tick.0, interval);
+        non_finite.outputs += brain.non_finite() - non_finite_before;
+        match (output, brain.layout()) {
+            (Some(output), Some(layout)) => {
+                let mut offset = 0;
+                for (decision, len) in decisions.iter_mut().zip(layout.outputs.iter()) {
+                    decision.fill(&output[offset..offset + len]);
+                    offset += len;
+                }
+            }
+            _ => {
+                for decision in decisions.iter_mut() {
+                    decision.start_dry_run();
+                }
+            }
+        }
     }
 }
 
-fn transition(commands: &mut Commands, query: Query<(Entity, &DecisionBundle)>) {
-    for (entity, decision) in query.iter() {
+fn transition(
+    config: Res<SimConfig>,
+    oscillators: Res<Oscillators>,
+    mut query: Query<(
+        &mut Brain,
+        &mut RngStream,
+        (
+            &mut BodyPerception,
+            &mut VisionPerception,
+            &mut NeighborPerception,
+            &mut FlockPerception,
+            &mut SmellPerception,
+            &mut HearingPerception,
+            &mut ClockPerception,
+            &mut CompassPerception,
+            &mut NoveltyPerception,
+            &mut EnvironmentPerception,
+            &mut MemoryPerception,
+        ),
+        (
+            &mut MovementDecision,
+            &mut DashDecision,
+            &mut BarkDecision,
+            &mut ReproductionDecision,
+            &mut ColorDecision,
+            &mut PheromoneDecision,
+            &mut EatDecision,
+            &mut AttackDecision,
+            &mut CarryDecision,
+            &mut RestDecision,
+            &mut GroomDecision,
+            &mut MemoryDecision,
+        ),
+    )>,
+) {
+    for (
+        mut brain,
+        mut rng,
+        (
+            mut body,
+            mut vision,
+            mut neighbors,
+            mut flock,
+            mut smell,
+            mut hearing,
+            mut clock,
+            mut compass,
+            mut novelty,
+            mut environment,
+            mut memory_perception,
+        ),
+        (
+            mut movement,
+            mut dash,
+            mut bark,
+            mut reproduction,
+            mut color,
+            mut pheromone,
+            mut eat,
+            mut attack,
+            mut carry,
+            mut rest,
+            mut groom,
+            mut memory,
+        ),
+    ) in query.iter_mut()
+    {
+        let mut perceptions = [
+            &mut body.0,
+            &mut vision.0,
+            &mut neighbors.0,
+            &mut flock.0,
+            &mut smell.0,
+            &mut hearing.0,
+            &mut clock.0,
+            &mut compass.0,
+            &mut novelty.0,
+            &mut environment.0,
+            &mut memory_perception.0,
+        ];
+        let mut decisions = [
+            &mut movement.0,
+            &mut dash.0,
+            &mut bark.0,
+            &mut reproduction.0,
+            &mut color.0,
+            &mut pheromone.0,
+            &mut eat.0,
+            &mut attack.0,
+            &mut carry.0,
+            &mut rest.0,
+            &mut groom.0,
+            &mut memory.0,
+        ];
+
+        if brain.is_dry() {
+            let layout = BrainLayout {
+                inputs: perceptions.iter().map(|p| p.len()).sum(),
+                outputs: decisions.iter_mut().map(|d| d.end_dry_run()).collect(),
+                smoothed: SMOOTHED_DECISIONS.to_vec(),
+            };
+            debug_assert_eq!(
+                layout,
+                expected_layout(&oscillators, &config),
+                "`expected_layout` is out of sync"
+            );
+            brain.set_layout(layout, &mut *rng);
+        }
 
         // check if all outputs have been consumed
-        assert_eq!(decision.movement.0.len(), 0);
-        assert_eq!(decision.reproduction.0.len(), 0);
+        for decision in decisions.iter() {
+            assert_eq!(decision.len(), 0, "not all decisions have been consumed");
+        }
 
-        // empty Perceptions and Decisions
+        // empty Perceptions
+        for perception in perceptions.iter_mut() {
+            perception.clear();
+        }
     }
 }
 
@@ -238,19 +639,6 @@ impl BrainStore for ThoughtDryRun {
     }
 }
 
-impl BrainInputStore for ThoughtDryRun {
-    fn put(&mut self, _: f32) {
-        self.count += 1;
-    }
-
-    fn extend<I>(&mut self, values: I)
-    where
-        I: Iterator<Item = f32>,
-    {
-        self.count += values.count();
-    }
-}
-
 impl BrainOutputStore for ThoughtDryRun {
     fn take(&mut self) -> f32 {
         self.count += 1;
@@ -259,30 +647,6 @@ impl BrainOutputStore for ThoughtDryRun {
 
     fn take_multiple(&mut self, n: usize) -> Vec<f32> {
         self.count += n;
-        vec![]
-    }
-}
-
-fn dry_run(query: Query<(PerceptionDryRun, DecisionDryRun), Added<IntelligenceBundle>>) {
-    // should the dryrun types persist after the dry run or should they be removed?
-    todo!()
-}
-
-macro_rules! perception {
-    ($($i:ident), *) => {
-        $(
-            #[derive(Default, Clone, Debug)]
-            pub struct $i(pub Perception);
-        )*
-    };
-}
-
-macro_rules! decision {
-    ($($i:ident), *) => {
-        $(
-            #[derive(Default, Clone, Debug)]
-            pub struct $i(pub Decision);
-        )*
+        vec![0.0; n]
     }
 }
-
@@ -1 +1,630 @@
+use super::{
+    io::{IoBool, IoF32},
+    BodyPerception, Brain, BrainInputStore, ClockPerception, CompassPerception,
+    EnvironmentPerception, FlockPerception, HearingPerception, Memory, MemoryPerception,
+    NeighborPerception, NoveltyPerception, Perception, SmellPerception, VisionPerception,
+};
+use crate::{
+    color::{self, ActiveColorSemantics, DisplayColor},
+    config::SimConfig,
+    corgi::{
+        AdultMass, Age, Energy, Health, Home, Load, Mass, RecentDamage, Stamina, CARRY_CAPACITY,
+        CORGI_BASE_MASS, MAX_HEALTH,
+    },
+    genes::{Genome, Genotype, MetabolismGene, SenseGene},
+    parasite::Infestation,
+    pheromone::PheromoneField,
+    rng::RngStream,
+    scent::ScentField,
+    sound::Sounds,
+    spatial::SpatialGrid,
+    universe::{
+        tile::{OnTile, TileGrid, TilePos, TileType},
+        Daylight, Light, Season, Tick, Topology, Universe,
+    },
+};
+use bevy::prelude::*;
+use bevy_rapier2d::{physics::RigidBodyHandleComponent, rapier::dynamics::RigidBodySet};
+use rand::Rng;
+use std::collections::{HashMap, VecDeque};
 
+/// Number of inputs of the `BodyPerception`.
+pub const BODY_INPUTS: usize = 9;
+
+/// Number of parasites at which the perceived infestation saturates.
+const INFESTATION_SATURATION: f32 = 10.0;
+
+/// Number of corgis in the `NeighborPerception`.
+pub const NEIGHBOR_COUNT: usize = 3;
+/// Corgis further away aren't perceived.
+pub const NEIGHBOR_RANGE: f32 = 100.0;
+
+/// Perceived velocities are relative to this speed and saturate beyond it.
+/// About the speed at which the drag of a base size corgi balances the full movement force.
+pub const PERCEIVED_SPEED: f32 = 100.0;
+
+/// Radius of the `FlockPerception`.
+pub const FLOCK_RANGE: f32 = 150.0;
+/// Number of flock mates at which the perceived flock size saturates.
+const FLOCK_SATURATION: f32 = 20.0;
+
+/// Number of inputs per ray of the `VisionPerception`.
+pub const VISION_RAY_INPUTS: usize = 4;
+pub const VISION_RANGE: f32 = 150.0;
+/// Fraction of the vision range left at midnight.
+pub const NIGHT_VISION: f32 = 0.3;
+/// Corgis are seen as circles of this radius.
+const VISION_CORGI_RADIUS: f32 = 10.0;
+/// Tiles holding at least this fraction of their capacity are seen as food.
+const VISION_FOOD_FRACTION: f32 = 0.5;
+
+/// The age at which the perceived age reaches one half.
+pub const AGE_SCALE: f32 = 10_000.0;
+
+/// Periods in ticks of the global sine oscillators of the `ClockPerception`.
+/// Changing them changes the brain layout, so it should only be done before any corgi exists.
+#[derive(Clone, Debug)]
+pub struct Oscillators {
+    pub periods: Vec<usize>,
+}
+
+impl Default for Oscillators {
+    fn default() -> Self {
+        Self {
+            periods: vec![60, 600, 3600],
+        }
+    }
+}
+
+/// Configuration of the `Novelty` estimation.
+#[derive(Clone, Copy, Debug)]
+pub struct NoveltySettings {
+    /// Number of remembered inputs.
+    pub archive_size: usize,
+    /// Ticks between two inputs entering the archive.
+    pub archive_interval: usize,
+    /// Fraction of the previous novelty kept every tick, smooths the signal.
+    pub decay: f32,
+}
+
+impl Default for NoveltySettings {
+    fn default() -> Self {
+        Self {
+            archive_size: 16,
+            archive_interval: 10,
+            decay: 0.9,
+        }
+    }
+}
+
+/// How different the current input of the brain is from the recently remembered ones.
+/// Updated by `think` and perceived in the next tick.
+#[derive(Default, Clone, Debug)]
+pub struct Novelty {
+    archive: VecDeque<Vec<f32>>,
+    ticks: usize,
+    pub value: f32,
+}
+
+impl Novelty {
+    /// Root mean square distance of `input` to the nearest remembered input.
+    pub fn observe(&mut self, input: &[f32], settings: &NoveltySettings) {
+        let distance = self
+            .archive
+            .iter()
+            .map(|past| {
+                let sum: f32 = past.iter().zip(input).map(|(a, b)| (a - b).powi(2)).sum();
+                (sum / input.len().max(1) as f32).sqrt()
+            })
+            .fold(None, |min: Option<f32>, d| {
+                Some(min.map_or(d, |min| min.min(d)))
+            })
+            .unwrap_or(0.0);
+        self.value = self.value * settings.decay + distance * (1.0 - settings.decay);
+
+        if self.ticks % settings.archive_interval.max(1) == 0 {
+            self.archive.push_back(input.to_vec());
+            while self.archive.len() > settings.archive_size {
+                self.archive.pop_front();
+            }
+        }
+        self.ticks += 1;
+    }
+}
+
+/// Perceives the novelty of the situation, enabling curiosity.
+pub fn perceive_novelty(mut query: Query<(&Novelty, &mut NoveltyPerception)>) {
+    for (novelty, mut perception) in query.iter_mut() {
+        // inputs are in [-1, 1], so the distance is at most 2
+        perception.0.put_io(IoF32(novelty.value / 2.0));
+    }
+}
+
+/// Perceives the state of the own body:
+/// hunger, health, the recently taken damage relative to the maximum health, stamina,
+/// the age relative to the lifespan, the mass relative to the adult mass,
+/// the age relative to the maturation age up to 1 for adults,
+/// the carried food relative to the carry capacity and the number of parasites.
+pub fn perceive_body(
+    mut query: Query<(
+        (&Energy, &Health, &Load, &Infestation),
+        &RecentDamage,
+        &Stamina,
+        (&Age, &MetabolismGene),
+        (&Mass, &AdultMass),
+        &mut BodyPerception,
+    )>,
+) {
+    for (
+        (energy, health, load, infestation),
+        damage,
+        stamina,
+        (age, metabolism),
+        (mass, adult_mass),
+        mut perception,
+    ) in query.iter_mut()
+    {
+        perception.0.put_io(IoF32(1.0 - energy.fraction()));
+        perception.0.put_io(IoF32(health.fraction()));
+        perception.0.put_io(IoF32(damage.0 / MAX_HEALTH));
+        perception.0.put_io(IoF32(stamina.0));
+        perception
+            .0
+            .put_io(IoF32(age.0 as f32 / metabolism.lifespan().max(1) as f32));
+        perception.0.put_io(IoF32(adult_mass.maturity(*mass)));
+        let maturation_age = metabolism.maturation_age().max(1);
+        perception
+            .0
+            .put_io(IoF32((age.0 as f32 / maturation_age as f32).min(1.0)));
+        perception.0.put_io(IoF32(load.0 / CARRY_CAPACITY));
+        perception.0.put_io(IoF32(
+            (infestation.0 as f32 / INFESTATION_SATURATION).min(1.0),
+        ));
+    }
+}
+
+/// Perceives presence, relative position, velocity, mass, displayed color and relatedness
+/// of the nearest corgis.
+/// The velocity is relative to `PERCEIVED_SPEED`, the mass is `m / (m + CORGI_BASE_MASS)`.
+/// The color is perceived as hue, saturation and value, its meaning depends on the color semantics.
+/// The relatedness is `1 / (1 + d)` for the genetic distance `d`, so it is 1 for clones.
+/// Missing neighbors are perceived as zeros.
+#[allow(clippy::too_many_arguments)]
+pub fn perceive_neighbors(
+    grid: Res<SpatialGrid>,
+    bodies: Res<RigidBodySet>,
+    semantics: Res<ActiveColorSemantics>,
+    mut genomes: Local<HashMap<Entity, Genome>>,
+    mut query: Query<(Entity, &Transform, &DisplayColor, &mut NeighborPerception)>,
+    others: Query<(&RigidBodyHandleComponent, &Mass, &DisplayColor)>,
+    genes: Query<(&Brain, &SenseGene, &Genotype)>,
+) {
+    // the network is only replaced at the end of the dry-run and `relatedness` doesn't cache
+    // dry brains, so a cached genome stays valid until its corgi is gone
+    genomes.retain(|entity, _| genes.get(*entity).is_ok());
+    for (entity, transform, own_color, mut perception) in query.iter_mut() {
+        let pos = transform.translation.truncate();
+        let neighbors = grid.nearest(pos, NEIGHBOR_COUNT, NEIGHBOR_RANGE, entity);
+        for i in 0..NEIGHBOR_COUNT {
+            let neighbor = neighbors.get(i).and_then(|(other, other_pos)| {
+                let (handle, mass, other_color) = others.get(*other).ok()?;
+                let body = bodies.get(handle.handle())?;
+                let color = color::hsv(semantics.0.perceived(other_color.0, own_color.0));
+                let relatedness = relatedness(&mut genomes, &genes, entity, *other).unwrap_or(0.0);
+                Some((
+                    *other_pos - pos,
+                    *body.linvel(),
+                    mass.get(),
+                    color,
+                    relatedness,
+                ))
+            });
+            match neighbor {
+                Some((offset, velocity, mass, [hue, saturation, value], relatedness)) => {
+                    perception.0.extend(
+                        [1.0, offset.x / NEIGHBOR_RANGE, offset.y / NEIGHBOR_RANGE]
+                            .iter()
+                            .copied(),
+                    );
+                    perception.0.put_io(signed(velocity.x / PERCEIVED_SPEED));
+                    perception.0.put_io(signed(velocity.y / PERCEIVED_SPEED));
+                    perception.0.put_io(IoF32(mass / (mass + CORGI_BASE_MASS)));
+                    perception
+                        .0
+                        .extend([hue, saturation, value, relatedness].iter().copied());
+                }
+                None => perception.0.extend([0.0; 10].iter().copied()),
+            }
+        }
+    }
+}
+
+/// Perceives the size, the center offset and the mean velocity of all corgis within `FLOCK_RANGE`.
+/// Cheaper than the `NeighborPerception` and not limited to a few corgis.
+/// The mean velocity is relative to `PERCEIVED_SPEED`.
+/// Without flock mates everything is zero.
+pub fn perceive_flock(
+    grid: Res<SpatialGrid>,
+    bodies: Res<RigidBodySet>,
+    mut query: Query<(Entity, &Transform, &mut FlockPerception)>,
+    others: Query<&RigidBodyHandleComponent>,
+) {
+    for (entity, transform, mut perception) in query.iter_mut() {
+        let pos = transform.translation.truncate();
+        let mut count = 0;
+        let mut offset = Vec2::zero();
+        let mut velocity = Vec2::zero();
+        for (other, other_pos) in grid.in_radius(pos, FLOCK_RANGE) {
+            if other == entity {
+                continue;
+            }
+            let body = others
+                .get(other)
+                .ok()
+                .and_then(|handle| bodies.get(handle.handle()));
+            if let Some(body) = body {
+                count += 1;
+                offset += other_pos - pos;
+                velocity += Vec2::new(body.linvel().x, body.linvel().y);
+            }
+        }
+        if count > 0 {
+            offset /= count as f32 * FLOCK_RANGE;
+            velocity /= count as f32;
+        }
+        perception
+            .0
+            .put_io(IoF32((count as f32 / FLOCK_SATURATION).min(1.0)));
+        perception.0.put(offset.x);
+        perception.0.put(offset.y);
+        perception.0.put_io(signed(velocity.x / PERCEIVED_SPEED));
+        perception.0.put_io(signed(velocity.y / PERCEIVED_SPEED));
+    }
+}
+
+/// Encodes a value in `[-1, 1]`, saturating beyond.
+fn signed(value: f32) -> IoF32 {
+    IoF32((value + 1.0) / 2.0)
+}
+
+/// Genetic relatedness of two corgis, `None` while one of the networks doesn't exist yet.
+fn relatedness(
+    genomes: &mut HashMap<Entity, Genome>,
+    genes: &Query<(&Brain, &SenseGene, &Genotype)>,
+    a: Entity,
+    b: Entity,
+) -> Option<f32> {
+    for entity in [a, b].iter() {
+        if genomes.contains_key(entity) {
+            continue;
+        }
+        let (brain, senses, genotype) = genes.get(*entity).ok()?;
+        if brain.is_dry() {
+            return None;
+        }
+        genomes.insert(*entity, Genome::from_parts(brain.gene(), senses, genotype));
+    }
+    Some(1.0 / (1.0 + genomes[&a].distance(&genomes[&b])))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum RayHit {
+    Corgi,
+    Wall,
+}
+
+/// Casts the configured number of rays over the field of view around the heading of the corgi.
+/// Every ray perceives the closeness of the first hit and what it hit,
+/// and the closeness of the first tile with food before the hit.
+/// Other corgis are only seen if they are detected,
+/// their visibility depends on the color semantics.
+/// Wall tiles block the rays.
+/// The range shrinks at night. A toroidal universe has no border.
+pub fn perceive_vision(
+    config: Res<SimConfig>,
+    daylight: Res<Daylight>,
+    grid: Res<SpatialGrid>,
+    semantics: Res<ActiveColorSemantics>,
+    tiles: Res<TileGrid>,
+    mut query: Query<(Entity, &Transform, &mut RngStream, &mut VisionPerception)>,
+    colors: Query<&DisplayColor>,
+) {
+    let universe = *grid.universe();
+    let visibility = |other: Entity, pos: Vec2| {
+        let color = colors.get(other).map_or(Color::BLACK, |c| c.0);
+        let background = tiles
+            .at(universe.wrap(pos))
+            .map_or(Color::BLACK, |tile| tile.base_color());
+        semantics.0.visibility(color, background)
+    };
+    let tile_at = |x: i64, y: i64| {
+        let (x, y) = match universe.topology {
+            Topology::Bounded => (x, y),
+            Topology::Toroidal => (
+                x.rem_euclid(universe.width_tiles as i64),
+                y.rem_euclid(universe.height_tiles as i64),
+            ),
+        };
+        if x < 0 || y < 0 {
+            return None;
+        }
+        tiles.get(TilePos {
+            x: x as usize,
+            y: y as usize,
+        })
+    };
+    let is_wall =
+        |x: i64, y: i64| tile_at(x, y).map_or(false, |tile| tile.tile_type == TileType::Wall);
+    let has_food = |x: i64, y: i64| {
+        tile_at(x, y).map_or(false, |tile| {
+            tile.capacity > 0.0 && tile.energy >= VISION_FOOD_FRACTION * tile.capacity
+        })
+    };
+
+    let range = VISION_RANGE * (NIGHT_VISION + (1.0 - NIGHT_VISION) * daylight.0);
+    for (entity, transform, mut rng, mut perception) in query.iter_mut() {
+        let origin = transform.translation.truncate();
+        let (axis, angle) = transform.rotation.to_axis_angle();
+        let heading = angle * axis.z.signum();
+        let others: Vec<Vec2> = grid
+            .in_radius(origin, range + VISION_CORGI_RADIUS)
+            .filter(|(other, _)| *other != entity)
+            .filter(|(other, pos)| rng.gen::<f32>() < visibility(*other, *pos))
+            .map(|(_, pos)| pos)
+            .collect();
+
+        for i in 0..config.vision_rays {
+            let offset = if config.vision_rays > 1 {
+                i as f32 / (config.vision_rays - 1) as f32 - 0.5
+            } else {
+                0.0
+            };
+            let ray_angle = heading + offset * config.vision_fov;
+            let direction = Vec2::new(ray_angle.cos(), ray_angle.sin());
+
+            let corgi_hit = others
+                .iter()
+                .filter_map(|center| ray_circle(origin, direction, *center, VISION_CORGI_RADIUS))
+                .min_by(|a, b| a.partial_cmp(b).unwrap())
+                .map(|distance| (distance, RayHit::Corgi));
+            let border_hit = match universe.topology {
+                Topology::Bounded => Some((ray_bounds(origin, direction, &universe), RayHit::Wall)),
+                Topology::Toroidal => None,
+            };
+            let wall_hit = ray_tiles(origin, direction, range, universe.tile_size, is_wall)
+                .map(|d| (d, RayHit::Wall));
+            let hit = corgi_hit
+                .into_iter()
+                .chain(border_hit)
+                .chain(wall_hit)
+                .filter(|(distance, _)| *distance <= range)
+                .min_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap());
+
+            let (closeness, kind) = match hit {
+                Some((distance, kind)) => (1.0 - distance / range, Some(kind)),
+                None => (0.0, None),
+            };
+            let visible = hit.map_or(range, |(distance, _)| distance);
+            let food = ray_tiles(origin, direction, visible, universe.tile_size, has_food)
+                .map_or(0.0, |distance| 1.0 - distance / range);
+            perception.0.put_io(IoF32(closeness));
+            perception.0.put_io(IoBool(kind == Some(RayHit::Corgi)));
+            perception.0.put_io(IoBool(kind == Some(RayHit::Wall)));
+            perception.0.put_io(IoF32(food));
+        }
+    }
+}
+
+/// Distance along the ray to the circle, if it's hit.
+fn ray_circle(origin: Vec2, direction: Vec2, center: Vec2, radius: f32) -> Option<f32> {
+    let m = origin - center;
+    let b = m.dot(direction);
+    let c = m.dot(m) - radius * radius;
+    if c > 0.0 && b > 0.0 {
+        return None;
+    }
+    let discriminant = b * b - c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    Some((-b - discriminant.sqrt()).max(0.0))
+}
+
+/// Distance along the ray to the first tile within `range` for which `matches` is true.
+/// Walks the tiles the ray passes through in order.
+fn ray_tiles<F>(
+    origin: Vec2,
+    direction: Vec2,
+    range: f32,
+    tile_size: f32,
+    matches: F,
+) -> Option<f32>
+where
+    F: Fn(i64, i64) -> bool,
+{
+    let mut x = (origin.x / tile_size).floor() as i64;
+    let mut y = (origin.y / tile_size).floor() as i64;
+    // distance along the ray to the next tile border and between two borders, per axis
+    let axis = |pos: f32, dir: f32, tile: i64| {
+        if dir > 0.0 {
+            (
+                ((tile + 1) as f32 * tile_size - pos) / dir,
+                tile_size / dir,
+                1,
+            )
+        } else if dir < 0.0 {
+            ((tile as f32 * tile_size - pos) / dir, -tile_size / dir, -1)
+        } else {
+            (f32::INFINITY, f32::INFINITY, 0)
+        }
+    };
+    let (mut next_x, delta_x, step_x) = axis(origin.x, direction.x, x);
+    let (mut next_y, delta_y, step_y) = axis(origin.y, direction.y, y);
+    let mut distance = 0.0;
+    while distance <= range {
+        if matches(x, y) {
+            return Some(distance);
+        }
+        if next_x < next_y {
+            distance = next_x;
+            next_x += delta_x;
+            x += step_x;
+        } else {
+            distance = next_y;
+            next_y += delta_y;
+            y += step_y;
+        }
+    }
+    None
+}
+
+/// Distance along the ray to the border of the universe.
+fn ray_bounds(origin: Vec2, direction: Vec2, universe: &Universe) -> f32 {
+    let axis = |pos: f32, dir: f32, max: f32| {
+        if dir > 0.0 {
+            (max - pos) / dir
+        } else if dir < 0.0 {
+            -pos / dir
+        } else {
+            f32::INFINITY
+        }
+    };
+    axis(origin.x, direction.x, universe.width())
+        .min(axis(origin.y, direction.y, universe.height()))
+        .max(0.0)
+}
+
+/// Perceives the local concentration of the body scent and then of the pheromones,
+/// each followed by the direction in which it increases.
+pub fn perceive_smell(
+    scent: Res<ScentField>,
+    pheromones: Res<PheromoneField>,
+    mut query: Query<(&Transform, &mut SmellPerception)>,
+) {
+    for (transform, mut perception) in query.iter_mut() {
+        let pos = transform.translation.truncate();
+        put_smell(
+            &mut perception.0,
+            scent.concentration(pos),
+            scent.gradient(pos),
+        );
+        put_smell(
+            &mut perception.0,
+            pheromones.concentration(pos),
+            pheromones.gradient(pos),
+        );
+    }
+}
+
+fn put_smell(perception: &mut Perception, concentration: f32, gradient: Vec2) {
+    let direction = if gradient.length() > 0.0 {
+        gradient.normalize()
+    } else {
+        Vec2::zero()
+    };
+    // maps the unbounded concentration into [0, 1)
+    perception.put_io(IoF32(concentration / (1.0 + concentration)));
+    perception.put(direction.x);
+    perception.put(direction.y);
+}
+
+/// Perceives the loudness of the barks of others and the direction they come from.
+pub fn perceive_hearing(
+    universe: Res<Universe>,
+    sounds: Res<Sounds>,
+    mut query: Query<(Entity, &Transform, &mut HearingPerception)>,
+) {
+    for (entity, transform, mut perception) in query.iter_mut() {
+        let pos = transform.translation.truncate();
+        let (loudness, direction) = sounds.hear(entity, pos, &universe);
+        perception.0.put_io(IoF32(loudness.min(1.0)));
+        perception.0.put(direction.x);
+        perception.0.put(direction.y);
+    }
+}
+
+/// Perceives the global oscillators, the own age and the daylight,
+/// so behavior can become time dependent and nocturnal or diurnal.
+pub fn perceive_clock(
+    tick: Res<Tick>,
+    daylight: Res<Daylight>,
+    oscillators: Res<Oscillators>,
+    mut query: Query<(&Age, &mut ClockPerception)>,
+) {
+    let phases: Vec<f32> = oscillators
+        .periods
+        .iter()
+        .map(|&period| {
+            let phase = (tick.0 % period.max(1)) as f32 / period.max(1) as f32;
+            (phase * std::f32::consts::PI * 2.0).sin()
+        })
+        .collect();
+    for (age, mut perception) in query.iter_mut() {
+        perception.0.extend(phases.iter().copied());
+        let age = age.0 as f32;
+        perception.0.put_io(IoF32(age / (age + AGE_SCALE)));
+        perception.0.put_io(IoF32(daylight.0));
+    }
+}
+
+/// Perceives the direction and distance to home, the shorter way around in a toroidal universe.
+pub fn perceive_compass(
+    universe: Res<Universe>,
+    mut query: Query<(&Transform, &Home, &mut CompassPerception)>,
+) {
+    let diagonal = universe.size().length();
+    for (transform, home, mut perception) in query.iter_mut() {
+        let offset = universe.offset(transform.translation.truncate(), home.0);
+        let distance = offset.length();
+        let direction = if distance > 0.0 {
+            offset / distance
+        } else {
+            Vec2::zero()
+        };
+        perception.0.put(direction.x);
+        perception.0.put(direction.y);
+        perception.0.put_io(IoF32(distance / diagonal));
+    }
+}
+
+/// Perceives the temperature of the own tile and how far it is from the preferred one,
+/// the light and the direction towards the sun spot.
+/// Outside of the universe the temperatures are zero.
+pub fn perceive_environment(
+    season: Res<Season>,
+    light: Res<Light>,
+    tiles: Res<TileGrid>,
+    mut query: Query<(
+        &Transform,
+        &OnTile,
+        &MetabolismGene,
+        &mut EnvironmentPerception,
+    )>,
+) {
+    for (transform, on_tile, metabolism, mut perception) in query.iter_mut() {
+        let temperature = on_tile
+            .0
+            .and_then(|pos| tiles.get(pos))
+            .map(|tile| tile.temperature(&season));
+        match temperature {
+            Some(temperature) => {
+                perception.0.put_io(IoF32(temperature));
+                perception.0.put(temperature - metabolism.comfort);
+            }
+            None => perception.0.extend([0.0; 2].iter().copied()),
+        }
+        let pos = transform.translation.truncate();
+        let direction = light.direction(pos);
+        perception.0.put_io(IoF32(light.at(pos)));
+        perception.0.put(direction.x);
+        perception.0.put(direction.y);
+    }
+}
+
+/// Perceives the memory cells written in the last tick, zeros for a new corgi.
+pub fn perceive_memory(config: Res<SimConfig>, mut query: Query<(&Memory, &mut MemoryPerception)>) {
+    for (memory, mut perception) in query.iter_mut() {
+        let cells = (0..config.memory_size).map(|i| memory.0.get(i).copied().unwrap_or(0.0));
+        perception.0.extend(cells);
+    }
+}
@@ -1,30 +1,75 @@
-pub fn perceive_vision(mut query: Query<&mut VisionPerception>) {
-    for mut vision in query.iter_mut() {
-        vision.0.put(0.0);
-        vision.0.put_multiple([1.2, 5.0, -1.0]);
-    }
+use super::{
+    io::{IoBool, IoF32},
+    *,
+};
+
+#[test]
+fn perceptions_keep_their_order() {
+    let mut perception = Perception::default();
+    perception.put(0.5);
+    perception.put_io(IoBool(true));
+    perception.put_io(IoF32(0.0));
+    perception.extend([0.25, -0.25].iter().copied());
+    assert_eq!(perception.len(), 5);
+    let input: Vec<f32> = perception.to_input(true).collect();
+    assert_eq!(input, vec![0.5, 1.0, -1.0, 0.25, -0.25]);
+
+    perception.clear();
+    assert_eq!(perception.len(), 0);
+}
+
+#[test]
+fn disabled_senses_are_zeroes() {
+    let mut perception = Perception::default();
+    perception.extend([0.5, -1.0, 1.0].iter().copied());
+    let input: Vec<f32> = perception.to_input(false).collect();
+    assert_eq!(input, vec![0.0; 3]);
 }
 
-pub fn perceive_body(mut query: Query<&mut BodyPerception>) {
-    for mut body in query.iter_mut() {
-        body.0.put(0.0);
-        body.0.put_multiple([1.2, 5.0, -1.0]);
-    }
+#[test]
+fn decisions_are_taken_in_output_order() {
+    let mut decision = Decision::default();
+    decision.fill(&[1.0, -1.0, 0.0, 0.5]);
+    assert_eq!(decision.len(), 4);
+    assert_eq!(decision.take_io::<IoBool>(), IoBool(true));
+    assert_eq!(decision.take_io::<IoBool>(), IoBool(false));
+    assert_eq!(decision.take_multiple(2), vec![0.0, 0.5]);
+    assert_eq!(decision.len(), 0);
 }
 
-pub fn decide_movement(
-    mut query: Query<&mut MovementDecision>
-) {
-    for mut movement in query.iter_mut() {
-        movement.0.take_multiple(5);
-    }
+#[test]
+fn dry_run_counts_the_retrievals() {
+    let mut decision = Decision::default();
+    decision.start_dry_run();
+    assert_eq!(decision.take_io::<IoF32>(), IoF32(0.5));
+    assert_eq!(decision.take_multiple(3), vec![0.0; 3]);
+    assert_eq!(decision.end_dry_run(), 4);
+    // nothing is left over for the check in the transition
+    assert_eq!(decision.len(), 0);
+    assert_eq!(decision.end_dry_run(), 0);
 }
 
-pub fn decide_reproduction(
-    mut query: Query<&mut ReproductionDecision>
-) {
-    for mut reproduction in query.iter_mut() {
-        reproduction.0.take();
-        reproduction.0.take();
-    }
+#[test]
+fn io_values_are_clamped_to_the_network_range() {
+    assert_eq!(IoF32(2.0).encode(), 1.0);
+    assert_eq!(IoF32(-1.0).encode(), -1.0);
+    assert_eq!(IoF32::decode(3.0), IoF32(1.0));
+    assert_eq!(IoF32::decode(IoF32(0.25).encode()), IoF32(0.25));
+    assert_eq!(IoBool::decode(0.0), IoBool(false));
+    assert_eq!(IoBool::decode(IoBool(true).encode()), IoBool(true));
+}
+
+#[test]
+fn layout_follows_the_config() {
+    let oscillators = Oscillators::default();
+    let mut config = SimConfig::default();
+    let layout = expected_layout(&oscillators, &config);
+    assert_eq!(layout.outputs.len(), layout.smoothed.len());
+    assert_eq!(layout.outputs.last(), Some(&0));
+
+    config.memory_size = 4;
+    config.vision_rays += 1;
+    let grown = expected_layout(&oscillators, &config);
+    assert_eq!(grown.inputs, layout.inputs + 4 + VISION_RAY_INPUTS);
+    assert_eq!(grown.outputs.last(), Some(&4));
 }
@@ -0,0 +1,185 @@
+//! An artificial life simulation of corgis with evolving neural networks.
+//!
+//! Embedders should only depend on [`prelude`].
+//! Everything else is an internal ECS detail and hidden from the docs,
+//! it can change with every release.
+
+#[doc(hidden)]
+pub mod analysis;
+#[doc(hidden)]
+pub mod archive;
+#[doc(hidden)]
+pub mod catastrophe;
+#[doc(hidden)]
+pub mod check;
+#[doc(hidden)]
+pub mod color;
+#[doc(hidden)]
+pub mod config;
+#[doc(hidden)]
+pub mod console;
+#[doc(hidden)]
+pub mod corgi;
+#[doc(hidden)]
+pub mod corpse;
+#[doc(hidden)]
+pub mod debug;
+#[doc(hidden)]
+pub mod disease;
+#[doc(hidden)]
+pub mod export;
+#[doc(hidden)]
+pub mod genes;
+#[doc(hidden)]
+pub mod headless;
+#[doc(hidden)]
+pub mod inspector;
+#[doc(hidden)]
+pub mod intelligence;
+#[doc(hidden)]
+pub mod lineage;
+#[doc(hidden)]
+pub mod loader;
+#[doc(hidden)]
+pub mod metabolism;
+#[doc(hidden)]
+pub mod observer;
+#[doc(hidden)]
+pub mod parasite;
+#[doc(hidden)]
+pub mod pheromone;
+#[doc(hidden)]
+pub mod rng;
+#[doc(hidden)]
+pub mod scent;
+#[doc(hidden)]
+pub mod selection;
+#[doc(hidden)]
+pub mod snapshot;
+#[doc(hidden)]
+pub mod sound;
+#[doc(hidden)]
+pub mod spatial;
+#[doc(hidden)]
+pub mod species;
+#[doc(hidden)]
+pub mod state;
+#[doc(hidden)]
+pub mod stats;
+#[doc(hidden)]
+pub mod territory;
+#[doc(hidden)]
+pub mod universe;
+
+/// The stable public API.
+///
+/// Every version of the prelude is its own module, `v1`, `v2`, ...
+/// Items are never removed from or changed in a released version,
+/// breaking changes go into a new version instead.
+/// The prelude itself re-exports the latest version.
+pub mod prelude {
+    pub mod v1 {
+        pub use crate::{
+            archive::SeedGenomes,
+            intelligence::NeuralNetwork as Genome,
+            selection::{Candidate, Selection, SelectionPolicy},
+            universe::{
+                scenario::Scenario as Config,
+                tile::{TileBehavior, TileBehaviors, TileContext},
+                Tick,
+            },
+            SimulationPlugin as Simulation,
+        };
+    }
+
+    /// The `Genome` is the composite one with all the genes, not only the network.
+    /// Adds the `Snapshot` of the world and the `Observer` of the simulation.
+    pub mod v2 {
+        pub use crate::{
+            archive::SeedGenomes,
+            color::{ColorSemantics, ColorSemanticsKind},
+            config::SimConfig,
+            corpse::DeathCause,
+            genes::{BodyGene, BrainGene, DistanceCoefficients, Genome, MetabolismGene, SenseGene},
+            intelligence::NeuralNetwork,
+            observer::{Observer, Observers},
+            selection::{Candidate, Selection, SelectionPolicy},
+            snapshot::WorldSnapshot as Snapshot,
+            universe::{
+                scenario::Scenario as Config,
+                tile::{TileBehavior, TileBehaviors, TileContext},
+                Tick, Topology, Universe,
+            },
+            SimulationPlugin as Simulation,
+        };
+    }
+
+    pub use v2::*;
+}
+
+use bevy::prelude::*;
+
+/// The whole simulation without the window, rendering and physics plugins.
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(universe::setup_graphics.system())
+            .add_startup_system(universe::setup_physics.system())
+            .add_startup_system(loader::load_assets.system())
+            .add_resource(config::SimConfig::default())
+            .add_resource(universe::Tick::default())
+            .add_resource(universe::Daylight::default())
+            .add_resource(universe::Season::default())
+            .add_resource(universe::Light::default())
+            .add_resource(scent::ScentField::default())
+            .add_resource(archive::SeedGenomes::default())
+            .add_resource(selection::Selection::default())
+            .add_plugin(rng::RngPlugin)
+            .add_plugin(state::StatePlugin)
+            .add_plugin(console::ConsolePlugin)
+            .add_plugin(universe::scenario::ScenarioPlugin)
+            .add_plugin(universe::tile::TilePlugin)
+            .add_plugin(universe::weather::WeatherPlugin)
+            .add_plugin(catastrophe::CatastrophePlugin)
+            .add_plugin(corpse::CorpsePlugin)
+            .add_plugin(observer::ObserverPlugin)
+            .add_plugin(metabolism::MetabolismPlugin)
+            .add_plugin(disease::DiseasePlugin)
+            .add_plugin(parasite::ParasitePlugin)
+            .add_plugin(pheromone::PheromonePlugin)
+            .add_system(universe::advance_tick.system())
+            .add_system(universe::update_daylight.system())
+            .add_system(universe::update_season.system())
+            .add_system(universe::update_light.system())
+            .add_system(universe::wrap_corgis.system())
+            .add_system(universe::enforce_boundary.system())
+            .add_system_to_stage(stage::POST_UPDATE, universe::change_boundary.system())
+            .add_system(scent::emit_scent.system())
+            .add_system(scent::spread_scent.system())
+            .add_system(corgi::corgi_spawner.system())
+            .add_system(corgi::age_corgis.system())
+            .add_system(corgi::fade_damage.system())
+            .add_system(corgi::apply_drag.system())
+            .add_system_to_stage(stage::POST_UPDATE, corgi::corgi_importer.system())
+            .add_system_to_stage(stage::POST_UPDATE, selection::switch_selection.system())
+            .add_plugin(spatial::SpatialPlugin)
+            .add_plugin(sound::SoundPlugin)
+            .add_plugin(intelligence::IntelligencePlugin)
+            .add_plugin(intelligence::decision::reproduction::ReproductionPlugin)
+            .add_plugin(lineage::LineagePlugin)
+            .add_plugin(species::SpeciesPlugin)
+            .add_plugin(territory::TerritoryPlugin)
+            .add_plugin(inspector::InspectorPlugin)
+            .add_plugin(archive::ArchivePlugin)
+            .add_plugin(analysis::AnalysisPlugin)
+            .add_plugin(debug::DebugPlugin)
+            .add_plugin(stats::StatsPlugin)
+            .add_plugin(export::ExportPlugin)
+            .add_plugin(snapshot::SnapshotPlugin);
+    }
+
+    fn name(&self) -> &str {
+        "SimulationPlugin"
+    }
+}
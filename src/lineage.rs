@@ -0,0 +1,294 @@
+use crate::{
+    corgi::Generation,
+    intelligence::{
+        decision::reproduction::{Egg, Pregnancy},
+        Brain, NetworkSummary,
+    },
+    universe::Tick,
+};
+use bevy::prelude::*;
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
+    fmt,
+};
+
+/// Ticks between two prunings of the `LineageLog`.
+const PRUNE_INTERVAL: usize = 1000;
+
+/// Unique for every corgi ever created, handed out by the `LineageLog`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LineageId(pub usize);
+
+impl fmt::Display for LineageId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Lineage {
+    pub id: LineageId,
+    /// Empty for spawned corgis.
+    pub parents: Vec<LineageId>,
+}
+
+impl Lineage {
+    pub fn new_root(log: &mut LineageLog) -> Self {
+        Self {
+            id: log.next_id(),
+            parents: Vec::new(),
+        }
+    }
+
+    pub fn new_child(log: &mut LineageLog, parents: &[&Lineage]) -> Self {
+        Self {
+            id: log.next_id(),
+            parents: parents.iter().map(|parent| parent.id).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LineageEntry {
+    pub parents: Vec<LineageId>,
+    pub children: Vec<LineageId>,
+    pub generation: usize,
+    /// Only known once the brain finished its dry-run.
+    pub genome: Option<NetworkSummary>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct CommonAncestor {
+    pub id: LineageId,
+    /// Generations between the first corgi and the ancestor.
+    pub distance_a: usize,
+    /// Generations between the second corgi and the ancestor.
+    pub distance_b: usize,
+}
+
+/// The living corgis and all their ancestors.
+/// Dead corgis without living descendants are pruned every `PRUNE_INTERVAL` ticks.
+#[derive(Default)]
+pub struct LineageLog {
+    entries: HashMap<LineageId, LineageEntry>,
+    next_id: usize,
+}
+
+impl LineageLog {
+    pub fn get(&self, id: LineageId) -> Option<&LineageEntry> {
+        self.entries.get(&id)
+    }
+
+    /// A new id, never handed out before.
+    pub fn next_id(&mut self) -> LineageId {
+        self.next_id += 1;
+        LineageId(self.next_id - 1)
+    }
+
+    /// Forgets every entry which isn't one of the `living` ids or one of their ancestors.
+    pub fn prune(&mut self, living: &HashSet<LineageId>) {
+        let mut kept = living.clone();
+        let mut queue: VecDeque<LineageId> = living.iter().copied().collect();
+        while let Some(id) = queue.pop_front() {
+            for &parent in self
+                .get(id)
+                .map(|entry| entry.parents.as_slice())
+                .unwrap_or(&[])
+            {
+                if kept.insert(parent) {
+                    queue.push_back(parent);
+                }
+            }
+        }
+        self.entries.retain(|id, _| kept.contains(id));
+        for entry in self.entries.values_mut() {
+            entry.children.retain(|child| kept.contains(child));
+        }
+    }
+
+    /// All ancestors with their distance in generations, starting with `id` itself at distance 0.
+    /// An ancestor reachable over several paths is listed once with its shortest distance.
+    pub fn ancestors(&self, id: LineageId) -> HashMap<LineageId, usize> {
+        self.walk(id, |entry| &entry.parents)
+    }
+
+    /// All descendants with their distance in generations, without `id` itself.
+    pub fn descendants(&self, id: LineageId) -> HashMap<LineageId, usize> {
+        let mut descendants = self.walk(id, |entry| &entry.children);
+        descendants.remove(&id);
+        descendants
+    }
+
+    /// The descendants of `id` which are in `living`, nearest first.
+    pub fn living_descendants(
+        &self,
+        id: LineageId,
+        living: &HashSet<LineageId>,
+    ) -> Vec<(LineageId, usize)> {
+        let mut descendants: Vec<(LineageId, usize)> = self
+            .descendants(id)
+            .into_iter()
+            .filter(|(descendant, _)| living.contains(descendant))
+            .collect();
+        descendants.sort_by_key(|&(descendant, distance)| (distance, descendant));
+        descendants
+    }
+
+    /// Breadth first search along `next`.
+    fn walk<F>(&self, id: LineageId, next: F) -> HashMap<LineageId, usize>
+    where
+        F: Fn(&LineageEntry) -> &Vec<LineageId>,
+    {
+        let mut found = HashMap::new();
+        let mut queue = VecDeque::new();
+        found.insert(id, 0);
+        queue.push_back((id, 0));
+        while let Some((id, distance)) = queue.pop_front() {
+            for &other in self
+                .get(id)
+                .map(|entry| next(entry).as_slice())
+                .unwrap_or(&[])
+            {
+                if let Entry::Vacant(entry) = found.entry(other) {
+                    entry.insert(distance + 1);
+                    queue.push_back((other, distance + 1));
+                }
+            }
+        }
+        found
+    }
+
+    /// The common ancestor with the fewest generations to both corgis.
+    /// A corgi counts as its own ancestor.
+    pub fn most_recent_common_ancestor(
+        &self,
+        a: LineageId,
+        b: LineageId,
+    ) -> Option<CommonAncestor> {
+        let ancestors_a = self.ancestors(a);
+        self.ancestors(b)
+            .into_iter()
+            .filter_map(|(id, distance_b)| {
+                ancestors_a.get(&id).map(|&distance_a| CommonAncestor {
+                    id,
+                    distance_a,
+                    distance_b,
+                })
+            })
+            .min_by_key(|ancestor| (ancestor.distance_a + ancestor.distance_b, ancestor.id))
+    }
+}
+
+pub struct LineagePlugin;
+
+impl Plugin for LineagePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(LineageLog::default())
+            .add_system(log_births.system())
+            .add_system(log_genomes.system())
+            .add_system(prune_lineages.system());
+    }
+
+    fn name(&self) -> &str {
+        "LineagePlugin"
+    }
+}
+
+fn log_births(mut log: ResMut<LineageLog>, query: Query<(&Lineage, &Generation), Added<Lineage>>) {
+    for (lineage, generation) in query.iter() {
+        for parent in &lineage.parents {
+            if let Some(entry) = log.entries.get_mut(parent) {
+                entry.children.push(lineage.id);
+            }
+        }
+        log.entries.insert(
+            lineage.id,
+            LineageEntry {
+                parents: lineage.parents.clone(),
+                children: Vec::new(),
+                generation: generation.0,
+                genome: None,
+            },
+        );
+    }
+}
+
+fn log_genomes(mut log: ResMut<LineageLog>, query: Query<(&Lineage, &Brain), Changed<Brain>>) {
+    for (lineage, brain) in query.iter() {
+        if brain.is_dry() {
+            continue;
+        }
+        if let (Some(entry), Some(network)) = (log.entries.get_mut(&lineage.id), brain.network()) {
+            if entry.genome.is_none() {
+                entry.genome = Some(network.summary());
+            }
+        }
+    }
+}
+
+/// Keeps the ancestors of the living corgis and of the parents of the unborn children.
+fn prune_lineages(
+    tick: Res<Tick>,
+    mut log: ResMut<LineageLog>,
+    lineages: Query<&Lineage>,
+    eggs: Query<&Egg>,
+    pregnancies: Query<&Pregnancy>,
+) {
+    if tick.0 % PRUNE_INTERVAL != 0 {
+        return;
+    }
+    let parents = eggs
+        .iter()
+        .flat_map(|egg| egg.parents.iter())
+        .chain(
+            pregnancies
+                .iter()
+                .flat_map(|pregnancy| pregnancy.parents.iter()),
+        )
+        .map(|(_, lineage)| lineage);
+    // children born this tick may not be logged yet, their parents are kept directly
+    let living: HashSet<LineageId> = lineages
+        .iter()
+        .chain(parents)
+        .flat_map(|lineage| std::iter::once(lineage.id).chain(lineage.parents.iter().copied()))
+        .collect();
+    log.prune(&living);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_child(log: &mut LineageLog, parents: &[LineageId]) -> LineageId {
+        let id = log.next_id();
+        for parent in parents {
+            log.entries.get_mut(parent).unwrap().children.push(id);
+        }
+        log.entries.insert(
+            id,
+            LineageEntry {
+                parents: parents.to_vec(),
+                children: Vec::new(),
+                generation: 0,
+                genome: None,
+            },
+        );
+        id
+    }
+
+    #[test]
+    fn pruning_keeps_the_ancestors_of_the_living() {
+        let mut log = LineageLog::default();
+        let root = log_child(&mut log, &[]);
+        let living = log_child(&mut log, &[root]);
+        let dead = log_child(&mut log, &[root]);
+        let dead_child = log_child(&mut log, &[dead]);
+
+        log.prune(&[living].iter().copied().collect());
+        assert!(log.get(dead).is_none());
+        assert!(log.get(dead_child).is_none());
+        assert_eq!(log.get(root).unwrap().children, vec![living]);
+        assert_eq!(log.ancestors(living).len(), 2);
+        assert_ne!(log.next_id(), dead_child);
+    }
+}
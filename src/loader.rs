@@ -3,12 +3,16 @@ use bevy::prelude::*;
 pub struct MyAssets {
     pub corgi_material: Handle<StandardMaterial>,
     pub corgi_mesh: Handle<Mesh>,
+    pub corpse_material: Handle<ColorMaterial>,
+    pub egg_material: Handle<ColorMaterial>,
+    pub parasite_material: Handle<ColorMaterial>,
 }
 
 pub fn load_assets(
     commands: &mut Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
 ) {
     let mesh = Mesh::from(shape::Quad {
         size: Vec2::new(2.0, 2.0),
@@ -21,6 +25,9 @@ pub fn load_assets(
     let my_assets = MyAssets {
         corgi_mesh: meshes.add(mesh),
         corgi_material: materials.add(material),
+        corpse_material: color_materials.add(Color::rgb(0.4, 0.3, 0.25).into()),
+        egg_material: color_materials.add(Color::rgb(0.95, 0.9, 0.75).into()),
+        parasite_material: color_materials.add(Color::rgb(0.1, 0.05, 0.05).into()),
     };
 
     commands.insert_resource(my_assets);
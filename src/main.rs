@@ -1,23 +1,72 @@
-mod corgi;
-mod intelligence;
-mod loader;
-mod universe;
+mod cli;
 
-use bevy::{pbr::PbrPlugin, prelude::*, render::pass::ClearColor};
+use bevy::{
+    app::ScheduleRunnerPlugin, audio::AudioPlugin, pbr::PbrPlugin, prelude::*,
+    render::pass::ClearColor, wgpu::WgpuPlugin, winit::WinitPlugin,
+};
 use bevy_rapier2d::{physics::RapierPhysicsPlugin, render::RapierRenderPlugin};
+use corgis::{headless::HeadlessPlugin, prelude::*, state::SpeedPlugin};
+use std::process;
 
 fn main() {
-    App::build()
-        .add_plugins(DefaultPlugins)
-        .add_plugin(RapierPhysicsPlugin)
-        .add_plugin(RapierRenderPlugin)
-        .add_plugin(PbrPlugin)
-        .add_resource(Msaa::default())
-        .add_resource(ClearColor(Color::rgb(0.0, 0.0, 0.0)))
-        .add_startup_system(universe::setup_graphics.system())
-        .add_startup_system(universe::setup_physics.system())
-        .add_startup_system(loader::load_assets.system())
-        .add_system(corgi::corgi_spawner.system())
-        .add_plugin(intelligence::IntelligencePlugin)
-        .run();
+    let options = cli::Options::from_args().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+    if let cli::Command::Check(check) = &options.command {
+        process::exit(if corgis::check::run(check) { 0 } else { 1 });
+    }
+    let seeds = match &options.seed_genomes {
+        Some(dir) => SeedGenomes::load(dir).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        }),
+        None => SeedGenomes::default(),
+    };
+    let mut config = match &options.config {
+        Some(path) => SimConfig::load(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        }),
+        None => SimConfig::default(),
+    };
+    if let Some(seed) = options.seed {
+        config.seed = Some(seed);
+    }
+    let problems = config.problems();
+    if !problems.is_empty() {
+        for problem in problems {
+            eprintln!("error: {}", problem);
+        }
+        process::exit(1);
+    }
+
+    let mut app = App::build();
+    match options.headless {
+        Some(run) => {
+            // no window, graphics device or sound, the schedule runner loops without waiting
+            app.add_plugins_with(DefaultPlugins, |group| {
+                group
+                    .disable::<WinitPlugin>()
+                    .disable::<WgpuPlugin>()
+                    .disable::<AudioPlugin>()
+            })
+            .add_plugin(ScheduleRunnerPlugin::default())
+            .add_plugin(RapierPhysicsPlugin)
+            .add_plugin(Simulation)
+            .add_plugin(HeadlessPlugin)
+            .add_resource(run);
+        }
+        None => {
+            app.add_plugins(DefaultPlugins)
+                .add_plugin(RapierPhysicsPlugin)
+                .add_plugin(RapierRenderPlugin)
+                .add_plugin(PbrPlugin)
+                .add_resource(Msaa::default())
+                .add_resource(ClearColor(Color::rgb(0.0, 0.0, 0.0)))
+                .add_plugin(Simulation)
+                .add_plugin(SpeedPlugin);
+        }
+    }
+    app.add_resource(seeds).add_resource(config).run();
 }
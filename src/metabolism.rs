@@ -0,0 +1,335 @@
+use crate::{
+    config::SimConfig,
+    corgi::{
+        self, AdultMass, Age, Corgi, Energy, Exertion, Health, Load, Mass, RecentDamage, Resting,
+        CORGI_BASE_MASS, CORGI_ENERGY_CAPACITY, MAX_HEALTH,
+    },
+    corpse::{self, DeathCause, Died},
+    debug::EnergyLedger,
+    genes::{BodyGene, MetabolismGene, SenseGene, MAX_TEMPERATURE_TOLERANCE},
+    loader::MyAssets,
+    rng::SimRng,
+    state::SimulationState,
+    universe::{
+        tile::{OnTile, TileGrid},
+        Light, Season,
+    },
+};
+use bevy::prelude::*;
+use bevy_rapier2d::{
+    physics::ColliderHandleComponent,
+    rapier::{dynamics::RigidBodySet, geometry::ColliderSet},
+};
+use rand::Rng;
+
+/// Energy a corgi of base mass burns per tick just to stay alive.
+const BASAL_ENERGY: f32 = 0.02;
+/// Energy the full weapon gene costs per tick.
+const WEAPON_UPKEEP: f32 = 0.01;
+/// Energy the full longevity gene costs per tick.
+const LONGEVITY_UPKEEP: f32 = 0.01;
+/// Energy maturing as early as possible costs per tick.
+const EARLY_MATURATION_UPKEEP: f32 = 0.01;
+/// Energy the full immunity costs per tick.
+const IMMUNITY_UPKEEP: f32 = 0.01;
+/// Energy the full toxin resistance costs per tick.
+const TOXIN_RESISTANCE_UPKEEP: f32 = 0.01;
+/// Health lost per tick on a fully toxic tile without any toxin resistance.
+const TOXIN_DAMAGE: f32 = 0.3;
+/// Fraction of the lifespan after which the corgi can die of old age.
+const SENESCENCE_START: f32 = 0.8;
+/// Chance to die per tick just before reaching the lifespan,
+/// it grows quadratically from zero at the start of the senescence.
+const MAX_SENESCENT_DEATH_CHANCE: f32 = 0.002;
+/// Fraction of the basal metabolism left while resting.
+const REST_METABOLISM_FACTOR: f32 = 0.5;
+/// Energy per unit of work done by the movement force.
+const ENERGY_PER_WORK: f32 = 2.5e-8;
+/// Growing corgis only convert energy above this fraction of the capacity into mass.
+const GROWTH_ENERGY_THRESHOLD: f32 = 0.5;
+/// Energy a growing corgi converts into mass per tick at most.
+const GROWTH_ENERGY: f32 = 0.2;
+/// Mass gained per converted unit of energy.
+const MASS_PER_ENERGY: f32 = 2.0;
+/// The collider is replaced once its radius is off by more than this fraction.
+const COLLIDER_RESIZE_TOLERANCE: f32 = 0.02;
+/// Health lost per tick without any energy left.
+const STARVATION_DAMAGE: f32 = 0.5;
+/// Health regenerated per tick.
+const HEALTH_REGENERATION: f32 = 0.05;
+/// Energy per regenerated unit of health.
+const REGENERATION_ENERGY: f32 = 0.5;
+/// Energy lost per tick for every unit of temperature outside of the comfort range.
+const THERMOREGULATION_ENERGY: f32 = 0.5;
+/// Energy the widest comfort range costs per tick.
+const TOLERANCE_UPKEEP: f32 = 0.05;
+
+/// All energy gains and costs which aren't tied to a decision or a tile,
+/// and the death of the corgis running out of energy.
+pub struct MetabolismPlugin;
+
+impl Plugin for MetabolismPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(basal_metabolism.system())
+            .add_system(movement_metabolism.system())
+            .add_system(sense_upkeep.system())
+            .add_system(photosynthesize.system())
+            .add_system(thermoregulate.system())
+            .add_system(grow.system())
+            .add_system(regenerate.system())
+            .add_system(poison.system())
+            // after all costs and damages of the tick
+            .add_system_to_stage(stage::POST_UPDATE, starve.system())
+            .add_system_to_stage(stage::POST_UPDATE, die_of_injuries.system())
+            .add_system_to_stage(stage::POST_UPDATE, die_of_age.system());
+    }
+
+    fn name(&self) -> &str {
+        "MetabolismPlugin"
+    }
+}
+
+/// Heavier corgis and those with a bigger appetite burn more energy,
+/// a long lifespan, an early maturation, immunity, toxin resistance and a weapon
+/// have their own upkeep.
+/// Resting lowers the basal metabolism, but not the upkeep.
+fn basal_metabolism(
+    ledger: Res<EnergyLedger>,
+    mut query: Query<(&Mass, &Resting, &BodyGene, &MetabolismGene, &mut Energy)>,
+) {
+    let mut burned = 0.0;
+    for (mass, resting, body, metabolism, mut energy) in query.iter_mut() {
+        let rest = if resting.0 {
+            REST_METABOLISM_FACTOR
+        } else {
+            1.0
+        };
+        let basal =
+            BASAL_ENERGY * mass.get() / CORGI_BASE_MASS * metabolism.appetite_factor() * rest;
+        let upkeep = metabolism.longevity * LONGEVITY_UPKEEP
+            + (1.0 - metabolism.maturation) * EARLY_MATURATION_UPKEEP
+            + metabolism.immunity * IMMUNITY_UPKEEP
+            + metabolism.toxin_resistance * TOXIN_RESISTANCE_UPKEEP
+            + body.weapon * WEAPON_UPKEEP;
+        burned += energy.sub(basal + upkeep);
+    }
+    ledger.sink(burned);
+}
+
+/// Moving costs energy proportional to the work done, a fast pace is less efficient.
+fn movement_metabolism(
+    ledger: Res<EnergyLedger>,
+    mut query: Query<(&Exertion, &BodyGene, &mut Energy)>,
+) {
+    let mut burned = 0.0;
+    for (exertion, body, mut energy) in query.iter_mut() {
+        burned += energy.sub(ENERGY_PER_WORK * body.pace_cost_factor() * exertion.0);
+    }
+    ledger.sink(burned);
+}
+
+/// Enabled senses cost energy.
+fn sense_upkeep(ledger: Res<EnergyLedger>, mut query: Query<(&SenseGene, &mut Energy)>) {
+    let mut burned = 0.0;
+    for (senses, mut energy) in query.iter_mut() {
+        burned += energy.sub(senses.upkeep());
+    }
+    ledger.sink(burned);
+}
+
+/// Corgis gain energy from light according to their photosynthesis gene.
+fn photosynthesize(
+    config: Res<SimConfig>,
+    light: Res<Light>,
+    ledger: Res<EnergyLedger>,
+    mut query: Query<(&Transform, &MetabolismGene, &mut Energy)>,
+) {
+    let mut gained = 0.0;
+    for (transform, metabolism, mut energy) in query.iter_mut() {
+        let light = light.at(transform.translation.truncate());
+        gained += energy.add(metabolism.photosynthesis * light * config.photosynthesis_energy);
+    }
+    ledger.source(gained);
+}
+
+/// Corgis lose energy keeping warm or cool outside of their comfort range,
+/// a wide comfort range has its own upkeep.
+fn thermoregulate(
+    season: Res<Season>,
+    grid: Res<TileGrid>,
+    ledger: Res<EnergyLedger>,
+    mut query: Query<(&OnTile, &MetabolismGene, &mut Energy)>,
+) {
+    let mut burned = 0.0;
+    for (on_tile, metabolism, mut energy) in query.iter_mut() {
+        let upkeep = metabolism.tolerance / MAX_TEMPERATURE_TOLERANCE * TOLERANCE_UPKEEP;
+        let discomfort = on_tile
+            .0
+            .and_then(|pos| grid.get(pos))
+            .map_or(0.0, |tile| metabolism.discomfort(tile.temperature(&season)));
+        burned += energy.sub(upkeep + discomfort * THERMOREGULATION_ENERGY);
+    }
+    ledger.sink(burned);
+}
+
+/// Corgis below their adult mass convert surplus energy into mass,
+/// their body and collider are scaled to the mass.
+fn grow(
+    ledger: Res<EnergyLedger>,
+    mut bodies: ResMut<RigidBodySet>,
+    mut colliders: ResMut<ColliderSet>,
+    mut query: Query<(
+        (&AdultMass, &BodyGene),
+        &mut Mass,
+        &mut Energy,
+        &mut Transform,
+        &mut ColliderHandleComponent,
+    )>,
+) {
+    for ((adult_mass, body), mut mass, mut energy, mut transform, mut handle) in query.iter_mut() {
+        let surplus = energy.get() - GROWTH_ENERGY_THRESHOLD * CORGI_ENERGY_CAPACITY;
+        let missing = adult_mass.0 - mass.get();
+        if surplus > 0.0 && missing > 0.0 {
+            let converted = energy.sub(surplus.min(GROWTH_ENERGY).min(missing / MASS_PER_ENERGY));
+            *mass = Mass::new(mass.get() + converted * MASS_PER_ENERGY);
+            ledger.sink(converted);
+        }
+        let size = adult_mass.maturity(*mass).sqrt();
+        transform.scale = Vec3::new(size, size, 1.0);
+
+        let half_size = corgi::half_size(body, adult_mass, *mass);
+        let radius = colliders
+            .get(handle.handle())
+            .and_then(|collider| collider.shape().as_ball())
+            .map(|ball| ball.radius);
+        let off = radius.map_or(false, |radius| {
+            (radius - half_size).abs() > COLLIDER_RESIZE_TOLERANCE * half_size
+        });
+        if !off {
+            continue;
+        }
+        // rapier can't resize a collider, it's replaced and the body gets the new mass
+        if let Some(old) = colliders.remove(handle.handle(), &mut *bodies, true) {
+            let collider = corgi::collider(body, adult_mass, *mass)
+                .user_data(old.user_data)
+                .build();
+            let new = colliders.insert(collider, old.parent(), &mut *bodies);
+            *handle = ColliderHandleComponent::from(new);
+        }
+    }
+}
+
+/// Injured corgis heal slowly, if they have the energy for it.
+fn regenerate(ledger: Res<EnergyLedger>, mut query: Query<(&mut Health, &mut Energy)>) {
+    for (mut health, mut energy) in query.iter_mut() {
+        let missing = MAX_HEALTH - health.get();
+        if missing <= 0.0 {
+            continue;
+        }
+        let amount = HEALTH_REGENERATION.min(missing);
+        if energy.spend(amount * REGENERATION_ENERGY) {
+            ledger.sink(amount * REGENERATION_ENERGY);
+            health.heal(amount);
+        }
+    }
+}
+
+/// Toxic tiles damage the corgis on them, unless they resist the toxin.
+fn poison(
+    grid: Res<TileGrid>,
+    mut query: Query<(&OnTile, &MetabolismGene, &mut Health, &mut RecentDamage)>,
+) {
+    for (on_tile, metabolism, mut health, mut damage) in query.iter_mut() {
+        let toxicity = on_tile
+            .0
+            .and_then(|pos| grid.get(pos))
+            .map_or(0.0, |tile| tile.biome.toxicity());
+        let amount = toxicity * (1.0 - metabolism.toxin_resistance) * TOXIN_DAMAGE;
+        if amount > 0.0 {
+            damage.hurt(health.hurt(amount, DeathCause::Poison));
+        }
+    }
+}
+
+/// Corgis without any energy left lose health.
+fn starve(
+    state: Res<SimulationState>,
+    mut query: Query<(&Energy, &mut Health, &mut RecentDamage)>,
+) {
+    // the post update stage keeps running while paused
+    if !state.is_running() {
+        return;
+    }
+    for (energy, mut health, mut damage) in query.iter_mut() {
+        if energy.get() <= 0.0 {
+            damage.hurt(health.hurt(STARVATION_DAMAGE, DeathCause::Starvation));
+        }
+    }
+}
+
+/// Corgis without any health left die of the cause of the latest damage.
+fn die_of_injuries(
+    commands: &mut Commands,
+    assets: Res<MyAssets>,
+    mut deaths: ResMut<Events<Died>>,
+    query: Query<(Entity, &Transform, &Energy, &Load, &Health), With<Corgi>>,
+) {
+    for (entity, transform, energy, load, health) in query.iter() {
+        if !health.is_dead() {
+            continue;
+        }
+        if let Some(cause) = health.cause() {
+            let pos = transform.translation.truncate();
+            let remains = corpse::remains(energy, load);
+            corpse::kill(commands, &assets, &mut deaths, entity, pos, remains, cause);
+        }
+    }
+}
+
+/// Corgis near the end of their lifespan die with a growing chance,
+/// those reaching it die for sure.
+/// Corgis without health left are already killed by `die_of_injuries`.
+fn die_of_age(
+    commands: &mut Commands,
+    assets: Res<MyAssets>,
+    mut deaths: ResMut<Events<Died>>,
+    state: Res<SimulationState>,
+    query: Query<
+        (
+            Entity,
+            &Transform,
+            (&Energy, &Load),
+            &Health,
+            &Age,
+            &MetabolismGene,
+        ),
+        With<Corgi>,
+    >,
+    mut rng: ResMut<SimRng>,
+) {
+    if !state.is_running() {
+        return;
+    }
+    let rng = &mut *rng;
+    for (entity, transform, (energy, load), health, age, metabolism) in query.iter() {
+        if health.is_dead() {
+            continue;
+        }
+        let lifespan = metabolism.lifespan();
+        let progress = age.0 as f32 / lifespan.max(1) as f32;
+        let senescence = ((progress - SENESCENCE_START) / (1.0 - SENESCENCE_START)).max(0.0);
+        let chance = senescence.powi(2) * MAX_SENESCENT_DEATH_CHANCE;
+        if age.0 >= lifespan || rng.gen::<f32>() < chance {
+            let pos = transform.translation.truncate();
+            corpse::kill(
+                commands,
+                &assets,
+                &mut deaths,
+                entity,
+                pos,
+                corpse::remains(energy, load),
+                DeathCause::OldAge,
+            );
+        }
+    }
+}
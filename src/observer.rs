@@ -0,0 +1,63 @@
+use crate::{
+    corgi::Corgi,
+    corpse::{DeathCause, Died},
+    universe::Tick,
+};
+use bevy::prelude::*;
+
+/// Gets told what happens in the simulation, for embedders recording their own statistics.
+/// Every method does nothing by default.
+pub trait Observer: Send + Sync {
+    /// Called once for every simulated tick with the living population.
+    fn tick(&mut self, _tick: usize, _population: usize) {}
+
+    /// Called for every corgi which died.
+    fn died(&mut self, _tick: usize, _pos: Vec2, _cause: DeathCause) {}
+}
+
+/// Holds the registered observers, push one to get notified.
+#[derive(Default)]
+pub struct Observers(pub Vec<Box<dyn Observer>>);
+
+impl Observers {
+    pub fn add<O: Observer + 'static>(&mut self, observer: O) {
+        self.0.push(Box::new(observer));
+    }
+}
+
+pub struct ObserverPlugin;
+
+impl Plugin for ObserverPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(Observers::default())
+            .add_system_to_stage(stage::POST_UPDATE, notify_observers.system());
+    }
+
+    fn name(&self) -> &str {
+        "ObserverPlugin"
+    }
+}
+
+/// The post update stage keeps running while paused, so ticks are only reported once.
+fn notify_observers(
+    tick: Res<Tick>,
+    mut observers: ResMut<Observers>,
+    mut reader: Local<EventReader<Died>>,
+    deaths: Res<Events<Died>>,
+    mut last_tick: Local<Option<usize>>,
+    query: Query<&Corgi>,
+) {
+    for died in reader.iter(&deaths) {
+        for observer in observers.0.iter_mut() {
+            observer.died(tick.0, died.pos, died.cause);
+        }
+    }
+    if *last_tick == Some(tick.0) {
+        return;
+    }
+    *last_tick = Some(tick.0);
+    let population = query.iter().len();
+    for observer in observers.0.iter_mut() {
+        observer.tick(tick.0, population);
+    }
+}
@@ -0,0 +1,275 @@
+use crate::{
+    corgi::Energy,
+    debug::EnergyLedger,
+    intelligence::decision::reproduction::Egg,
+    loader::MyAssets,
+    rng::SimRng,
+    spatial::SpatialGrid,
+    universe::{tile::TileGrid, Tick, Universe},
+};
+use bevy::prelude::*;
+use rand::Rng;
+
+/// Chance per tick that a free parasite appears somewhere in the universe.
+const PARASITE_SPAWN_CHANCE: f64 = 0.02;
+/// Ticks a free parasite survives without finding a host.
+pub const FREE_PARASITE_LIFETIME: usize = 2_000;
+/// Free parasites latch onto corgis this close.
+const LATCH_RANGE: f32 = 15.0;
+/// Fraction of the energy of its host a parasite siphons per tick.
+const SIPHON_FRACTION: f32 = 0.0005;
+/// A parasite which siphoned this much energy splits in two on the same host.
+const PARASITE_SPLIT_ENERGY: f32 = 10.0;
+/// Parasites can jump to corgis and eggs this close to their host.
+const CONTACT_RANGE: f32 = 25.0;
+/// Chance per tick of an attached parasite to jump to a corgi or an egg in contact.
+const JUMP_CHANCE: f32 = 0.002;
+/// Side length of the sprite of a parasite.
+const PARASITE_SIZE: f32 = 3.0;
+
+/// A small parasite, either free on the ground or attached to a corgi or an egg.
+/// Parasites of a despawned host are left free where it was,
+/// so those on a hatching egg latch onto the child.
+/// The siphoned energy of dying and splitting parasites returns to the food of the tile below.
+pub struct Parasite {
+    pub host: Option<Entity>,
+    /// Siphoned energy since the last split.
+    pub energy: f32,
+    /// Tick at which a free parasite dies.
+    pub expires: usize,
+}
+
+/// Number of parasites attached to a corgi.
+#[derive(Default)]
+pub struct Infestation(pub usize);
+
+pub struct ParasitePlugin;
+
+impl Plugin for ParasitePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(spawn_parasites.system())
+            .add_system(latch_parasites.system())
+            .add_system(parasitize.system());
+    }
+
+    fn name(&self) -> &str {
+        "ParasitePlugin"
+    }
+}
+
+pub fn spawn_parasite(commands: &mut Commands, assets: &MyAssets, pos: Vec2, parasite: Parasite) {
+    commands
+        .spawn(SpriteBundle {
+            sprite: Sprite::new(Vec2::splat(PARASITE_SIZE)),
+            material: assets.parasite_material.clone(),
+            transform: Transform::from_translation(pos.extend(1.0)),
+            ..Default::default()
+        })
+        .with(parasite);
+}
+
+/// Occasionally spawns a free parasite at a random position.
+fn spawn_parasites(
+    commands: &mut Commands,
+    tick: Res<Tick>,
+    assets: Res<MyAssets>,
+    universe: Res<Universe>,
+    mut rng: ResMut<SimRng>,
+) {
+    let rng = &mut *rng;
+    if rng.gen_bool(PARASITE_SPAWN_CHANCE) {
+        let pos = Vec2::new(rng.gen(), rng.gen()) * universe.size();
+        let parasite = Parasite {
+            host: None,
+            energy: 0.0,
+            expires: tick.0 + FREE_PARASITE_LIFETIME,
+        };
+        spawn_parasite(commands, &assets, pos, parasite);
+    }
+}
+
+/// Returns the siphoned energy of a parasite to the food of the tile at `pos`,
+/// beyond the capacity of the tile it is lost.
+pub fn release_energy(tiles: &mut TileGrid, ledger: &EnergyLedger, pos: Vec2, energy: f32) {
+    ledger.sink(tiles.fertilize(pos, energy));
+}
+
+/// Free parasites latch onto the nearest corgi in range, or die once they expire.
+fn latch_parasites(
+    commands: &mut Commands,
+    tick: Res<Tick>,
+    grid: Res<SpatialGrid>,
+    ledger: Res<EnergyLedger>,
+    mut tiles: ResMut<TileGrid>,
+    mut query: Query<(Entity, &Transform, &mut Parasite)>,
+) {
+    for (entity, transform, mut parasite) in query.iter_mut() {
+        if parasite.host.is_some() {
+            continue;
+        }
+        let pos = transform.translation.truncate();
+        if tick.0 >= parasite.expires {
+            release_energy(&mut tiles, &ledger, pos, parasite.energy);
+            commands.despawn(entity);
+            continue;
+        }
+        if let Some((host, _)) = grid.nearest(pos, 1, LATCH_RANGE, entity).first() {
+            parasite.host = Some(*host);
+        }
+    }
+}
+
+/// Attached parasites follow their host, siphon its energy, split once they have enough
+/// and jump to corgis and eggs in contact, like the eggs their host lays.
+/// The attached parasites are counted in the `Infestation` of the hosts.
+#[allow(clippy::too_many_arguments)]
+fn parasitize(
+    commands: &mut Commands,
+    tick: Res<Tick>,
+    assets: Res<MyAssets>,
+    universe: Res<Universe>,
+    grid: Res<SpatialGrid>,
+    ledger: Res<EnergyLedger>,
+    mut tiles: ResMut<TileGrid>,
+    mut parasites: Query<(&mut Transform, &mut Parasite)>,
+    mut hosts: Query<
+        (&Transform, Option<&mut Energy>, Option<&mut Infestation>),
+        Without<Parasite>,
+    >,
+    eggs: Query<(Entity, &Transform), With<Egg>>,
+    mut rng: ResMut<SimRng>,
+) {
+    let rng = &mut *rng;
+    for (_, _, infestation) in hosts.iter_mut() {
+        if let Some(mut infestation) = infestation {
+            infestation.0 = 0;
+        }
+    }
+    for (mut transform, mut parasite) in parasites.iter_mut() {
+        let host = match parasite.host {
+            Some(host) => host,
+            None => continue,
+        };
+        let pos = match hosts.get_mut(host) {
+            Ok((host_transform, energy, infestation)) => {
+                if let Some(mut energy) = energy {
+                    let siphoned = energy.get() * SIPHON_FRACTION;
+                    parasite.energy += energy.sub(siphoned);
+                }
+                if let Some(mut infestation) = infestation {
+                    infestation.0 += 1;
+                }
+                host_transform.translation.truncate()
+            }
+            Err(_) => {
+                parasite.host = None;
+                parasite.expires = tick.0 + FREE_PARASITE_LIFETIME;
+                continue;
+            }
+        };
+        transform.translation = pos.extend(1.0);
+
+        if parasite.energy >= PARASITE_SPLIT_ENERGY {
+            release_energy(&mut tiles, &ledger, pos, parasite.energy);
+            parasite.energy = 0.0;
+            let child = Parasite {
+                host: Some(host),
+                energy: 0.0,
+                expires: 0,
+            };
+            spawn_parasite(commands, &assets, pos, child);
+        }
+        if rng.gen::<f32>() < JUMP_CHANCE {
+            let corgi = grid
+                .nearest(pos, 1, CONTACT_RANGE, host)
+                .first()
+                .map(|(other, _)| *other);
+            let in_contact = |other: &Transform| {
+                universe.offset(pos, other.translation.truncate()).length() <= CONTACT_RANGE
+            };
+            let egg = || {
+                eggs.iter()
+                    .find(|(_, egg)| in_contact(egg))
+                    .map(|(egg, _)| egg)
+            };
+            if let Some(other) = corgi.or_else(egg) {
+                parasite.host = Some(other);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::universe::{
+        terrain::Biome,
+        tile::{Tile, TileType},
+    };
+    use bevy::ecs::Stage;
+
+    /// A small universe of grass tiles without food.
+    fn resources() -> Resources {
+        let mut resources = Resources::default();
+        resources.insert(Tick(100));
+        resources.insert(SpatialGrid::default());
+        resources.insert(EnergyLedger::default());
+        let universe = Universe {
+            width_tiles: 4,
+            height_tiles: 4,
+            ..Universe::default()
+        };
+        resources.insert(TileGrid::new(&universe, |pos| {
+            let mut tile = Tile::new(TileType::Grass, pos, Biome::Grassland, 0.0, 1.0);
+            tile.energy = 0.0;
+            tile
+        }));
+        resources
+    }
+
+    fn spawn_free(world: &mut World, pos: Vec2, energy: f32, expires: usize) -> Entity {
+        let parasite = Parasite {
+            host: None,
+            energy,
+            expires,
+        };
+        world.spawn((Transform::from_translation(pos.extend(1.0)), parasite))
+    }
+
+    fn latch(world: &mut World, resources: &mut Resources) {
+        SystemStage::single(latch_parasites.system()).run(world, resources);
+    }
+
+    #[test]
+    fn free_parasites_latch_onto_corgis_in_range() {
+        let mut world = World::default();
+        let mut resources = resources();
+        let corgi = world.spawn((Transform::default(),));
+        let corgi_pos = Vec2::new(30.0, 30.0);
+        resources
+            .get_mut::<SpatialGrid>()
+            .unwrap()
+            .insert(corgi, corgi_pos);
+        let near = spawn_free(&mut world, corgi_pos + Vec2::new(10.0, 0.0), 0.0, 1000);
+        let far = spawn_free(&mut world, corgi_pos + Vec2::new(40.0, 0.0), 0.0, 1000);
+
+        latch(&mut world, &mut resources);
+
+        assert_eq!(world.get::<Parasite>(near).unwrap().host, Some(corgi));
+        assert_eq!(world.get::<Parasite>(far).unwrap().host, None);
+    }
+
+    #[test]
+    fn expired_parasites_return_their_energy_to_the_tile() {
+        let mut world = World::default();
+        let mut resources = resources();
+        let pos = Vec2::new(30.0, 30.0);
+        let parasite = spawn_free(&mut world, pos, 3.0, 100);
+
+        latch(&mut world, &mut resources);
+
+        assert!(!world.contains(parasite));
+        let tiles = resources.get::<TileGrid>().unwrap();
+        assert_eq!(tiles.at(pos).unwrap().energy, 3.0);
+    }
+}
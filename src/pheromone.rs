@@ -0,0 +1,219 @@
+use crate::{
+    console::ConsoleCommand,
+    scent,
+    universe::{Topology, Universe},
+};
+use bevy::prelude::*;
+
+/// Fraction of the pheromone of a tile which flows to its four neighbors every tick.
+const PHEROMONE_DIFFUSION: f32 = 0.1;
+/// Fraction of the pheromone which evaporates every tick.
+const PHEROMONE_DECAY: f32 = 0.02;
+
+/// Pheromone concentration per tile, deposited on purpose by the corgis which decide to,
+/// so the others can follow the trails with their smell.
+/// It spreads and evaporates at its own rates, apart from the body scent in the `ScentField`.
+/// Snapshots keep it, switching the scenario or resizing the universe clears it.
+pub struct PheromoneField {
+    /// The universe the field was created for.
+    universe: Universe,
+    values: Vec<f32>,
+    /// Buffer for the diffusion, to keep the allocation.
+    next: Vec<f32>,
+}
+
+impl Default for PheromoneField {
+    fn default() -> Self {
+        Self::new(&Universe::default())
+    }
+}
+
+impl PheromoneField {
+    /// An empty field with one cell per tile of `universe`.
+    pub fn new(universe: &Universe) -> Self {
+        let tiles = universe.width_tiles * universe.height_tiles;
+        Self {
+            universe: *universe,
+            values: vec![0.0; tiles],
+            next: vec![0.0; tiles],
+        }
+    }
+
+    /// A field for `universe` with the values of a snapshot, row by row.
+    /// `None` if the number of values doesn't match the universe.
+    pub fn from_values(universe: &Universe, values: Vec<f32>) -> Option<Self> {
+        let mut field = Self::new(universe);
+        if values.len() != field.values.len() {
+            return None;
+        }
+        field.values = values;
+        Some(field)
+    }
+
+    /// The concentrations of all tiles row by row.
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        let universe = &self.universe;
+        scent::cell_index(
+            x,
+            y,
+            universe.width_tiles,
+            universe.height_tiles,
+            universe.topology,
+        )
+    }
+
+    fn tile(&self, pos: Vec2) -> (i32, i32) {
+        (
+            (pos.x / self.universe.tile_size).floor() as i32,
+            (pos.y / self.universe.tile_size).floor() as i32,
+        )
+    }
+
+    /// Positions outside of a bounded universe are clamped to the border.
+    pub fn concentration(&self, pos: Vec2) -> f32 {
+        let (x, y) = self.tile(pos);
+        self.values[self.index(x, y)]
+    }
+
+    /// Central difference of the neighboring tiles, pointing towards more pheromone.
+    pub fn gradient(&self, pos: Vec2) -> Vec2 {
+        let (x, y) = self.tile(pos);
+        let value = |x, y| self.values[self.index(x, y)];
+        Vec2::new(
+            value(x + 1, y) - value(x - 1, y),
+            value(x, y + 1) - value(x, y - 1),
+        ) / (2.0 * self.universe.tile_size)
+    }
+
+    /// Adds `amount` to the tile at `pos`.
+    pub fn deposit(&mut self, pos: Vec2, amount: f32) {
+        let (x, y) = self.tile(pos);
+        let index = self.index(x, y);
+        self.values[index] += amount;
+    }
+
+    fn spread(&mut self, diffusion: f32, decay: f32) {
+        scent::diffuse(
+            &mut self.values,
+            &mut self.next,
+            self.universe.width_tiles,
+            self.universe.height_tiles,
+            self.universe.topology,
+            diffusion,
+            decay,
+        );
+    }
+}
+
+pub struct PheromonePlugin;
+
+impl Plugin for PheromonePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(PheromoneField::default())
+            .add_system(spread_pheromones.system())
+            // after the post update stage, which replaces the world
+            .add_system_to_stage(stage::LAST, clear_pheromones.system());
+    }
+
+    fn name(&self) -> &str {
+        "PheromonePlugin"
+    }
+}
+
+fn spread_pheromones(mut field: ResMut<PheromoneField>) {
+    field.spread(PHEROMONE_DIFFUSION, PHEROMONE_DECAY);
+}
+
+/// Starts with a clean field for every new scenario and every changed universe,
+/// like the initial one created at startup. Loaded snapshots restore their own field.
+fn clear_pheromones(
+    mut reader: Local<EventReader<ConsoleCommand>>,
+    console: Res<Events<ConsoleCommand>>,
+    universe: Res<Universe>,
+    mut field: ResMut<PheromoneField>,
+) {
+    let switched = reader
+        .iter(&console)
+        .filter(|command| matches!(command, ConsoleCommand::Scenario(_)));
+    if switched.count() > 0 || field.universe != *universe {
+        *field = PheromoneField::new(&universe);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn universe(topology: Topology) -> Universe {
+        Universe {
+            width_tiles: 5,
+            height_tiles: 4,
+            topology,
+            ..Universe::default()
+        }
+    }
+
+    fn total(field: &PheromoneField) -> f32 {
+        field.values().iter().sum()
+    }
+
+    #[test]
+    fn deposits_land_on_the_tile() {
+        let universe = universe(Topology::Bounded);
+        let mut field = PheromoneField::new(&universe);
+        let pos = Vec2::new(2.5, 1.5) * universe.tile_size;
+        field.deposit(pos, 2.0);
+        field.deposit(pos, 1.0);
+        assert_eq!(field.concentration(pos), 3.0);
+        assert_eq!(field.values()[universe.width_tiles + 2], 3.0);
+        assert_eq!(total(&field), 3.0);
+        // outside of a bounded universe it's the border tile
+        field.deposit(Vec2::new(-100.0, -100.0), 1.0);
+        assert_eq!(field.values()[0], 1.0);
+    }
+
+    #[test]
+    fn pheromones_decay() {
+        let universe = universe(Topology::Bounded);
+        let mut field = PheromoneField::new(&universe);
+        field.deposit(Vec2::new(30.0, 30.0), 10.0);
+        field.spread(0.0, 0.1);
+        assert!((total(&field) - 9.0).abs() < 1e-5);
+        field.spread(0.0, 0.1);
+        assert!((total(&field) - 8.1).abs() < 1e-5);
+    }
+
+    #[test]
+    fn diffusion_conserves_the_pheromones() {
+        for &topology in &[Topology::Bounded, Topology::Toroidal] {
+            let universe = universe(topology);
+            let mut field = PheromoneField::new(&universe);
+            // in a corner, so some of it flows over the edges
+            field.deposit(Vec2::new(1.0, 1.0), 8.0);
+            field.deposit(Vec2::new(50.0, 30.0), 4.0);
+            for _ in 0..20 {
+                field.spread(0.5, 0.0);
+            }
+            assert!((total(&field) - 12.0).abs() < 1e-4, "{:?}", topology);
+        }
+    }
+
+    #[test]
+    fn toroidal_diffusion_wraps_around() {
+        let universe = universe(Topology::Toroidal);
+        let mut field = PheromoneField::new(&universe);
+        field.deposit(Vec2::new(1.0, 1.0), 4.0);
+        field.spread(1.0, 0.0);
+        // the left and bottom neighbors are on the opposite edges
+        let size = universe.size();
+        let left = Vec2::new(size.x - 1.0, 1.0);
+        let below = Vec2::new(1.0, size.y - 1.0);
+        assert_eq!(field.concentration(left), 1.0);
+        assert_eq!(field.concentration(below), 1.0);
+        assert_eq!(field.concentration(Vec2::new(1.0, 1.0)), 0.0);
+    }
+}
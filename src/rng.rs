@@ -0,0 +1,110 @@
+use crate::config::SimConfig;
+use bevy::{app::startup_stage, prelude::*};
+use rand::{rngs::StdRng, Error, Rng, RngCore, SeedableRng};
+
+/// The random number generator of the simulation.
+/// Runs with the same seed, config and scenario make the same random choices.
+pub struct SimRng {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl Default for SimRng {
+    fn default() -> Self {
+        Self::new(rand::random())
+    }
+}
+
+impl SimRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// A new independent stream, seeded with the next number of this one.
+    pub fn stream(&mut self) -> RngStream {
+        RngStream(StdRng::seed_from_u64(self.rng.gen()))
+    }
+}
+
+/// The own random number stream of an entity.
+/// Systems iterating over the entities in parallel use it instead of the `SimRng`,
+/// so the numbers don't depend on the order of the iteration.
+pub struct RngStream(StdRng);
+
+macro_rules! delegate_rng_core {
+    ($type:ty, $field:tt) => {
+        impl RngCore for $type {
+            fn next_u32(&mut self) -> u32 {
+                self.$field.next_u32()
+            }
+
+            fn next_u64(&mut self) -> u64 {
+                self.$field.next_u64()
+            }
+
+            fn fill_bytes(&mut self, dest: &mut [u8]) {
+                self.$field.fill_bytes(dest)
+            }
+
+            fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+                self.$field.try_fill_bytes(dest)
+            }
+        }
+    };
+}
+
+delegate_rng_core!(SimRng, rng);
+delegate_rng_core!(RngStream, 0);
+
+/// Seeds the `SimRng` with the seed of the config, or a random one which gets logged.
+pub struct RngPlugin;
+
+impl Plugin for RngPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(SimRng::default())
+            .add_startup_system_to_stage(startup_stage::PRE_STARTUP, seed_rng.system());
+    }
+
+    fn name(&self) -> &str {
+        "RngPlugin"
+    }
+}
+
+fn seed_rng(config: Res<SimConfig>, mut rng: ResMut<SimRng>) {
+    if let Some(seed) = config.seed {
+        *rng = SimRng::new(seed);
+    }
+    info!("random seed {}", rng.seed());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbers<R: Rng>(rng: &mut R) -> Vec<u64> {
+        (0..10).map(|_| rng.gen()).collect()
+    }
+
+    #[test]
+    fn the_same_seed_makes_the_same_numbers() {
+        let (mut a, mut b) = (SimRng::new(7), SimRng::new(7));
+        assert_eq!(numbers(&mut a), numbers(&mut b));
+        assert_eq!(numbers(&mut a.stream()), numbers(&mut b.stream()));
+        assert_ne!(numbers(&mut a), numbers(&mut SimRng::new(8)));
+    }
+
+    #[test]
+    fn streams_are_independent() {
+        let mut rng = SimRng::new(7);
+        let mut first = rng.stream();
+        let mut second = rng.stream();
+        assert_ne!(numbers(&mut first), numbers(&mut second));
+    }
+}
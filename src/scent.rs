@@ -0,0 +1,208 @@
+use crate::{
+    corgi::Corgi,
+    universe::{Topology, Universe},
+};
+use bevy::prelude::*;
+
+const SCENT_CELL_SIZE: f32 = 10.0;
+/// Fraction of the scent of a cell which flows to its four neighbors every tick.
+const SCENT_DIFFUSION: f32 = 0.2;
+/// Fraction of the scent which evaporates every tick.
+const SCENT_DECAY: f32 = 0.01;
+/// Scent every corgi leaves behind per tick.
+const BODY_SCENT: f32 = 0.05;
+
+/// Scent concentration over the universe, stored in square cells.
+/// Every corgi leaves a faint trail of body scent, which spreads to the neighboring cells
+/// and evaporates. The deliberate signals go into the `PheromoneField` instead.
+pub struct ScentField {
+    cell_size: f32,
+    width: usize,
+    height: usize,
+    topology: Topology,
+    values: Vec<f32>,
+    /// Buffer for the diffusion, to keep the allocation.
+    next: Vec<f32>,
+}
+
+impl Default for ScentField {
+    fn default() -> Self {
+        Self::new(&Universe::default())
+    }
+}
+
+impl ScentField {
+    /// An empty field covering `universe`.
+    pub fn new(universe: &Universe) -> Self {
+        let width = (universe.width() / SCENT_CELL_SIZE).ceil() as usize;
+        let height = (universe.height() / SCENT_CELL_SIZE).ceil() as usize;
+        Self {
+            cell_size: SCENT_CELL_SIZE,
+            width,
+            height,
+            topology: universe.topology,
+            values: vec![0.0; width * height],
+            next: vec![0.0; width * height],
+        }
+    }
+
+    /// A field covering `universe` with the values of a snapshot, row by row.
+    /// `None` if the number of values doesn't match the universe.
+    pub fn from_values(universe: &Universe, values: Vec<f32>) -> Option<Self> {
+        let mut field = Self::new(universe);
+        if values.len() != field.values.len() {
+            return None;
+        }
+        field.values = values;
+        Some(field)
+    }
+
+    /// The concentrations of all cells row by row.
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        cell_index(x, y, self.width, self.height, self.topology)
+    }
+
+    fn cell(&self, pos: Vec2) -> (i32, i32) {
+        (
+            (pos.x / self.cell_size).floor() as i32,
+            (pos.y / self.cell_size).floor() as i32,
+        )
+    }
+
+    /// Positions outside of a bounded universe are clamped to the border.
+    pub fn concentration(&self, pos: Vec2) -> f32 {
+        let (x, y) = self.cell(pos);
+        self.values[self.index(x, y)]
+    }
+
+    /// Central difference of the neighboring cells, pointing towards more scent.
+    pub fn gradient(&self, pos: Vec2) -> Vec2 {
+        let (x, y) = self.cell(pos);
+        let value = |x, y| self.values[self.index(x, y)];
+        Vec2::new(
+            value(x + 1, y) - value(x - 1, y),
+            value(x, y + 1) - value(x, y - 1),
+        ) / (2.0 * self.cell_size)
+    }
+
+    /// Adds `amount` to the cell at `pos`, positions outside of a bounded universe are clamped.
+    pub fn deposit(&mut self, pos: Vec2, amount: f32) {
+        let (x, y) = self.cell(pos);
+        let index = self.index(x, y);
+        self.values[index] += amount;
+    }
+
+    fn spread(&mut self, diffusion: f32, decay: f32) {
+        diffuse(
+            &mut self.values,
+            &mut self.next,
+            self.width,
+            self.height,
+            self.topology,
+            diffusion,
+            decay,
+        );
+    }
+}
+
+/// The index of the cell `x`, `y` of a `width` x `height` grid stored row by row.
+/// Cells outside of the grid wrap around in a toroidal universe and are clamped otherwise.
+pub fn cell_index(x: i32, y: i32, width: usize, height: usize, topology: Topology) -> usize {
+    let (width, height) = (width as i32, height as i32);
+    let (x, y) = match topology {
+        Topology::Bounded => (x.max(0).min(width - 1), y.max(0).min(height - 1)),
+        Topology::Toroidal => (x.rem_euclid(width), y.rem_euclid(height)),
+    };
+    (y * width + x) as usize
+}
+
+/// Spreads a fraction of every cell of a `width` x `height` grid evenly to its four neighbors
+/// and lets the rest decay. `next` is the buffer of the same size.
+/// In a toroidal universe the edges wrap around, in a bounded one flowing over the border
+/// stays in the cell. Either way only the decay removes anything.
+pub fn diffuse(
+    values: &mut [f32],
+    next: &mut [f32],
+    width: usize,
+    height: usize,
+    topology: Topology,
+    diffusion: f32,
+    decay: f32,
+) {
+    let index = |x: i32, y: i32| cell_index(x, y, width, height, topology);
+    for value in next.iter_mut() {
+        *value = 0.0;
+    }
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            let value = values[index(x, y)];
+            let flow = value * diffusion / 4.0;
+            for &(dx, dy) in &[(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                next[index(x + dx, y + dy)] += flow;
+            }
+            next[index(x, y)] += value * (1.0 - diffusion);
+        }
+    }
+    for (value, next) in values.iter_mut().zip(next.iter()) {
+        *value = next * (1.0 - decay);
+    }
+}
+
+pub fn emit_scent(mut scent: ResMut<ScentField>, query: Query<&Transform, With<Corgi>>) {
+    for transform in query.iter() {
+        scent.deposit(transform.translation.truncate(), BODY_SCENT);
+    }
+}
+
+pub fn spread_scent(mut scent: ResMut<ScentField>) {
+    scent.spread(SCENT_DIFFUSION, SCENT_DECAY);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 3x3 grid after one step with all of the value at `(x, y)`.
+    fn diffuse_from(x: i32, y: i32, topology: Topology, decay: f32) -> Vec<f32> {
+        let mut values = vec![0.0; 9];
+        values[cell_index(x, y, 3, 3, topology)] = 1.0;
+        let mut next = vec![0.0; 9];
+        diffuse(&mut values, &mut next, 3, 3, topology, 0.5, decay);
+        values
+    }
+
+    #[test]
+    fn diffusion_spreads_to_the_four_neighbors() {
+        #[rustfmt::skip]
+        let expected: Vec<f32> = vec![
+            0.0, 0.125, 0.0,
+            0.125, 0.5, 0.125,
+            0.0, 0.125, 0.0,
+        ];
+        assert_eq!(diffuse_from(1, 1, Topology::Bounded, 0.0), expected);
+        let decayed: Vec<f32> = expected.iter().map(|value| value * 0.5).collect();
+        assert_eq!(diffuse_from(1, 1, Topology::Toroidal, 0.5), decayed);
+    }
+
+    #[test]
+    fn diffusion_at_the_edges_depends_on_the_topology() {
+        #[rustfmt::skip]
+        let bounded: Vec<f32> = vec![
+            0.75, 0.125, 0.0,
+            0.125, 0.0, 0.0,
+            0.0, 0.0, 0.0,
+        ];
+        assert_eq!(diffuse_from(0, 0, Topology::Bounded, 0.0), bounded);
+        #[rustfmt::skip]
+        let toroidal: Vec<f32> = vec![
+            0.5, 0.125, 0.125,
+            0.125, 0.0, 0.0,
+            0.125, 0.0, 0.0,
+        ];
+        assert_eq!(diffuse_from(0, 0, Topology::Toroidal, 0.0), toroidal);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::console::ConsoleCommand;
+use bevy::prelude::*;
+use rand::{seq::SliceRandom, RngCore};
+
+/// A corgi at the time of reproduction.
+#[derive(Clone, Copy, Debug)]
+pub struct Candidate {
+    pub entity: Entity,
+    pub energy: f32,
+    /// Whether the corgi decided to reproduce and has enough energy for it.
+    pub willing: bool,
+}
+
+impl Candidate {
+    /// The explicit fitness used by the tournament and elitism.
+    pub fn fitness(&self) -> f32 {
+        self.energy
+    }
+}
+
+/// Decides who of the population may reproduce this tick.
+pub trait SelectionPolicy: Send + Sync {
+    /// Gets the whole population and returns the parents.
+    /// Only willing candidates should be returned.
+    fn select(&mut self, population: &[Candidate]) -> Vec<Entity>;
+
+    /// Like `select`, but random policies draw from `rng`, so seeded runs can be reproduced.
+    fn select_with(&mut self, population: &[Candidate], _rng: &mut dyn RngCore) -> Vec<Entity> {
+        self.select(population)
+    }
+}
+
+/// Holds the active selection policy, swap it to change the experiment.
+pub struct Selection(pub Box<dyn SelectionPolicy>);
+
+impl Default for Selection {
+    fn default() -> Self {
+        Self(Box::new(NaturalSelection))
+    }
+}
+
+/// The built-in policies, as chosen on the console.
+#[derive(Clone, Copy, Debug)]
+pub enum PolicyKind {
+    Natural,
+    Tournament(usize),
+    Elitism(f32),
+}
+
+impl PolicyKind {
+    fn build(self) -> Box<dyn SelectionPolicy> {
+        match self {
+            Self::Natural => Box::new(NaturalSelection),
+            Self::Tournament(size) => Box::new(Tournament { size }),
+            Self::Elitism(fraction) => Box::new(Elitism { fraction }),
+        }
+    }
+}
+
+pub fn switch_selection(
+    mut selection: ResMut<Selection>,
+    mut reader: Local<EventReader<ConsoleCommand>>,
+    console: Res<Events<ConsoleCommand>>,
+) {
+    for command in reader.iter(&console) {
+        if let ConsoleCommand::Selection(kind) = command {
+            selection.0 = kind.build();
+            info!("selection policy: {:?}", kind);
+        }
+    }
+}
+
+/// Whoever survives and wants to reproduces.
+pub struct NaturalSelection;
+
+impl SelectionPolicy for NaturalSelection {
+    fn select(&mut self, population: &[Candidate]) -> Vec<Entity> {
+        population
+            .iter()
+            .filter(|c| c.willing)
+            .map(|c| c.entity)
+            .collect()
+    }
+}
+
+/// The willing corgis compete in random groups of `size`,
+/// only the fittest of every group reproduces.
+pub struct Tournament {
+    pub size: usize,
+}
+
+impl SelectionPolicy for Tournament {
+    fn select(&mut self, population: &[Candidate]) -> Vec<Entity> {
+        self.select_with(population, &mut rand::thread_rng())
+    }
+
+    fn select_with(&mut self, population: &[Candidate], rng: &mut dyn RngCore) -> Vec<Entity> {
+        let mut willing: Vec<&Candidate> = population.iter().filter(|c| c.willing).collect();
+        willing.shuffle(rng);
+        willing
+            .chunks(self.size.max(1))
+            .filter_map(|group| {
+                group
+                    .iter()
+                    .max_by(|a, b| a.fitness().partial_cmp(&b.fitness()).unwrap())
+                    .map(|c| c.entity)
+            })
+            .collect()
+    }
+}
+
+/// Only the willing corgis among the fittest `fraction` of the population reproduce.
+pub struct Elitism {
+    pub fraction: f32,
+}
+
+impl SelectionPolicy for Elitism {
+    fn select(&mut self, population: &[Candidate]) -> Vec<Entity> {
+        let mut ranked: Vec<&Candidate> = population.iter().collect();
+        ranked.sort_by(|a, b| b.fitness().partial_cmp(&a.fitness()).unwrap());
+        let elite = (population.len() as f32 * self.fraction).ceil() as usize;
+        ranked
+            .into_iter()
+            .take(elite)
+            .filter(|c| c.willing)
+            .map(|c| c.entity)
+            .collect()
+    }
+}
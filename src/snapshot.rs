@@ -0,0 +1,658 @@
+use crate::{
+    console::ConsoleCommand,
+    corgi::{self, Age, Corgi, Energy, Generation, Health, Load, Mass, MAX_HEALTH},
+    corpse::{self, Corpse},
+    disease::Infection,
+    genes::{Alleles, Environment, Genome, Genotype, SenseGene},
+    intelligence::{
+        decision::reproduction::{self, Egg, Pregnancy},
+        Brain,
+    },
+    lineage::{Lineage, LineageLog},
+    loader::MyAssets,
+    parasite::{self, Parasite, FREE_PARASITE_LIFETIME},
+    pheromone::PheromoneField,
+    rng::SimRng,
+    scent::ScentField,
+    sound::Sounds,
+    spatial::SpatialGrid,
+    universe::{
+        scenario::Scenario,
+        tile::{self, ChunkSprite, Tile, TileBehaviors, TileGrid, TilePos, WallCollider},
+        weather::{Weather, WeatherKind},
+        Boundary, Tick, Topology, Universe, DEFAULT_TILE_SIZE,
+    },
+};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{fs, mem, path::Path};
+
+/// A corgi in a snapshot, it is restored from its genome.
+/// The genome is the expressed one with the network of the brain,
+/// diploid corgis keep both their alleles next to it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CorgiSnapshot {
+    pub genome: Genome,
+    /// Only for diploid scenarios.
+    pub alleles: Option<Alleles>,
+    pub position: (f32, f32),
+    pub energy: f32,
+    /// The carried food.
+    #[serde(default)]
+    pub load: f32,
+    pub generation: usize,
+    #[serde(default)]
+    pub age: usize,
+    #[serde(default = "full_health")]
+    pub health: f32,
+    /// The adult mass if missing.
+    #[serde(default)]
+    pub mass: Option<f32>,
+    #[serde(default)]
+    pub pregnancy: Option<PregnancySnapshot>,
+    #[serde(default)]
+    pub infection: Option<Infection>,
+    /// The conditions it developed in, neutral if missing.
+    #[serde(default)]
+    pub environment: Environment,
+}
+
+fn full_health() -> f32 {
+    MAX_HEALTH
+}
+
+fn default_tile_size() -> f32 {
+    DEFAULT_TILE_SIZE
+}
+
+/// A conceived child in a snapshot, the lineages of the parents start anew like the corgis.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PregnancySnapshot {
+    pub due: usize,
+    pub genome: Genome,
+    pub alleles: Option<Alleles>,
+    pub parent_generations: Vec<usize>,
+}
+
+impl PregnancySnapshot {
+    fn new(pregnancy: &Pregnancy) -> Self {
+        Self {
+            due: pregnancy.due,
+            genome: pregnancy.genome.clone(),
+            alleles: pregnancy.alleles.clone(),
+            parent_generations: pregnancy.parents.iter().map(|(gen, _)| gen.0).collect(),
+        }
+    }
+
+    fn restore(self, log: &mut LineageLog) -> Pregnancy {
+        Pregnancy {
+            due: self.due,
+            genome: self.genome,
+            alleles: self.alleles,
+            parents: restore_parents(&self.parent_generations, log),
+        }
+    }
+}
+
+/// A laid egg in a snapshot.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EggSnapshot {
+    pub position: (f32, f32),
+    pub hatch: usize,
+    pub energy: f32,
+    pub genome: Genome,
+    pub alleles: Option<Alleles>,
+    pub parent_generations: Vec<usize>,
+}
+
+/// A parasite in a snapshot, attached ones are restored free and latch on again.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ParasiteSnapshot {
+    pub position: (f32, f32),
+    pub energy: f32,
+    pub expires: usize,
+}
+
+/// A corpse in a snapshot, its decay only depends on the energy.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CorpseSnapshot {
+    pub position: (f32, f32),
+    pub energy: f32,
+}
+
+/// The current weather in a snapshot, its rng starts anew from the seed of the scenario.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WeatherSnapshot {
+    pub kind: WeatherKind,
+    pub until: usize,
+    pub wind: (f32, f32),
+}
+
+fn restore_parents(generations: &[usize], log: &mut LineageLog) -> Vec<(Generation, Lineage)> {
+    generations
+        .iter()
+        .map(|generation| (Generation(*generation), Lineage::new_root(log)))
+        .collect()
+}
+
+/// The whole world at one tick, saved as RON with the `snapshot save` command.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WorldSnapshot {
+    pub tick: usize,
+    pub scenario: Scenario,
+    pub width_tiles: usize,
+    pub height_tiles: usize,
+    /// The positions are in pixels, so the tiles keep their size.
+    #[serde(default = "default_tile_size")]
+    pub tile_size: f32,
+    pub topology: Topology,
+    #[serde(default)]
+    pub boundary: Boundary,
+    /// Row by row.
+    pub tiles: Vec<Tile>,
+    pub portals: Vec<(TilePos, TilePos)>,
+    /// The cells of the `ScentField` row by row.
+    pub scent: Vec<f32>,
+    /// The tiles of the `PheromoneField` row by row, no pheromones if empty.
+    #[serde(default)]
+    pub pheromones: Vec<f32>,
+    pub corgis: Vec<CorgiSnapshot>,
+    #[serde(default)]
+    pub eggs: Vec<EggSnapshot>,
+    #[serde(default)]
+    pub parasites: Vec<ParasiteSnapshot>,
+    #[serde(default)]
+    pub corpses: Vec<CorpseSnapshot>,
+    /// Clear weather if missing.
+    #[serde(default)]
+    pub weather: Option<WeatherSnapshot>,
+}
+
+impl WorldSnapshot {
+    pub fn universe(&self) -> Universe {
+        Universe {
+            width_tiles: self.width_tiles,
+            height_tiles: self.height_tiles,
+            tile_size: self.tile_size,
+            topology: self.topology,
+            boundary: self.boundary,
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let string = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read `{}`: {}", path.display(), e))?;
+        ron::de::from_str(&string)
+            .map_err(|e| format!("invalid snapshot `{}`: {}", path.display(), e))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let string = ron::ser::to_string(self).map_err(|e| e.to_string())?;
+        fs::write(path, string).map_err(|e| format!("failed to write `{}`: {}", path.display(), e))
+    }
+}
+
+pub struct SnapshotPlugin;
+
+impl Plugin for SnapshotPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_to_stage(stage::POST_UPDATE, save_snapshot.system())
+            .add_system_to_stage(stage::POST_UPDATE, load_snapshot.system());
+    }
+
+    fn name(&self) -> &str {
+        "SnapshotPlugin"
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn save_snapshot(
+    tick: Res<Tick>,
+    scenario: Res<Scenario>,
+    universe: Res<Universe>,
+    grid: Res<TileGrid>,
+    scent: Res<ScentField>,
+    pheromones: Res<PheromoneField>,
+    weather: Res<Weather>,
+    mut reader: Local<EventReader<ConsoleCommand>>,
+    console: Res<Events<ConsoleCommand>>,
+    corgis: Query<(
+        &Transform,
+        (&Energy, &Load, &Health, &Mass, &Age, &Generation),
+        (
+            &Brain,
+            &SenseGene,
+            &Genotype,
+            Option<&Alleles>,
+            &Environment,
+        ),
+        (Option<&Pregnancy>, Option<&Infection>),
+    )>,
+    eggs: Query<(&Transform, &Egg)>,
+    parasites: Query<(&Transform, &Parasite)>,
+    corpses: Query<(&Transform, &Corpse)>,
+) {
+    for command in reader.iter(&console) {
+        let path = match command {
+            ConsoleCommand::SaveSnapshot(path) => path,
+            _ => continue,
+        };
+        let tiles = (0..grid.height())
+            .flat_map(|y| (0..grid.width()).map(move |x| TilePos { x, y }))
+            .filter_map(|pos| grid.get(pos).cloned())
+            .collect();
+        let mut saved_corpses: Vec<CorpseSnapshot> = corpses
+            .iter()
+            .map(|(transform, corpse)| CorpseSnapshot {
+                position: (transform.translation.x, transform.translation.y),
+                energy: corpse.energy,
+            })
+            .collect();
+        let mut saved_corgis = Vec::new();
+        for (transform, vitals, genes, state) in corgis.iter() {
+            let (energy, load, health, mass, age, generation) = vitals;
+            let (brain, senses, genotype, alleles, environment) = genes;
+            let (pregnancy, infection) = state;
+            let position = (transform.translation.x, transform.translation.y);
+            // the dead ones are saved as the corpse they leave at the end of this tick
+            if health.is_dead() {
+                saved_corpses.push(CorpseSnapshot {
+                    position,
+                    energy: corpse::remains(energy, load),
+                });
+                continue;
+            }
+            saved_corgis.push(CorgiSnapshot {
+                genome: Genome::from_parts(brain.gene(), senses, genotype),
+                alleles: alleles.cloned(),
+                position,
+                energy: energy.get(),
+                load: load.0,
+                generation: generation.0,
+                age: age.0,
+                health: health.get(),
+                mass: Some(mass.get()),
+                pregnancy: pregnancy.map(PregnancySnapshot::new),
+                infection: infection.copied(),
+                environment: *environment,
+            });
+        }
+        let eggs = eggs
+            .iter()
+            .map(|(transform, egg)| EggSnapshot {
+                position: (transform.translation.x, transform.translation.y),
+                hatch: egg.hatch,
+                energy: egg.energy,
+                genome: egg.genome.clone(),
+                alleles: egg.alleles.clone(),
+                parent_generations: egg.parents.iter().map(|(gen, _)| gen.0).collect(),
+            })
+            .collect();
+        let parasites = parasites
+            .iter()
+            .map(|(transform, parasite)| ParasiteSnapshot {
+                position: (transform.translation.x, transform.translation.y),
+                energy: parasite.energy,
+                expires: match parasite.host {
+                    Some(_) => tick.0 + FREE_PARASITE_LIFETIME,
+                    None => parasite.expires,
+                },
+            })
+            .collect();
+        let snapshot = WorldSnapshot {
+            tick: tick.0,
+            scenario: scenario.clone(),
+            width_tiles: universe.width_tiles,
+            height_tiles: universe.height_tiles,
+            tile_size: universe.tile_size,
+            topology: universe.topology,
+            boundary: universe.boundary,
+            tiles,
+            portals: grid.portals().to_vec(),
+            scent: scent.values().to_vec(),
+            pheromones: pheromones.values().to_vec(),
+            corgis: saved_corgis,
+            eggs,
+            parasites,
+            corpses: saved_corpses,
+            weather: Some(WeatherSnapshot {
+                kind: weather.kind,
+                until: weather.until,
+                wind: (weather.wind.x, weather.wind.y),
+            }),
+        };
+        match snapshot.save(path) {
+            Ok(()) => info!("snapshot of tick {} saved to `{}`", tick.0, path.display()),
+            Err(e) => error!("{}", e),
+        }
+    }
+}
+
+/// Replaces the world with a snapshot, like a scenario switch.
+/// Snapshots with problems in their scenario are rejected before anything is torn down.
+#[allow(clippy::too_many_arguments)]
+fn load_snapshot(
+    commands: &mut Commands,
+    mut reader: Local<EventReader<ConsoleCommand>>,
+    console: Res<Events<ConsoleCommand>>,
+    assets: Res<MyAssets>,
+    mut tick: ResMut<Tick>,
+    mut scenario: ResMut<Scenario>,
+    mut universe: ResMut<Universe>,
+    (mut scent, mut pheromones, mut weather, mut log): (
+        ResMut<ScentField>,
+        ResMut<PheromoneField>,
+        ResMut<Weather>,
+        ResMut<LineageLog>,
+    ),
+    mut spatial: ResMut<SpatialGrid>,
+    mut sounds: ResMut<Sounds>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut textures: ResMut<Assets<Texture>>,
+    mut tile_grid: ResMut<TileGrid>,
+    mut behaviors: ResMut<TileBehaviors>,
+    mut rng: ResMut<SimRng>,
+    existing: Query<
+        Entity,
+        Or<(
+            With<Corgi>,
+            With<Corpse>,
+            With<Egg>,
+            With<Parasite>,
+            With<ChunkSprite>,
+            With<WallCollider>,
+        )>,
+    >,
+) {
+    let path = match reader.iter(&console).find_map(|command| match command {
+        ConsoleCommand::LoadSnapshot(path) => Some(path),
+        _ => None,
+    }) {
+        Some(path) => path,
+        None => return,
+    };
+    let mut snapshot = match WorldSnapshot::load(path) {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    let problems = snapshot.scenario.problems();
+    if !problems.is_empty() {
+        for problem in problems {
+            error!("scenario of `{}`: {}", path.display(), problem);
+        }
+        return;
+    }
+    let new_universe = snapshot.universe();
+    let mut grid = match TileGrid::from_tiles(&new_universe, &snapshot.tiles) {
+        Some(grid) => grid,
+        None => {
+            error!(
+                "the tiles of `{}` don't fit its {}x{} universe",
+                path.display(),
+                new_universe.width_tiles,
+                new_universe.height_tiles
+            );
+            return;
+        }
+    };
+    let new_scent = match ScentField::from_values(&new_universe, mem::take(&mut snapshot.scent)) {
+        Some(scent) => scent,
+        None => {
+            error!(
+                "the scent field of `{}` doesn't fit the universe",
+                path.display()
+            );
+            return;
+        }
+    };
+    let new_pheromones = if snapshot.pheromones.is_empty() {
+        Some(PheromoneField::new(&new_universe))
+    } else {
+        PheromoneField::from_values(&new_universe, mem::take(&mut snapshot.pheromones))
+    };
+    let new_pheromones = match new_pheromones {
+        Some(pheromones) => pheromones,
+        None => {
+            error!(
+                "the pheromone field of `{}` doesn't fit the universe",
+                path.display()
+            );
+            return;
+        }
+    };
+    grid.set_portals(mem::take(&mut snapshot.portals));
+
+    for entity in existing.iter() {
+        commands.despawn(entity);
+    }
+    tick.0 = snapshot.tick;
+    *universe = new_universe;
+    *scent = new_scent;
+    *pheromones = new_pheromones;
+    *weather = Weather::new(snapshot.scenario.weather_seed, &mut rng);
+    if let Some(saved) = &snapshot.weather {
+        weather.kind = saved.kind;
+        weather.until = saved.until;
+        weather.wind = Vec2::new(saved.wind.0, saved.wind.1);
+    }
+    *scenario = snapshot.scenario.clone();
+    *spatial = SpatialGrid::default();
+    *sounds = Sounds::default();
+    *tile_grid = grid;
+    tile::spawn_tile_entities(
+        commands,
+        &mut materials,
+        &mut textures,
+        &tile_grid,
+        &mut behaviors,
+    );
+
+    let count = snapshot.corgis.len();
+    spawn_entities(commands, snapshot, &assets, &mut log, &mut rng);
+    info!(
+        "loaded tick {} with {} corgis from `{}`",
+        tick.0,
+        count,
+        path.display()
+    );
+}
+
+/// Spawns the corgis, eggs, parasites and corpses of `snapshot`.
+fn spawn_entities(
+    commands: &mut Commands,
+    snapshot: WorldSnapshot,
+    assets: &MyAssets,
+    log: &mut LineageLog,
+    rng: &mut SimRng,
+) {
+    for corgi in snapshot.corgis {
+        let pos = Vec2::new(corgi.position.0, corgi.position.1);
+        let (energy, generation) = (corgi.energy, corgi.generation);
+        let mass = corgi.mass;
+        let mut alleles = corgi.alleles;
+        if let (Some(alleles), Some(network)) = (&mut alleles, &corgi.genome.brain.network) {
+            alleles.adopt_network(network);
+        }
+        let entity = corgi::spawn_restored(
+            commands,
+            corgi.genome,
+            &corgi.environment,
+            pos,
+            energy,
+            generation,
+            assets,
+            log,
+            rng,
+        );
+        commands.insert(
+            entity,
+            (Age(corgi.age), Health::new(corgi.health), Load(corgi.load)),
+        );
+        if let Some(mass) = mass {
+            commands.insert_one(entity, Mass::new(mass));
+        }
+        if let Some(alleles) = alleles {
+            commands.insert_one(entity, alleles);
+        }
+        if let Some(pregnancy) = corgi.pregnancy {
+            commands.insert_one(entity, pregnancy.restore(log));
+        }
+        if let Some(infection) = corgi.infection {
+            commands.insert_one(entity, infection);
+        }
+    }
+    for egg in snapshot.eggs {
+        let pos = Vec2::new(egg.position.0, egg.position.1);
+        let restored = Egg {
+            hatch: egg.hatch,
+            energy: egg.energy,
+            genome: egg.genome,
+            alleles: egg.alleles,
+            parents: restore_parents(&egg.parent_generations, log),
+        };
+        reproduction::spawn_egg(commands, assets, pos, restored);
+    }
+    for saved in snapshot.parasites {
+        let pos = Vec2::new(saved.position.0, saved.position.1);
+        let restored = Parasite {
+            host: None,
+            energy: saved.energy,
+            expires: saved.expires,
+        };
+        parasite::spawn_parasite(commands, assets, pos, restored);
+    }
+    for corpse in snapshot.corpses {
+        let pos = Vec2::new(corpse.position.0, corpse.position.1);
+        corpse::spawn_corpse(commands, assets, pos, corpse.energy);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::universe::{terrain::Biome, tile::TileType};
+    use bevy::ecs::Stage;
+
+    fn assets() -> MyAssets {
+        MyAssets {
+            corgi_material: Handle::default(),
+            corgi_mesh: Handle::default(),
+            corpse_material: Handle::default(),
+            egg_material: Handle::default(),
+            parasite_material: Handle::default(),
+        }
+    }
+
+    /// Everything holding energy: tiles, corgis with their load, eggs, parasites and corpses.
+    fn total_energy(world: &World, grid: &TileGrid) -> f64 {
+        let tiles: f64 = grid.iter().map(|tile| tile.energy as f64).sum();
+        let corgis: f64 = world
+            .query::<(&Energy, &Load)>()
+            .map(|(energy, load)| (energy.get() + load.0) as f64)
+            .sum();
+        let eggs: f64 = world.query::<&Egg>().map(|egg| egg.energy as f64).sum();
+        let parasites: f64 = world
+            .query::<&Parasite>()
+            .map(|parasite| parasite.energy as f64)
+            .sum();
+        let corpses: f64 = world
+            .query::<&Corpse>()
+            .map(|corpse| corpse.energy as f64)
+            .sum();
+        tiles + corgis + eggs + parasites + corpses
+    }
+
+    #[test]
+    fn snapshots_keep_the_energy() {
+        let path = std::env::temp_dir().join("corgis-snapshot-round-trip.ron");
+        let assets = assets();
+        let mut rng = SimRng::new(0);
+        let universe = Universe {
+            width_tiles: 4,
+            height_tiles: 4,
+            ..Universe::default()
+        };
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        resources.insert(Tick(100));
+        resources.insert(Scenario::default());
+        resources.insert(universe);
+        resources.insert(TileGrid::new(&universe, |pos| {
+            let mut tile = Tile::new(TileType::Grass, pos, Biome::Grassland, 0.0, 1.0);
+            tile.energy = pos.x as f32;
+            tile
+        }));
+        resources.insert(ScentField::new(&universe));
+        let mut pheromones = PheromoneField::new(&universe);
+        pheromones.deposit(Vec2::new(30.0, 30.0), 2.0);
+        resources.insert(pheromones);
+        resources.insert(Weather::default());
+        let mut console = Events::<ConsoleCommand>::default();
+        console.send(ConsoleCommand::SaveSnapshot(path.clone()));
+        resources.insert(console);
+
+        let environment = Environment {
+            light: 0.9,
+            crowding: 0.1,
+        };
+        let mut log = LineageLog::default();
+        let mut commands = Commands::default();
+        commands.set_entity_reserver(world.get_entity_reserver());
+        let genome = Genome::new_random(&mut rng);
+        let pos = Vec2::new(30.0, 30.0);
+        let corgi = corgi::spawn_restored(
+            &mut commands,
+            genome,
+            &environment,
+            pos,
+            40.0,
+            3,
+            &assets,
+            &mut log,
+            &mut rng,
+        );
+        commands.insert(corgi, (Load(2.5), Infection { virulence: 0.4 }));
+        corpse::spawn_corpse(&mut commands, &assets, Vec2::new(10.0, 50.0), 25.0);
+        let parasite = Parasite {
+            host: None,
+            energy: 1.5,
+            expires: 500,
+        };
+        parasite::spawn_parasite(&mut commands, &assets, Vec2::new(70.0, 10.0), parasite);
+        let egg = Egg {
+            hatch: 300,
+            energy: 12.0,
+            genome: Genome::new_random(&mut rng),
+            alleles: None,
+            parents: restore_parents(&[3], &mut log),
+        };
+        reproduction::spawn_egg(&mut commands, &assets, Vec2::new(50.0, 70.0), egg);
+        commands.apply(&mut world, &mut resources);
+        let saved_energy = total_energy(&world, &resources.get::<TileGrid>().unwrap());
+
+        SystemStage::single(save_snapshot.system()).run(&mut world, &mut resources);
+        let snapshot = WorldSnapshot::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let grid = TileGrid::from_tiles(&snapshot.universe(), &snapshot.tiles).unwrap();
+        let pheromones =
+            PheromoneField::from_values(&snapshot.universe(), snapshot.pheromones.clone()).unwrap();
+        assert_eq!(pheromones.concentration(Vec2::new(30.0, 30.0)), 2.0);
+        let mut restored = World::default();
+        let mut commands = Commands::default();
+        commands.set_entity_reserver(restored.get_entity_reserver());
+        spawn_entities(&mut commands, snapshot, &assets, &mut log, &mut rng);
+        commands.apply(&mut restored, &mut Resources::default());
+
+        assert!((total_energy(&restored, &grid) - saved_energy).abs() < 1e-3);
+        let infections: Vec<Infection> = restored.query::<&Infection>().copied().collect();
+        assert_eq!(infections, vec![Infection { virulence: 0.4 }]);
+        let environments: Vec<Environment> = restored.query::<&Environment>().copied().collect();
+        assert_eq!(environments, vec![environment]);
+    }
+}
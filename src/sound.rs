@@ -0,0 +1,86 @@
+use crate::universe::Universe;
+use bevy::prelude::*;
+
+/// Fraction of the loudness left after one tick.
+const SOUND_DECAY: f32 = 0.9;
+/// Quieter sounds are removed.
+const SOUND_MIN_LOUDNESS: f32 = 0.01;
+/// Sounds can't be heard further away.
+pub const SOUND_RANGE: f32 = 200.0;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Sound {
+    pub emitter: Entity,
+    pub pos: Vec2,
+    pub loudness: f32,
+}
+
+/// All sounds currently audible in the universe.
+#[derive(Default)]
+pub struct Sounds {
+    sounds: Vec<Sound>,
+}
+
+impl Sounds {
+    pub fn emit(&mut self, emitter: Entity, pos: Vec2, loudness: f32) {
+        self.sounds.push(Sound {
+            emitter,
+            pos,
+            loudness,
+        });
+    }
+
+    /// The loudness of the sounds at `pos`, attenuated linearly with the distance,
+    /// and the direction they come from. Sounds of `listener` are ignored.
+    pub fn hear(&self, listener: Entity, pos: Vec2, universe: &Universe) -> (f32, Vec2) {
+        let (loudness, direction) = self
+            .sounds
+            .iter()
+            .filter(|sound| sound.emitter != listener)
+            .filter_map(|sound| {
+                let offset = universe.offset(pos, sound.pos);
+                let distance = offset.length();
+                if distance > SOUND_RANGE {
+                    return None;
+                }
+                let loudness = sound.loudness * (1.0 - distance / SOUND_RANGE);
+                let direction = if distance > 0.0 {
+                    offset / distance
+                } else {
+                    Vec2::zero()
+                };
+                Some((loudness, direction * loudness))
+            })
+            .fold((0.0, Vec2::zero()), |(l, d), (loudness, direction)| {
+                (l + loudness, d + direction)
+            });
+        let direction = if direction.length() > 0.0 {
+            direction.normalize()
+        } else {
+            direction
+        };
+        (loudness, direction)
+    }
+}
+
+pub struct SoundPlugin;
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(Sounds::default())
+            .add_system(decay_sounds.system());
+    }
+
+    fn name(&self) -> &str {
+        "SoundPlugin"
+    }
+}
+
+fn decay_sounds(mut sounds: ResMut<Sounds>) {
+    for sound in sounds.sounds.iter_mut() {
+        sound.loudness *= SOUND_DECAY;
+    }
+    sounds
+        .sounds
+        .retain(|sound| sound.loudness >= SOUND_MIN_LOUDNESS);
+}
@@ -0,0 +1,182 @@
+use crate::{
+    corgi::Corgi,
+    universe::{Topology, Universe},
+};
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+const GRID_CELL_SIZE: f32 = 50.0;
+
+/// Buckets the corgis by position for fast neighborhood queries.
+/// Rebuilt every frame before the perceive stage.
+/// In a toroidal universe the queries look across the edges.
+pub struct SpatialGrid {
+    cell_size: f32,
+    universe: Universe,
+    cells: HashMap<(i32, i32), Vec<(Entity, Vec2)>>,
+}
+
+impl Default for SpatialGrid {
+    fn default() -> Self {
+        Self {
+            cell_size: GRID_CELL_SIZE,
+            universe: Universe::default(),
+            cells: HashMap::new(),
+        }
+    }
+}
+
+impl SpatialGrid {
+    fn cell(&self, pos: Vec2) -> (i32, i32) {
+        (
+            (pos.x / self.cell_size).floor() as i32,
+            (pos.y / self.cell_size).floor() as i32,
+        )
+    }
+
+    /// The universe the grid was last built for.
+    pub fn universe(&self) -> &Universe {
+        &self.universe
+    }
+
+    /// The cells from `min` to `max` along one axis, wrapped around `count` cells if toroidal.
+    fn axis_cells(&self, min: i32, max: i32, count: i32) -> Vec<i32> {
+        match self.universe.topology {
+            Topology::Bounded => (min..=max).collect(),
+            Topology::Toroidal if max - min + 1 >= count => (0..count).collect(),
+            Topology::Toroidal => (min..=max).map(|cell| cell.rem_euclid(count)).collect(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for cell in self.cells.values_mut() {
+            cell.clear();
+        }
+    }
+
+    pub fn insert(&mut self, entity: Entity, pos: Vec2) {
+        let pos = self.universe.wrap(pos);
+        let cell = self.cell(pos);
+        self.cells.entry(cell).or_default().push((entity, pos));
+    }
+
+    /// Every entry of the grid, unordered.
+    pub fn entries(&self) -> impl Iterator<Item = (Entity, Vec2)> + '_ {
+        self.cells.values().flatten().copied()
+    }
+
+    /// All entities within `radius` of `pos`, unordered.
+    /// In a toroidal universe the positions are moved across the edges next to `pos`,
+    /// so the offsets to them are the shortest ones.
+    pub fn in_radius(&self, pos: Vec2, radius: f32) -> impl Iterator<Item = (Entity, Vec2)> + '_ {
+        let (min_x, min_y) = self.cell(pos - Vec2::splat(radius));
+        let (max_x, max_y) = self.cell(pos + Vec2::splat(radius));
+        let columns = (self.universe.width() / self.cell_size).ceil() as i32;
+        let rows = (self.universe.height() / self.cell_size).ceil() as i32;
+        let xs = self.axis_cells(min_x, max_x, columns);
+        let ys = self.axis_cells(min_y, max_y, rows);
+        let universe = self.universe;
+        xs.into_iter()
+            .flat_map(move |x| ys.clone().into_iter().map(move |y| (x, y)))
+            .filter_map(move |cell| self.cells.get(&cell))
+            .flatten()
+            .map(move |(entity, other)| (*entity, pos + universe.offset(pos, *other)))
+            .filter(move |(_, other)| (*other - pos).length() <= radius)
+    }
+
+    /// The `k` nearest entities within `radius`, nearest first, without `exclude`.
+    pub fn nearest(
+        &self,
+        pos: Vec2,
+        k: usize,
+        radius: f32,
+        exclude: Entity,
+    ) -> Vec<(Entity, Vec2)> {
+        let mut found: Vec<(Entity, Vec2)> = self
+            .in_radius(pos, radius)
+            .filter(|(entity, _)| *entity != exclude)
+            .collect();
+        found.sort_by(|(_, a), (_, b)| {
+            (*a - pos)
+                .length()
+                .partial_cmp(&(*b - pos).length())
+                .unwrap()
+        });
+        found.truncate(k);
+        found
+    }
+}
+
+pub struct SpatialPlugin;
+
+impl Plugin for SpatialPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(SpatialGrid::default())
+            .add_system_to_stage(stage::POST_UPDATE, update_spatial_grid.system());
+    }
+
+    fn name(&self) -> &str {
+        "SpatialPlugin"
+    }
+}
+
+fn update_spatial_grid(
+    universe: Res<Universe>,
+    mut grid: ResMut<SpatialGrid>,
+    query: Query<(Entity, &Transform), With<Corgi>>,
+) {
+    if grid.universe != *universe {
+        grid.cells.clear();
+        grid.universe = *universe;
+    }
+    grid.clear();
+    for (entity, transform) in query.iter() {
+        grid.insert(entity, transform.translation.truncate());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::universe::Boundary;
+
+    fn grid(topology: Topology) -> SpatialGrid {
+        SpatialGrid {
+            universe: Universe {
+                width_tiles: 10,
+                height_tiles: 10,
+                tile_size: 20.0,
+                topology,
+                boundary: Boundary::Bounce,
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn toroidal_queries_look_across_the_edges() {
+        let (a, b) = (Entity::new(0), Entity::new(1));
+        let mut grid = grid(Topology::Toroidal);
+        grid.insert(a, Vec2::new(5.0, 100.0));
+        grid.insert(b, Vec2::new(195.0, 100.0));
+        // positions outside are wrapped into the universe
+        grid.insert(Entity::new(2), Vec2::new(300.0, 100.0));
+
+        let nearest = grid.nearest(Vec2::new(5.0, 100.0), 3, 20.0, a);
+        assert_eq!(nearest, vec![(b, Vec2::new(-5.0, 100.0))]);
+        let found: Vec<Entity> = grid
+            .in_radius(Vec2::new(100.0, 100.0), 1.0)
+            .map(|(entity, _)| entity)
+            .collect();
+        assert_eq!(found, vec![Entity::new(2)]);
+    }
+
+    #[test]
+    fn bounded_queries_stop_at_the_edges() {
+        let (a, b) = (Entity::new(0), Entity::new(1));
+        let mut grid = grid(Topology::Bounded);
+        grid.insert(a, Vec2::new(5.0, 100.0));
+        grid.insert(b, Vec2::new(195.0, 100.0));
+        assert!(grid.nearest(Vec2::new(5.0, 100.0), 3, 20.0, a).is_empty());
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{
+    console::ConsoleCommand,
+    genes::{Genome, Genotype, SenseGene, SPECIES_HASH_BITS},
+    intelligence::Brain,
+};
+use bevy::prelude::*;
+
+/// The approximate species of a corgi, see `Genome::species_hash`.
+/// Assigned once the network of the corgi exists.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Species(pub u64);
+
+impl Species {
+    /// A distinct color for every species.
+    pub fn color(self) -> Color {
+        // spread the hash bits over the channels
+        let mixed = self.0.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> (64 - 3 * 5);
+        let channel = |shift: u64| 0.2 + 0.8 * ((mixed >> shift) & 0x1f) as f32 / 31.0;
+        Color::rgb_linear(channel(0), channel(5), channel(10))
+    }
+}
+
+/// Whether the corgis are rendered in the color of their species instead of their displayed color.
+/// Only the rendering changes, the other corgis still perceive the displayed color.
+#[derive(Default)]
+pub struct SpeciesColors(pub bool);
+
+pub struct SpeciesPlugin;
+
+impl Plugin for SpeciesPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(SpeciesColors::default())
+            .add_system(assign_species.system())
+            .add_system_to_stage(stage::POST_UPDATE, toggle_species_colors.system())
+            // after the displayed color was applied in the decide stage
+            .add_system_to_stage("transition", color_species.system());
+    }
+
+    fn name(&self) -> &str {
+        "SpeciesPlugin"
+    }
+}
+
+fn assign_species(
+    commands: &mut Commands,
+    query: Query<(Entity, &Brain, &SenseGene, &Genotype), Without<Species>>,
+) {
+    for (entity, brain, senses, genotype) in query.iter() {
+        if brain.is_dry() {
+            continue;
+        }
+        let genome = Genome::from_parts(brain.gene(), senses, genotype);
+        commands.insert_one(entity, Species(genome.species_hash()));
+    }
+}
+
+fn toggle_species_colors(
+    mut colors: ResMut<SpeciesColors>,
+    mut reader: Local<EventReader<ConsoleCommand>>,
+    console: Res<Events<ConsoleCommand>>,
+) {
+    for command in reader.iter(&console) {
+        if let ConsoleCommand::SpeciesColors(enabled) = command {
+            colors.0 = *enabled;
+            info!(
+                "species colors {}, {} bit hashes",
+                if *enabled { "on" } else { "off" },
+                SPECIES_HASH_BITS
+            );
+        }
+    }
+}
+
+fn color_species(
+    colors: Res<SpeciesColors>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    query: Query<(&Species, &Handle<StandardMaterial>)>,
+) {
+    if !colors.0 {
+        return;
+    }
+    for (species, material) in query.iter() {
+        if let Some(material) = materials.get_mut(material) {
+            material.albedo = species.color();
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use crate::config::SimConfig;
+use bevy::{ecs::ShouldRun, prelude::*};
+use bevy_rapier2d::physics::RapierConfiguration;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Pauses and resumes the simulation.
+const PAUSE_KEY: KeyCode = KeyCode::Space;
+/// Select the speed 1x, 4x, 16x and max.
+const SPEED_KEYS: [(KeyCode, SimulationSpeed); 4] = [
+    (KeyCode::Key1, SimulationSpeed::Times(1)),
+    (KeyCode::Key2, SimulationSpeed::Times(4)),
+    (KeyCode::Key3, SimulationSpeed::Times(16)),
+    (KeyCode::Key4, SimulationSpeed::Max),
+];
+/// Time between two rendered frames at the max speed.
+const MAX_SPEED_FRAME_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Whether the simulation advances.
+/// While paused, the gameplay stages and the physics stand still,
+/// but the world is still rendered and can be inspected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimulationState {
+    Running,
+    Paused,
+}
+
+impl Default for SimulationState {
+    fn default() -> Self {
+        Self::Running
+    }
+}
+
+impl SimulationState {
+    pub fn is_running(self) -> bool {
+        self == Self::Running
+    }
+}
+
+/// How many ticks are simulated per rendered frame.
+/// Every tick runs the whole schedule, only the render stage is skipped for all but the last one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SimulationSpeed {
+    Times(usize),
+    /// Renders only a few frames per second, the simulation runs as fast as it can.
+    Max,
+}
+
+impl Default for SimulationSpeed {
+    fn default() -> Self {
+        Self::Times(1)
+    }
+}
+
+/// Adds the `SimulationState` and skips the update stage while paused.
+/// Gameplay systems in other stages use the `running` run criteria or check the state themselves,
+/// systems which have to keep running while paused go into the post update stage,
+/// like the console and everything handling its commands.
+pub struct StatePlugin;
+
+impl Plugin for StatePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(SimulationState::default())
+            .stage(stage::UPDATE, |stage: &mut SystemStage| {
+                stage.set_run_criteria(running.system())
+            })
+            .add_system_to_stage(stage::PRE_UPDATE, toggle_pause.system())
+            .add_system_to_stage(stage::PRE_UPDATE, pause_physics.system());
+    }
+
+    fn name(&self) -> &str {
+        "StatePlugin"
+    }
+}
+
+/// Runs the schedule once per tick of the `SimulationSpeed` in every app update
+/// and renders only the last tick, starting with the speed of the config. Needs the render plugin.
+pub struct SpeedPlugin;
+
+impl Plugin for SpeedPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.app.schedule.set_run_criteria(repeat_ticks.system());
+        app.add_resource(SimulationSpeed::default())
+            .add_resource(LastTick(true))
+            .stage(bevy::render::stage::RENDER, |stage: &mut SystemStage| {
+                stage.set_run_criteria(render_frame.system())
+            })
+            .add_startup_system(apply_config_speed.system())
+            .add_system_to_stage(stage::PRE_UPDATE, change_speed.system());
+    }
+
+    fn name(&self) -> &str {
+        "SpeedPlugin"
+    }
+}
+
+/// Run criteria of the gameplay stages.
+pub fn running(state: Res<SimulationState>) -> ShouldRun {
+    if state.is_running() {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+fn toggle_pause(keys: Res<Input<KeyCode>>, mut state: ResMut<SimulationState>) {
+    if keys.just_pressed(PAUSE_KEY) {
+        *state = match *state {
+            SimulationState::Running => SimulationState::Paused,
+            SimulationState::Paused => SimulationState::Running,
+        };
+        info!("simulation {:?}", *state);
+    }
+}
+
+fn pause_physics(state: Res<SimulationState>, mut configuration: ResMut<RapierConfiguration>) {
+    let active = state.is_running();
+    if configuration.physics_pipeline_active != active {
+        configuration.physics_pipeline_active = active;
+    }
+}
+
+fn apply_config_speed(config: Res<SimConfig>, mut speed: ResMut<SimulationSpeed>) {
+    *speed = config.speed;
+}
+
+fn change_speed(keys: Res<Input<KeyCode>>, mut speed: ResMut<SimulationSpeed>) {
+    for (key, selected) in SPEED_KEYS.iter() {
+        if keys.just_pressed(*key) && *speed != *selected {
+            *speed = *selected;
+            info!("simulation speed {:?}", *speed);
+        }
+    }
+}
+
+/// Whether the current tick is the last one of the app update and gets rendered.
+struct LastTick(bool);
+
+/// Run criteria of the whole schedule, repeats it for `n` ticks per app update.
+/// At the max speed it repeats until `MAX_SPEED_FRAME_INTERVAL` has passed.
+/// Paused simulations run once per update.
+fn repeat_ticks(
+    state: Res<SimulationState>,
+    speed: Res<SimulationSpeed>,
+    mut last_tick: ResMut<LastTick>,
+    mut ticks: Local<usize>,
+    mut frame_start: Local<Option<Instant>>,
+) -> ShouldRun {
+    let start = *frame_start.get_or_insert_with(Instant::now);
+    *ticks += 1;
+    last_tick.0 = match *speed {
+        _ if !state.is_running() => true,
+        SimulationSpeed::Times(n) => *ticks >= n,
+        SimulationSpeed::Max => start.elapsed() >= MAX_SPEED_FRAME_INTERVAL,
+    };
+    if last_tick.0 {
+        *ticks = 0;
+        *frame_start = None;
+        ShouldRun::Yes
+    } else {
+        ShouldRun::YesAndLoop
+    }
+}
+
+/// Run criteria of the render stage, only renders the last tick of an app update.
+fn render_frame(last_tick: Res<LastTick>) -> ShouldRun {
+    if last_tick.0 {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
@@ -0,0 +1,201 @@
+use crate::{
+    console::ConsoleCommand,
+    corgi::{Corgi, Energy, Generation},
+    species::Species,
+    state::SimulationState,
+    universe::{tile::TilePos, Tick, Universe},
+};
+use bevy::prelude::*;
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// Side length of a region in tiles.
+pub const REGION_SIZE: usize = 8;
+/// Seconds between two updates of the window title.
+const TITLE_INTERVAL: f64 = 1.0;
+const TITLE: &str = "corgis";
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Region {
+    pub population: usize,
+    pub energy: f32,
+    /// Number of distinct species.
+    pub species: usize,
+    generation_sum: usize,
+}
+
+impl Region {
+    pub fn mean_energy(&self) -> f32 {
+        self.energy / self.population.max(1) as f32
+    }
+
+    pub fn mean_generation(&self) -> f32 {
+        self.generation_sum as f32 / self.population.max(1) as f32
+    }
+}
+
+/// Statistics of the corgis per region of `REGION_SIZE` x `REGION_SIZE` tiles,
+/// rebuilt every tick. Resized with the universe.
+pub struct RegionStats {
+    universe: Universe,
+    /// Regions per row.
+    columns: usize,
+    regions: Vec<Region>,
+}
+
+impl Default for RegionStats {
+    fn default() -> Self {
+        Self::new(&Universe::default())
+    }
+}
+
+impl RegionStats {
+    pub fn new(universe: &Universe) -> Self {
+        let columns = (universe.width_tiles + REGION_SIZE - 1) / REGION_SIZE;
+        let rows = (universe.height_tiles + REGION_SIZE - 1) / REGION_SIZE;
+        Self {
+            universe: *universe,
+            columns,
+            regions: vec![Region::default(); columns * rows],
+        }
+    }
+
+    fn index(&self, tile: TilePos) -> usize {
+        tile.y / REGION_SIZE * self.columns + tile.x / REGION_SIZE
+    }
+
+    /// The region containing `pos`, if it's inside of the universe.
+    pub fn get(&self, pos: Vec2) -> Option<&Region> {
+        let tile = self.universe.tile_at(pos)?;
+        self.regions.get(self.index(tile))
+    }
+
+    /// All regions row by row, with their region coordinates.
+    pub fn iter(&self) -> impl Iterator<Item = ((usize, usize), &Region)> {
+        let columns = self.columns;
+        self.regions
+            .iter()
+            .enumerate()
+            .map(move |(i, region)| ((i % columns, i / columns), region))
+    }
+
+    /// World size of a region.
+    pub fn region_size(&self) -> f32 {
+        REGION_SIZE as f32 * self.universe.tile_size
+    }
+
+    pub fn write_csv<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "x,y,population,mean_energy,mean_generation,species")?;
+        for ((x, y), region) in self.iter() {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{}",
+                x,
+                y,
+                region.population,
+                region.mean_energy(),
+                region.mean_generation(),
+                region.species
+            )?;
+        }
+        writer.flush()
+    }
+}
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(RegionStats::default())
+            .add_system_to_stage(stage::POST_UPDATE, update_region_stats.system())
+            .add_system_to_stage(stage::POST_UPDATE, export_region_stats.system())
+            .add_system_to_stage(stage::POST_UPDATE, update_window_title.system());
+    }
+
+    fn name(&self) -> &str {
+        "StatsPlugin"
+    }
+}
+
+fn update_region_stats(
+    universe: Res<Universe>,
+    mut stats: ResMut<RegionStats>,
+    mut species: Local<Vec<HashSet<Species>>>,
+    query: Query<(&Transform, &Energy, &Generation, Option<&Species>)>,
+) {
+    if stats.universe != *universe {
+        *stats = RegionStats::new(&universe);
+    }
+    species.resize_with(stats.regions.len(), HashSet::new);
+    for (region, species) in stats.regions.iter_mut().zip(species.iter_mut()) {
+        *region = Region::default();
+        species.clear();
+    }
+    for (transform, energy, generation, corgi_species) in query.iter() {
+        let tile = match universe.tile_at(transform.translation.truncate()) {
+            Some(tile) => tile,
+            None => continue,
+        };
+        let index = stats.index(tile);
+        let region = &mut stats.regions[index];
+        region.population += 1;
+        region.energy += energy.get();
+        region.generation_sum += generation.0;
+        if let Some(corgi_species) = corgi_species {
+            species[index].insert(*corgi_species);
+        }
+    }
+    for (region, species) in stats.regions.iter_mut().zip(species.iter()) {
+        region.species = species.len();
+    }
+}
+
+fn export_region_stats(
+    stats: Res<RegionStats>,
+    mut reader: Local<EventReader<ConsoleCommand>>,
+    console: Res<Events<ConsoleCommand>>,
+) {
+    for command in reader.iter(&console) {
+        if let ConsoleCommand::Regions(path) = command {
+            match stats.write_csv(path) {
+                Ok(()) => info!("region stats written to `{}`", path.display()),
+                Err(e) => error!("failed to write `{}`: {}", path.display(), e),
+            }
+        }
+    }
+}
+
+/// Shows the progress in the window title, so minimized runs can be monitored.
+/// The best fitness is the highest energy, as in the selection.
+fn update_window_title(
+    time: Res<Time>,
+    tick: Res<Tick>,
+    state: Res<SimulationState>,
+    mut last_update: Local<f64>,
+    mut windows: ResMut<Windows>,
+    query: Query<&Energy, With<Corgi>>,
+) {
+    let now = time.seconds_since_startup();
+    if now - *last_update < TITLE_INTERVAL {
+        return;
+    }
+    *last_update = now;
+
+    let window = match windows.get_primary_mut() {
+        Some(window) => window,
+        None => return,
+    };
+    let (population, best) = query.iter().fold((0, 0.0f32), |(count, best), energy| {
+        (count + 1, best.max(energy.get()))
+    });
+    let paused = if state.is_running() { "" } else { " - paused" };
+    window.set_title(format!(
+        "{} - tick {} - {} corgis - best fitness {:.1}{}",
+        TITLE, tick.0, population, best, paused
+    ));
+}
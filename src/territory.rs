@@ -0,0 +1,156 @@
+use crate::{
+    console::ConsoleCommand,
+    corgi::Energy,
+    debug::EnergyLedger,
+    lineage::{Lineage, LineageId},
+    rng::SimRng,
+    universe::{
+        scenario::Scenario,
+        tile::{OnTile, TileGrid, TileType},
+    },
+};
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Energy per tick for standing on a tile owned by the own team.
+const TERRITORY_ENERGY: f32 = 0.05;
+/// How strongly owned tiles are tinted in the color of their team.
+pub const TERRITORY_TINT: f32 = 0.35;
+
+/// The team of a corgi, inherited from the first parent.
+/// Only assigned if the scenario has teams.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Team {
+    Blue,
+    Red,
+}
+
+impl Team {
+    pub fn color(self) -> Color {
+        match self {
+            Self::Blue => Color::rgb(0.2, 0.3, 0.9),
+            Self::Red => Color::rgb(0.9, 0.2, 0.2),
+        }
+    }
+
+    fn random<R: Rng>(rng: &mut R) -> Self {
+        if rng.gen() {
+            Self::Blue
+        } else {
+            Self::Red
+        }
+    }
+}
+
+/// Number of tiles owned by each team, recounted every tick.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Territory {
+    pub blue: usize,
+    pub red: usize,
+}
+
+impl Territory {
+    pub fn get(&self, team: Team) -> usize {
+        match team {
+            Team::Blue => self.blue,
+            Team::Red => self.red,
+        }
+    }
+
+    fn get_mut(&mut self, team: Team) -> &mut usize {
+        match team {
+            Team::Blue => &mut self.blue,
+            Team::Red => &mut self.red,
+        }
+    }
+}
+
+pub struct TerritoryPlugin;
+
+impl Plugin for TerritoryPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(Territory::default())
+            .add_system(assign_teams.system())
+            .add_system(capture_territory.system())
+            .add_system(count_territory.system())
+            .add_system_to_stage(stage::POST_UPDATE, print_territory.system());
+    }
+
+    fn name(&self) -> &str {
+        "TerritoryPlugin"
+    }
+}
+
+/// Children join the team of their first parent, spawned corgis a random one.
+fn assign_teams(
+    commands: &mut Commands,
+    scenario: Res<Scenario>,
+    teams: Query<(&Lineage, &Team)>,
+    query: Query<(Entity, &Lineage), Without<Team>>,
+    mut rng: ResMut<SimRng>,
+) {
+    if !scenario.teams {
+        return;
+    }
+    let known: HashMap<LineageId, Team> = teams
+        .iter()
+        .map(|(lineage, team)| (lineage.id, *team))
+        .collect();
+    let rng = &mut *rng;
+    for (entity, lineage) in query.iter() {
+        let team = lineage
+            .parents
+            .first()
+            .and_then(|parent| known.get(parent).copied())
+            .unwrap_or_else(|| Team::random(rng));
+        commands.insert_one(entity, team);
+    }
+}
+
+/// Corgis gain energy on the tiles of their team and capture the grass tiles they stand on.
+fn capture_territory(
+    ledger: Res<EnergyLedger>,
+    mut grid: ResMut<TileGrid>,
+    mut query: Query<(&Team, &OnTile, &mut Energy)>,
+) {
+    for (team, on_tile, mut energy) in query.iter_mut() {
+        let pos = match on_tile.0 {
+            Some(pos) => pos,
+            None => continue,
+        };
+        let owner = match grid.get(pos) {
+            Some(tile) if tile.tile_type == TileType::Grass => tile.owner,
+            _ => continue,
+        };
+        if owner == Some(*team) {
+            ledger.source(energy.add(TERRITORY_ENERGY));
+        } else if let Some(tile) = grid.get_mut(pos) {
+            // only on change, mutable access rerenders the chunk
+            tile.owner = Some(*team);
+        }
+    }
+}
+
+fn count_territory(grid: Res<TileGrid>, mut territory: ResMut<Territory>) {
+    *territory = Territory::default();
+    for owner in grid.iter().filter_map(|tile| tile.owner) {
+        *territory.get_mut(owner) += 1;
+    }
+}
+
+fn print_territory(
+    territory: Res<Territory>,
+    mut reader: Local<EventReader<ConsoleCommand>>,
+    console: Res<Events<ConsoleCommand>>,
+) {
+    for command in reader.iter(&console) {
+        if let ConsoleCommand::Territory = command {
+            info!(
+                "territory: blue {} tiles, red {} tiles",
+                territory.blue, territory.red
+            );
+        }
+    }
+}
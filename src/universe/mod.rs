@@ -0,0 +1,463 @@
+pub mod scenario;
+pub mod terrain;
+pub mod tile;
+pub mod weather;
+
+use crate::{
+    console::ConsoleCommand,
+    corgi::{Corgi, Energy, Load},
+    corpse::{self, DeathCause, Died},
+    loader::MyAssets,
+};
+use bevy::prelude::*;
+use bevy_rapier2d::{
+    na::{Isometry2, Vector2},
+    physics::{RapierConfiguration, RigidBodyHandleComponent},
+    rapier::dynamics::RigidBodySet,
+};
+use serde::{Deserialize, Serialize};
+
+/// Size of the universe in tiles, if the scenario doesn't set it.
+pub const DEFAULT_WIDTH_TILES: usize = 25;
+pub const DEFAULT_HEIGHT_TILES: usize = 25;
+/// Side length of a tile in pixels, if the config doesn't set it.
+pub const DEFAULT_TILE_SIZE: f32 = 20.0;
+/// Distance from the edges in tiles at which the `Boundary::Repel` force starts.
+const BOUNDARY_REPEL_RANGE: f32 = 1.0;
+/// Acceleration of the `Boundary::Repel` force right at the edge, it grows linearly towards it.
+const BOUNDARY_REPEL_ACCELERATION: f32 = 2_000.0;
+
+/// What happens at the edges of the universe.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Topology {
+    /// The universe ends at its edges.
+    Bounded,
+    /// Leaving the universe at one edge enters it at the opposite one,
+    /// distances are measured the shorter way around.
+    Toroidal,
+}
+
+impl Default for Topology {
+    fn default() -> Self {
+        Self::Bounded
+    }
+}
+
+/// What happens to corgis reaching the edges of a bounded universe.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Boundary {
+    /// They are kept inside and bounce off.
+    Bounce,
+    /// A force pushes them back, growing the closer they get.
+    Repel,
+    /// They die as soon as they leave the universe.
+    Death,
+}
+
+impl Default for Boundary {
+    fn default() -> Self {
+        Self::Bounce
+    }
+}
+
+/// The size and shape of the universe.
+/// Taken from the scenario and changed at runtime by the `resize` command.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Universe {
+    pub width_tiles: usize,
+    pub height_tiles: usize,
+    /// Side length of a tile in pixels, from the config of the initial universe.
+    pub tile_size: f32,
+    pub topology: Topology,
+    /// Only used in a bounded universe.
+    pub boundary: Boundary,
+}
+
+impl Default for Universe {
+    fn default() -> Self {
+        Self {
+            width_tiles: DEFAULT_WIDTH_TILES,
+            height_tiles: DEFAULT_HEIGHT_TILES,
+            tile_size: DEFAULT_TILE_SIZE,
+            topology: Topology::default(),
+            boundary: Boundary::default(),
+        }
+    }
+}
+
+impl Universe {
+    pub fn width(&self) -> f32 {
+        self.width_tiles as f32 * self.tile_size
+    }
+
+    pub fn height(&self) -> f32 {
+        self.height_tiles as f32 * self.tile_size
+    }
+
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(self.width(), self.height())
+    }
+
+    /// The tile containing `pos`, if it's inside of the universe.
+    pub fn tile_at(&self, pos: Vec2) -> Option<tile::TilePos> {
+        if pos.x < 0.0 || pos.y < 0.0 {
+            return None;
+        }
+        let x = (pos.x / self.tile_size) as usize;
+        let y = (pos.y / self.tile_size) as usize;
+        if x < self.width_tiles && y < self.height_tiles {
+            Some(tile::TilePos { x, y })
+        } else {
+            None
+        }
+    }
+
+    /// The tile `dx`, `dy` tiles away from `tile`, wrapped around in a toroidal universe.
+    /// `None` if it is outside of a bounded one.
+    pub fn step(&self, tile: tile::TilePos, dx: i64, dy: i64) -> Option<tile::TilePos> {
+        let (width, height) = (self.width_tiles as i64, self.height_tiles as i64);
+        let (mut x, mut y) = (tile.x as i64 + dx, tile.y as i64 + dy);
+        if self.topology == Topology::Toroidal {
+            x = x.rem_euclid(width.max(1));
+            y = y.rem_euclid(height.max(1));
+        }
+        if x < 0 || y < 0 || x >= width || y >= height {
+            return None;
+        }
+        Some(tile::TilePos {
+            x: x as usize,
+            y: y as usize,
+        })
+    }
+
+    /// The up to 4 tiles sharing an edge with `tile`.
+    pub fn neighbors4(&self, tile: tile::TilePos) -> impl Iterator<Item = tile::TilePos> + '_ {
+        const STEPS: [(i64, i64); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+        STEPS
+            .iter()
+            .filter_map(move |&(dx, dy)| self.step(tile, dx, dy))
+    }
+
+    /// The up to 8 tiles sharing an edge or a corner with `tile`.
+    pub fn neighbors8(&self, tile: tile::TilePos) -> impl Iterator<Item = tile::TilePos> + '_ {
+        const STEPS: [(i64, i64); 8] = [
+            (1, 0),
+            (1, 1),
+            (0, 1),
+            (-1, 1),
+            (-1, 0),
+            (-1, -1),
+            (0, -1),
+            (1, -1),
+        ];
+        STEPS
+            .iter()
+            .filter_map(move |&(dx, dy)| self.step(tile, dx, dy))
+    }
+
+    /// The tiles with their center at most `radius` away from `pos`.
+    pub fn tiles_in_radius(&self, pos: Vec2, radius: f32) -> Vec<tile::TilePos> {
+        let reach = (radius / self.tile_size).ceil() as i64 + 1;
+        let center = tile::TilePos {
+            x: (pos.x / self.tile_size).max(0.0) as usize,
+            y: (pos.y / self.tile_size).max(0.0) as usize,
+        };
+        let mut tiles: Vec<tile::TilePos> = (-reach..=reach)
+            .flat_map(|dy| (-reach..=reach).map(move |dx| (dx, dy)))
+            .filter_map(|(dx, dy)| self.step(center, dx, dy))
+            .filter(|tile| self.offset(pos, tile.center(self.tile_size)).length() <= radius)
+            .collect();
+        // small toroidal universes reach the same tile several times
+        tiles.sort_by_key(|tile| (tile.y, tile.x));
+        tiles.dedup();
+        tiles
+    }
+
+    /// The nearest position inside of the universe, at least half a tile away from the edges.
+    pub fn clamp(&self, pos: Vec2) -> Vec2 {
+        let margin = Vec2::splat(self.tile_size / 2.0);
+        pos.max(margin).min(self.size() - margin)
+    }
+
+    /// The position inside of the universe `pos` corresponds to.
+    pub fn wrap(&self, pos: Vec2) -> Vec2 {
+        match self.topology {
+            Topology::Bounded => pos,
+            Topology::Toroidal => Vec2::new(
+                pos.x.rem_euclid(self.width()),
+                pos.y.rem_euclid(self.height()),
+            ),
+        }
+    }
+
+    /// The shortest offset from `from` to `to`.
+    pub fn offset(&self, from: Vec2, to: Vec2) -> Vec2 {
+        let offset = to - from;
+        match self.topology {
+            Topology::Bounded => offset,
+            Topology::Toroidal => {
+                let size = self.size();
+                Vec2::new(
+                    offset.x - size.x * (offset.x / size.x).round(),
+                    offset.y - size.y * (offset.y / size.y).round(),
+                )
+            }
+        }
+    }
+}
+
+/// Moves corgis which left a toroidal universe to the opposite edge.
+pub fn wrap_corgis(
+    universe: Res<Universe>,
+    mut bodies: ResMut<RigidBodySet>,
+    query: Query<&RigidBodyHandleComponent, With<Corgi>>,
+) {
+    if universe.topology == Topology::Bounded {
+        return;
+    }
+    for handle in query.iter() {
+        let body = match bodies.get_mut(handle.handle()) {
+            Some(body) => body,
+            None => continue,
+        };
+        let translation = body.position().translation;
+        let pos = Vec2::new(translation.x, translation.y);
+        let wrapped = universe.wrap(pos);
+        if wrapped != pos {
+            let rotation = body.position().rotation.angle();
+            body.set_position(
+                Isometry2::new(Vector2::new(wrapped.x, wrapped.y), rotation),
+                true,
+            );
+        }
+    }
+}
+
+/// Keeps the corgis of a bounded universe inside according to its `Boundary`.
+pub fn enforce_boundary(
+    commands: &mut Commands,
+    universe: Res<Universe>,
+    assets: Res<MyAssets>,
+    mut deaths: ResMut<Events<Died>>,
+    mut bodies: ResMut<RigidBodySet>,
+    query: Query<(Entity, &RigidBodyHandleComponent, &Energy, &Load), With<Corgi>>,
+) {
+    if universe.topology == Topology::Toroidal {
+        return;
+    }
+    let size = universe.size();
+    for (entity, handle, energy, load) in query.iter() {
+        let body = match bodies.get_mut(handle.handle()) {
+            Some(body) => body,
+            None => continue,
+        };
+        let translation = body.position().translation;
+        let pos = Vec2::new(translation.x, translation.y);
+        match universe.boundary {
+            Boundary::Bounce => {
+                let inside = pos.max(Vec2::zero()).min(size);
+                if inside == pos {
+                    continue;
+                }
+                let mut velocity = *body.linvel();
+                if pos.x != inside.x {
+                    velocity.x = if pos.x < 0.0 {
+                        velocity.x.abs()
+                    } else {
+                        -velocity.x.abs()
+                    };
+                }
+                if pos.y != inside.y {
+                    velocity.y = if pos.y < 0.0 {
+                        velocity.y.abs()
+                    } else {
+                        -velocity.y.abs()
+                    };
+                }
+                let rotation = body.position().rotation.angle();
+                body.set_position(
+                    Isometry2::new(Vector2::new(inside.x, inside.y), rotation),
+                    true,
+                );
+                body.set_linvel(velocity, true);
+            }
+            Boundary::Repel => {
+                // how far the corgi reached into the range, beyond 1 outside of the universe
+                let range = BOUNDARY_REPEL_RANGE * universe.tile_size;
+                let depth = |distance: f32| (1.0 - distance / range).max(0.0);
+                let push = Vector2::new(
+                    depth(pos.x) - depth(size.x - pos.x),
+                    depth(pos.y) - depth(size.y - pos.y),
+                );
+                if push != Vector2::zeros() {
+                    body.apply_force(push * BOUNDARY_REPEL_ACCELERATION * body.mass(), true);
+                }
+            }
+            Boundary::Death => {
+                if universe.tile_at(pos).is_none() {
+                    let remains = corpse::remains(energy, load);
+                    let pos = universe.clamp(pos);
+                    corpse::kill(
+                        commands,
+                        &assets,
+                        &mut deaths,
+                        entity,
+                        pos,
+                        remains,
+                        DeathCause::Boundary,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Switches the topology or the boundary with the `boundary` command.
+pub fn change_boundary(
+    mut reader: Local<EventReader<ConsoleCommand>>,
+    console: Res<Events<ConsoleCommand>>,
+    mut universe: ResMut<Universe>,
+) {
+    for command in reader.iter(&console) {
+        if let ConsoleCommand::Boundary(boundary) = command {
+            match boundary {
+                Some(boundary) => {
+                    universe.topology = Topology::Bounded;
+                    universe.boundary = *boundary;
+                }
+                None => universe.topology = Topology::Toroidal,
+            }
+            info!(
+                "the universe is {:?} with the boundary {:?}",
+                universe.topology, universe.boundary
+            );
+        }
+    }
+}
+
+/// Number of simulated frames since the start.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Tick(pub usize);
+
+pub fn advance_tick(mut tick: ResMut<Tick>) {
+    tick.0 += 1;
+}
+
+/// Ticks of a full day and night.
+pub const DAY_LENGTH: usize = 3600;
+
+/// The light level, between 0 at midnight and 1 at noon.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Daylight(pub f32);
+
+pub fn update_daylight(tick: Res<Tick>, mut daylight: ResMut<Daylight>) {
+    let phase = (tick.0 % DAY_LENGTH) as f32 / DAY_LENGTH as f32;
+    daylight.0 = 0.5 - 0.5 * (phase * std::f32::consts::PI * 2.0).cos();
+}
+
+/// Exposure far away from the sun spot, relative to its center.
+const AMBIENT_EXPOSURE: f32 = 0.3;
+/// Radius of the sun spot relative to the smaller side of the universe.
+const SUN_SPOT_RADIUS: f32 = 0.3;
+/// Ticks the sun spot takes for one round over the universe.
+const SUN_SPOT_PERIOD: usize = 20_000;
+
+/// The light over the universe: the daylight is brightest in a sun spot wandering over the map.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Light {
+    pub daylight: f32,
+    pub center: Vec2,
+    pub radius: f32,
+    universe: Universe,
+}
+
+impl Light {
+    /// Between `AMBIENT_EXPOSURE` far away and 1 in the center of the sun spot,
+    /// independent of the time of day.
+    pub fn exposure(&self, pos: Vec2) -> f32 {
+        let offset = self.universe.offset(pos, self.center);
+        let distance = offset.length() / self.radius.max(f32::EPSILON);
+        AMBIENT_EXPOSURE + (1.0 - AMBIENT_EXPOSURE) * (-0.5 * distance * distance).exp()
+    }
+
+    /// The light level at `pos`, between 0 and 1.
+    pub fn at(&self, pos: Vec2) -> f32 {
+        self.daylight * self.exposure(pos)
+    }
+
+    /// Unit vector from `pos` towards the sun spot, zero in its center.
+    pub fn direction(&self, pos: Vec2) -> Vec2 {
+        let offset = self.universe.offset(pos, self.center);
+        if offset.length() > 0.0 {
+            offset.normalize()
+        } else {
+            Vec2::zero()
+        }
+    }
+}
+
+/// Moves the sun spot on an ellipse around the center of the universe.
+pub fn update_light(
+    tick: Res<Tick>,
+    daylight: Res<Daylight>,
+    universe: Res<Universe>,
+    mut light: ResMut<Light>,
+) {
+    let phase = (tick.0 % SUN_SPOT_PERIOD) as f32 / SUN_SPOT_PERIOD as f32;
+    let angle = phase * std::f32::consts::PI * 2.0;
+    let size = universe.size();
+    light.daylight = daylight.0;
+    light.center = size / 2.0 + Vec2::new(angle.cos(), angle.sin()) * size / 3.0;
+    light.radius = size.x.min(size.y) * SUN_SPOT_RADIUS;
+    light.universe = *universe;
+}
+
+/// Maximum temperature change by the seasons at full amplitude.
+const SEASON_TEMPERATURE_RANGE: f32 = 0.3;
+
+/// The time of the year, `warmth` is 1 in midsummer and -1 in midwinter.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Season {
+    pub warmth: f32,
+    /// Between 0 for no seasons and 1, from the scenario.
+    pub amplitude: f32,
+}
+
+impl Season {
+    /// Factor of the food regrowth, no regrowth at all in midwinter at full amplitude.
+    pub fn regrowth(&self) -> f32 {
+        1.0 + self.amplitude * self.warmth
+    }
+
+    /// Added to the temperature of every tile.
+    pub fn temperature_offset(&self) -> f32 {
+        self.amplitude * self.warmth * SEASON_TEMPERATURE_RANGE
+    }
+}
+
+pub fn update_season(
+    tick: Res<Tick>,
+    scenario: Res<scenario::Scenario>,
+    mut season: ResMut<Season>,
+) {
+    let length = scenario.season_length.max(1);
+    let phase = (tick.0 % length) as f32 / length as f32;
+    season.warmth = (phase * std::f32::consts::PI * 2.0).sin();
+    season.amplitude = scenario.season_amplitude.max(0.0).min(1.0);
+}
+
+pub fn setup_physics(mut configuration: ResMut<RapierConfiguration>) {
+    configuration.gravity = Vector2::new(0.0, 0.0);
+}
+
+pub fn setup_graphics(commands: &mut Commands) {
+    commands
+        .spawn(LightBundle {
+            transform: Transform::from_translation(Vec3::new(1000.0, 100.0, 2000.0)),
+            ..Default::default()
+        })
+        .spawn(Camera2dBundle {
+            transform: Transform::from_translation(Vec3::new(0.0, 0.0, 0.0)),
+            ..Camera2dBundle::default()
+        });
+}
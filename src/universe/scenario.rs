@@ -0,0 +1,390 @@
+use super::{
+    tile::{self, ChunkSprite, TileBehaviors, TileGrid, WallCollider},
+    Boundary, Topology, Universe, DEFAULT_HEIGHT_TILES, DEFAULT_WIDTH_TILES,
+};
+use crate::{
+    catastrophe::{Catastrophe, ScheduledCatastrophe},
+    color::{ActiveColorSemantics, ColorSemanticsKind},
+    console::ConsoleCommand,
+    corgi::Corgi,
+    corpse::Corpse,
+    intelligence::{brain::MutationNoise, decision::reproduction::Egg},
+    parasite::Parasite,
+    rng::SimRng,
+    scent::ScentField,
+    sound::Sounds,
+    spatial::SpatialGrid,
+};
+use bevy::prelude::*;
+use bevy_rapier2d::{
+    na::{Isometry2, Vector2},
+    physics::RigidBodyHandleComponent,
+    rapier::dynamics::RigidBodySet,
+};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+/// The world a simulation runs in, loaded from a RON file.
+/// Missing fields take their default values.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scenario {
+    /// New corgis are spawned if the population drops below this.
+    pub min_corgi_count: usize,
+    /// Size of the universe in tiles.
+    pub width_tiles: usize,
+    pub height_tiles: usize,
+    /// Fraction of the tiles which are healing springs.
+    pub spring_probability: f64,
+    pub portal_pairs: usize,
+    /// Tiles with a moisture above this are water, 1 for no water at all.
+    pub water_level: f32,
+    /// Number of wall structures, some of them bent.
+    pub wall_count: usize,
+    /// Whether the universe wraps around at its edges.
+    pub topology: Topology,
+    /// What happens at the edges of a bounded universe.
+    pub boundary: Boundary,
+    /// Whether the corgis form a blue and a red team which capture tiles.
+    pub teams: bool,
+    /// Seed of the terrain noise, a random one if `None`.
+    pub terrain_seed: Option<u64>,
+    /// Size of the fertile and barren regions in tiles.
+    pub terrain_scale: f32,
+    /// Size of the biomes in tiles.
+    pub biome_scale: f32,
+    /// Size of the hills and valleys in tiles.
+    pub elevation_scale: f32,
+    /// Ticks of a full year.
+    pub season_length: usize,
+    /// How strongly the seasons change regrowth and temperature, between 0 and 1.
+    pub season_amplitude: f32,
+    /// Whether rain, droughts and storms change the regrowth and push the corgis.
+    pub weather: bool,
+    /// Seed of the weather, a random one if `None`.
+    pub weather_seed: Option<u64>,
+    /// Catastrophes striking at fixed ticks.
+    pub catastrophes: Vec<ScheduledCatastrophe>,
+    /// Mean ticks between two random catastrophes, none if `None`.
+    pub catastrophe_interval: Option<usize>,
+    /// The meaning of the color of the corgis.
+    pub color_semantics: ColorSemanticsKind,
+    pub reproduction: ReproductionMode,
+    pub genetics: Genetics,
+    pub locomotion: Locomotion,
+    /// How the weights of the networks mutate.
+    pub mutation: MutationNoise,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Genetics {
+    /// One copy of every gene, which is always expressed.
+    Haploid,
+    /// Two alleles of every gene with a dominance,
+    /// each parent passes on a recombination of its two copies.
+    Diploid,
+}
+
+impl Default for Genetics {
+    fn default() -> Self {
+        Self::Haploid
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ReproductionMode {
+    /// A selected corgi gives birth to a mutated copy of itself.
+    Asexual,
+    /// A selected corgi mates with a willing partner nearby,
+    /// the child gets a recombined genome and both pay half of the energy.
+    Sexual,
+}
+
+impl Default for ReproductionMode {
+    fn default() -> Self {
+        Self::Asexual
+    }
+}
+
+/// How the movement decision moves the corgi.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Locomotion {
+    /// The decision is a force vector in world space.
+    Force,
+    /// The decision is a turn rate and a forward thrust along the heading of the corgi,
+    /// which also orients its body.
+    Heading,
+}
+
+impl Default for Locomotion {
+    fn default() -> Self {
+        Self::Force
+    }
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Self {
+            min_corgi_count: 1,
+            width_tiles: DEFAULT_WIDTH_TILES,
+            height_tiles: DEFAULT_HEIGHT_TILES,
+            spring_probability: 0.02,
+            portal_pairs: 2,
+            water_level: 0.8,
+            wall_count: 4,
+            topology: Topology::default(),
+            boundary: Boundary::default(),
+            teams: false,
+            terrain_seed: None,
+            terrain_scale: 8.0,
+            biome_scale: 16.0,
+            elevation_scale: 12.0,
+            season_length: 36_000,
+            season_amplitude: 0.5,
+            weather: true,
+            weather_seed: None,
+            catastrophes: Vec::new(),
+            catastrophe_interval: None,
+            color_semantics: ColorSemanticsKind::default(),
+            reproduction: ReproductionMode::default(),
+            genetics: Genetics::default(),
+            locomotion: Locomotion::default(),
+            mutation: MutationNoise::default(),
+        }
+    }
+}
+
+impl Scenario {
+    /// Values which load fine, but make no sense.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if !(0.0..=1.0).contains(&self.spring_probability) {
+            problems.push(format!(
+                "spring_probability {} is not in [0, 1]",
+                self.spring_probability
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.season_amplitude) {
+            problems.push(format!(
+                "season_amplitude {} is not in [0, 1]",
+                self.season_amplitude
+            ));
+        }
+        problems.extend(self.size_problems(self.width_tiles, self.height_tiles));
+        if self.catastrophe_interval == Some(0) {
+            problems.push("catastrophe_interval has to be at least 1".to_string());
+        }
+        for scheduled in &self.catastrophes {
+            if let Catastrophe::Plague { fraction } = scheduled.catastrophe {
+                if !(0.0..=1.0).contains(&fraction) {
+                    problems.push(format!(
+                        "plague at tick {} has fraction {}, which is not in [0, 1]",
+                        scheduled.tick, fraction
+                    ));
+                }
+            }
+        }
+        problems
+    }
+
+    /// Problems of a universe of the given size with this scenario.
+    pub fn size_problems(&self, width_tiles: usize, height_tiles: usize) -> Vec<String> {
+        let mut problems = Vec::new();
+        if width_tiles == 0 || height_tiles == 0 {
+            problems.push(format!(
+                "the universe of {}x{} tiles is empty",
+                width_tiles, height_tiles
+            ));
+        }
+        if self.portal_pairs * 2 > width_tiles * height_tiles {
+            problems.push(format!(
+                "{} portal pairs don't fit into the universe",
+                self.portal_pairs
+            ));
+        }
+        problems
+    }
+
+    /// The universe at the start of the scenario, with tiles of `tile_size` pixels.
+    pub fn universe(&self, tile_size: f32) -> Universe {
+        Universe {
+            width_tiles: self.width_tiles,
+            height_tiles: self.height_tiles,
+            tile_size,
+            topology: self.topology,
+            boundary: self.boundary,
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let string = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read `{}`: {}", path.display(), e))?;
+        ron::de::from_str(&string)
+            .map_err(|e| format!("invalid scenario `{}`: {}", path.display(), e))
+    }
+}
+
+pub struct ScenarioPlugin;
+
+impl Plugin for ScenarioPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(Scenario::default())
+            .add_resource(ActiveColorSemantics::default())
+            .add_resource(Universe::default())
+            .add_system_to_stage(stage::POST_UPDATE, switch_scenario.system())
+            .add_system_to_stage(stage::POST_UPDATE, resize_universe.system());
+    }
+
+    fn name(&self) -> &str {
+        "ScenarioPlugin"
+    }
+}
+
+/// Tears down the universe and builds it again from the new scenario:
+/// all corgis, corpses and tiles are despawned and the universe resources are reset.
+/// Scenarios with problems are rejected and the current one is kept.
+/// History like the lineage log and the genome archive is kept.
+#[allow(clippy::too_many_arguments)]
+fn switch_scenario(
+    commands: &mut Commands,
+    mut reader: Local<EventReader<ConsoleCommand>>,
+    console: Res<Events<ConsoleCommand>>,
+    mut scenario: ResMut<Scenario>,
+    mut universe: ResMut<Universe>,
+    mut color_semantics: ResMut<ActiveColorSemantics>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut textures: ResMut<Assets<Texture>>,
+    mut tile_grid: ResMut<TileGrid>,
+    mut behaviors: ResMut<TileBehaviors>,
+    mut grid: ResMut<SpatialGrid>,
+    mut scent: ResMut<ScentField>,
+    mut sounds: ResMut<Sounds>,
+    mut rng: ResMut<SimRng>,
+    existing: Query<
+        Entity,
+        Or<(
+            With<Corgi>,
+            With<Corpse>,
+            With<Egg>,
+            With<Parasite>,
+            With<ChunkSprite>,
+            With<WallCollider>,
+        )>,
+    >,
+) {
+    let path = match reader.iter(&console).find_map(|command| match command {
+        ConsoleCommand::Scenario(path) => Some(path),
+        _ => None,
+    }) {
+        Some(path) => path,
+        None => return,
+    };
+    let new_scenario = match Scenario::load(path) {
+        Ok(scenario) => scenario,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    let problems = new_scenario.problems();
+    if !problems.is_empty() {
+        for problem in problems {
+            error!("scenario `{}`: {}", path.display(), problem);
+        }
+        return;
+    }
+
+    for entity in existing.iter() {
+        commands.despawn(entity);
+    }
+    *universe = new_scenario.universe(universe.tile_size);
+    *grid = SpatialGrid::default();
+    *scent = ScentField::new(&universe);
+    *sounds = Sounds::default();
+
+    color_semantics.0 = new_scenario.color_semantics.build();
+    *scenario = new_scenario;
+    tile::spawn_tiles(
+        commands,
+        &scenario,
+        &universe,
+        &mut materials,
+        &mut textures,
+        &mut tile_grid,
+        &mut behaviors,
+        &mut rng,
+    );
+    info!("switched to scenario `{}`", path.display());
+}
+
+/// Regenerates the tiles of the current scenario in a universe of the new size.
+/// The corgis are kept, the ones outside of the new universe are moved to its edge.
+/// Sizes the scenario can't fill are rejected.
+#[allow(clippy::too_many_arguments)]
+fn resize_universe(
+    commands: &mut Commands,
+    mut reader: Local<EventReader<ConsoleCommand>>,
+    console: Res<Events<ConsoleCommand>>,
+    scenario: Res<Scenario>,
+    mut universe: ResMut<Universe>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut textures: ResMut<Assets<Texture>>,
+    mut tile_grid: ResMut<TileGrid>,
+    mut behaviors: ResMut<TileBehaviors>,
+    mut scent: ResMut<ScentField>,
+    mut bodies: ResMut<RigidBodySet>,
+    mut rng: ResMut<SimRng>,
+    tiles: Query<Entity, Or<(With<ChunkSprite>, With<WallCollider>)>>,
+    corgis: Query<&RigidBodyHandleComponent, With<Corgi>>,
+) {
+    let (width, height) = match reader.iter(&console).find_map(|command| match command {
+        ConsoleCommand::Resize { width, height } => Some((*width, *height)),
+        _ => None,
+    }) {
+        Some(size) => size,
+        None => return,
+    };
+    let problems = scenario.size_problems(width, height);
+    if !problems.is_empty() {
+        for problem in problems {
+            error!("can't resize: {}", problem);
+        }
+        return;
+    }
+
+    for entity in tiles.iter() {
+        commands.despawn(entity);
+    }
+    universe.width_tiles = width;
+    universe.height_tiles = height;
+    *scent = ScentField::new(&universe);
+    tile::spawn_tiles(
+        commands,
+        &scenario,
+        &universe,
+        &mut materials,
+        &mut textures,
+        &mut tile_grid,
+        &mut behaviors,
+        &mut rng,
+    );
+
+    for handle in corgis.iter() {
+        let body = match bodies.get_mut(handle.handle()) {
+            Some(body) => body,
+            None => continue,
+        };
+        let translation = body.position().translation;
+        let pos = Vec2::new(translation.x, translation.y);
+        if universe.tile_at(pos).is_none() {
+            let clamped = universe.clamp(pos);
+            let rotation = body.position().rotation.angle();
+            body.set_position(
+                Isometry2::new(Vector2::new(clamped.x, clamped.y), rotation),
+                true,
+            );
+        }
+    }
+    info!("resized the universe to {}x{} tiles", width, height);
+}
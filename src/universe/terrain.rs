@@ -0,0 +1,172 @@
+use bevy::prelude::*;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// Number of octaves of the fractal noise.
+const OCTAVES: usize = 4;
+/// Frequency multiplier between two octaves.
+const LACUNARITY: f32 = 2.0;
+/// Amplitude multiplier between two octaves.
+const PERSISTENCE: f32 = 0.5;
+
+/// Below this temperature noise the biome is tundra.
+const TUNDRA_TEMPERATURE: f32 = 0.35;
+/// Above this temperature noise dry regions are desert.
+const DESERT_TEMPERATURE: f32 = 0.6;
+const DESERT_MOISTURE: f32 = 0.45;
+/// Above this moisture noise the biome is swamp.
+const SWAMP_MOISTURE: f32 = 0.65;
+/// Above this temperature noise swamps are toxic.
+const TOXIC_TEMPERATURE: f32 = 0.6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Biome {
+    Grassland,
+    Desert,
+    Swamp,
+    Tundra,
+    /// A warm swamp with lush food, which damages corgis without toxin resistance.
+    Toxic,
+}
+
+impl Biome {
+    /// Classifies layered temperature and moisture noise, both between 0 and 1.
+    pub fn from_climate(temperature: f32, moisture: f32) -> Self {
+        if temperature < TUNDRA_TEMPERATURE {
+            Self::Tundra
+        } else if moisture > SWAMP_MOISTURE && temperature > TOXIC_TEMPERATURE {
+            Self::Toxic
+        } else if moisture > SWAMP_MOISTURE {
+            Self::Swamp
+        } else if temperature > DESERT_TEMPERATURE && moisture < DESERT_MOISTURE {
+            Self::Desert
+        } else {
+            Self::Grassland
+        }
+    }
+
+    /// Factor of the food regrowth rate.
+    pub fn regrowth(self) -> f32 {
+        match self {
+            Self::Grassland => 1.0,
+            Self::Desert => 0.2,
+            Self::Swamp => 1.5,
+            Self::Tundra => 0.4,
+            Self::Toxic => 2.0,
+        }
+    }
+
+    /// Divides the movement force of the corgis.
+    pub fn movement_cost(self) -> f32 {
+        match self {
+            Self::Grassland => 1.0,
+            Self::Desert => 1.2,
+            Self::Swamp => 2.0,
+            Self::Tundra => 1.5,
+            Self::Toxic => 1.5,
+        }
+    }
+
+    /// Between 0 for freezing and 1 for hot.
+    pub fn temperature(self) -> f32 {
+        match self {
+            Self::Grassland => 0.5,
+            Self::Desert => 0.9,
+            Self::Swamp => 0.6,
+            Self::Tundra => 0.1,
+            Self::Toxic => 0.7,
+        }
+    }
+
+    /// Tint of the grass tiles.
+    pub fn color(self) -> Color {
+        match self {
+            Self::Grassland => Color::rgb(0.1, 0.4, 0.1),
+            Self::Desert => Color::rgb(0.6, 0.5, 0.25),
+            Self::Swamp => Color::rgb(0.15, 0.25, 0.15),
+            Self::Tundra => Color::rgb(0.6, 0.65, 0.7),
+            Self::Toxic => Color::rgb(0.35, 0.45, 0.05),
+        }
+    }
+
+    /// Between 0 and 1, scales the damage to corgis without toxin resistance.
+    pub fn toxicity(self) -> f32 {
+        match self {
+            Self::Toxic => 1.0,
+            Self::Grassland | Self::Desert | Self::Swamp | Self::Tundra => 0.0,
+        }
+    }
+}
+
+/// Seeded 2D Perlin noise.
+pub struct Perlin {
+    permutation: [u8; 512],
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut values: Vec<u8> = (0..=255).collect();
+        values.shuffle(&mut StdRng::seed_from_u64(seed));
+        let mut permutation = [0; 512];
+        for (i, value) in permutation.iter_mut().enumerate() {
+            *value = values[i % 256];
+        }
+        Self { permutation }
+    }
+
+    fn hash(&self, x: usize, y: usize) -> u8 {
+        self.permutation[self.permutation[x & 255] as usize + (y & 255)]
+    }
+
+    /// The dot product of a pseudo random gradient and the offset to the corner.
+    fn gradient(hash: u8, x: f32, y: f32) -> f32 {
+        match hash & 7 {
+            0 => x + y,
+            1 => x - y,
+            2 => -x + y,
+            3 => -x - y,
+            4 => x,
+            5 => -x,
+            6 => y,
+            _ => -y,
+        }
+    }
+
+    /// Roughly between -1 and 1, 0 at every integer point.
+    pub fn get(&self, x: f32, y: f32) -> f32 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (dx, dy) = (x - x0, y - y0);
+        // the permutation wraps, so negative coordinates work too
+        let (ix, iy) = (x0 as i64 as usize, y0 as i64 as usize);
+        let fade = |t: f32| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+
+        let corner = |cx: usize, cy: usize, ox: f32, oy: f32| {
+            Self::gradient(
+                self.hash(ix.wrapping_add(cx), iy.wrapping_add(cy)),
+                dx - ox,
+                dy - oy,
+            )
+        };
+        let (u, v) = (fade(dx), fade(dy));
+        lerp(
+            lerp(corner(0, 0, 0.0, 0.0), corner(1, 0, 1.0, 0.0), u),
+            lerp(corner(0, 1, 0.0, 1.0), corner(1, 1, 1.0, 1.0), u),
+            v,
+        )
+    }
+
+    /// Fractal noise of several octaves, between 0 and 1.
+    /// `scale` is the size of the biggest features in noise units.
+    pub fn fractal(&self, x: f32, y: f32, scale: f32) -> f32 {
+        let (mut frequency, mut amplitude) = (1.0 / scale.max(f32::EPSILON), 1.0);
+        let (mut sum, mut total) = (0.0, 0.0);
+        for _ in 0..OCTAVES {
+            sum += amplitude * self.get(x * frequency, y * frequency);
+            total += amplitude;
+            frequency *= LACUNARITY;
+            amplitude *= PERSISTENCE;
+        }
+        (0.5 + 0.5 * sum / total).max(0.0).min(1.0)
+    }
+}
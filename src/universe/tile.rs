@@ -0,0 +1,1001 @@
+use super::{
+    scenario::Scenario,
+    terrain::{Biome, Perlin},
+    weather::Weather,
+    Daylight, Light, Season, Tick, Universe,
+};
+use crate::{
+    config::SimConfig,
+    corgi::{Corgi, Energy, CORGI_ENERGY_CAPACITY},
+    debug::EnergyLedger,
+    genes::BodyGene,
+    pheromone::PheromoneField,
+    rng::SimRng,
+    scent::ScentField,
+    territory::{Team, TERRITORY_TINT},
+};
+use bevy::{
+    app::startup_stage,
+    prelude::*,
+    render::texture::{Extent3d, FilterMode, TextureDimension, TextureFormat},
+};
+use bevy_rapier2d::{
+    na::{Isometry2, Vector2},
+    physics::RigidBodyHandleComponent,
+    rapier::{
+        dynamics::{RigidBody, RigidBodyBuilder, RigidBodySet},
+        geometry::ColliderBuilder,
+    },
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
+
+/// Side length of a chunk of the `TileGrid` in tiles.
+/// A 512 x 512 map needs 64 sprites, a changed chunk uploads 16 KiB.
+pub const CHUNK_SIZE: usize = 64;
+
+/// Energy gained per tick on a spring.
+const SPRING_ENERGY: f32 = 0.5;
+/// Minimum distance in tiles between the two ends of a portal.
+const PORTAL_MIN_DISTANCE: f32 = 10.0;
+/// Fraction of the velocity a corgi without any swimming efficiency loses per tick in water.
+const WATER_DRAG: f32 = 0.1;
+/// Energy a corgi without any swimming efficiency loses per tick in water.
+const WATER_ENERGY_COST: f32 = 0.1;
+/// Length range in tiles of every leg of a wall structure.
+const WALL_LENGTH: (usize, usize) = (3, 10);
+/// Probability of a wall structure bending once, which creates a sheltered corner.
+const WALL_BEND_PROBABILITY: f64 = 0.5;
+/// Ticks until a corgi can use a portal again.
+const PORTAL_COOLDOWN: usize = 60;
+/// Food energy the most fertile grass tile can hold.
+const FOOD_CAPACITY: f32 = 20.0;
+/// Tiles with a fertility below this are barren and grow no food.
+const BARREN_FERTILITY: f32 = 0.35;
+/// The regrowth never stops completely, even on a grazed down tile.
+const FOOD_REGROWTH_SEED: f32 = 0.5;
+/// Depletion per grazed food energy, overgrazed tiles regrow slower for a long time.
+const GRAZING_DEPLETION: f32 = 0.002;
+/// Depletion a tile recovers per tick.
+const DEPLETION_RECOVERY: f32 = 0.0001;
+/// Upper bound of the depletion, so even overgrazed tiles regrow a little.
+const MAX_DEPLETION: f32 = 0.9;
+/// Regrowth factor without light exposure, the exposure is added to it.
+const LIGHT_REGROWTH_BASE: f32 = 0.5;
+/// Energy it costs to climb from the lowest to the highest elevation.
+const CLIMB_ENERGY: f32 = 20.0;
+/// Fraction of the climbing energy a corgi gets back when it descends again.
+const DESCENT_REFUND: f32 = 0.5;
+/// How much darker the lowest tiles are than the highest ones.
+const ELEVATION_SHADING: f32 = 0.4;
+/// Brightness of the tiles at midnight, relative to noon.
+const NIGHT_BRIGHTNESS: f32 = 0.4;
+/// Brightness of a tile without any food, relative to a full one of the same type.
+const BARREN_BRIGHTNESS: f32 = 0.3;
+/// Cycles through the overlays.
+const OVERLAY_KEY: KeyCode = KeyCode::O;
+/// Corgis on a tile at which the density overlay is saturated.
+const DENSITY_SATURATION: f32 = 4.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TileType {
+    Grass,
+    Spring,
+    Portal,
+    /// Impassable, blocks movement and vision.
+    Wall,
+    /// Slows down and exhausts corgis, unless they are good swimmers.
+    Water,
+}
+
+impl TileType {
+    pub fn color(self) -> Color {
+        match self {
+            Self::Grass => Color::rgb(0.1, 0.4, 0.1),
+            Self::Spring => Color::rgb(0.2, 0.5, 0.9),
+            Self::Portal => Color::rgb(0.6, 0.1, 0.8),
+            Self::Wall => Color::rgb(0.35, 0.3, 0.3),
+            Self::Water => Color::rgb(0.1, 0.2, 0.55),
+        }
+    }
+
+    /// Food energy a tile of this type with `fertility` between 0 and 1 regrows to.
+    pub fn food_capacity(self, fertility: f32) -> f32 {
+        match self {
+            Self::Grass => {
+                FOOD_CAPACITY * ((fertility - BARREN_FERTILITY) / (1.0 - BARREN_FERTILITY)).max(0.0)
+            }
+            Self::Spring | Self::Portal | Self::Wall | Self::Water => 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Tile {
+    pub tile_type: TileType,
+    pub pos: TilePos,
+    pub biome: Biome,
+    /// Between 0 in the valleys and 1 on the peaks.
+    pub elevation: f32,
+    /// Food energy, between 0 and `capacity`.
+    pub energy: f32,
+    pub capacity: f32,
+    /// Fraction by which grazing reduced the regrowth, recovers slowly.
+    pub depletion: f32,
+    /// The team which captured the tile last.
+    pub owner: Option<Team>,
+}
+
+impl Tile {
+    pub fn new(
+        tile_type: TileType,
+        pos: TilePos,
+        biome: Biome,
+        elevation: f32,
+        fertility: f32,
+    ) -> Self {
+        let capacity = tile_type.food_capacity(fertility);
+        Self {
+            tile_type,
+            pos,
+            biome,
+            elevation,
+            energy: capacity,
+            capacity,
+            depletion: 0.0,
+            owner: None,
+        }
+    }
+
+    /// Removes up to `amount` food and returns the removed amount.
+    /// Depletes the tile, so it regrows slower afterwards.
+    pub fn graze(&mut self, amount: f32) -> f32 {
+        let grazed = amount.min(self.energy);
+        self.energy -= grazed;
+        self.depletion = (self.depletion + grazed * GRAZING_DEPLETION).min(MAX_DEPLETION);
+        grazed
+    }
+
+    /// Removes all food and depletes the tile as far as possible.
+    pub fn sterilize(&mut self) {
+        self.energy = 0.0;
+        self.depletion = MAX_DEPLETION;
+    }
+
+    /// Grass is tinted by the biome and its owner, the special tiles have their own colors.
+    pub fn base_color(&self) -> Color {
+        match (self.tile_type, self.owner) {
+            (TileType::Grass, None) => self.biome.color(),
+            (TileType::Grass, Some(team)) => {
+                self.biome.color() * (1.0 - TERRITORY_TINT) + team.color() * TERRITORY_TINT
+            }
+            (tile_type, _) => tile_type.color(),
+        }
+    }
+
+    /// The color on the map: grazed and lower tiles are darker.
+    pub fn display_color(&self) -> Color {
+        let food = BARREN_BRIGHTNESS + (1.0 - BARREN_BRIGHTNESS) * self.food_fraction();
+        let relief = 1.0 - ELEVATION_SHADING * (1.0 - self.elevation);
+        self.base_color() * food * relief
+    }
+
+    /// Between 0 for freezing and 1 for hot, changes with the seasons.
+    pub fn temperature(&self, season: &Season) -> f32 {
+        (self.biome.temperature() + season.temperature_offset())
+            .max(0.0)
+            .min(1.0)
+    }
+
+    /// The food relative to the most fertile tile of the same type, between 0 and 1.
+    /// 1 for tile types which never hold food.
+    pub fn food_fraction(&self) -> f32 {
+        let max = self.tile_type.food_capacity(1.0);
+        if max > 0.0 {
+            self.energy / max
+        } else {
+            1.0
+        }
+    }
+
+    /// The food fraction as it is rendered, the chunk only has to be rendered again if it changes.
+    fn food_shade(&self) -> u8 {
+        (self.food_fraction().max(0.0).min(1.0) * 255.0).round() as u8
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TilePos {
+    pub x: usize,
+    pub y: usize,
+}
+
+impl TilePos {
+    pub fn center(self, tile_size: f32) -> Vec2 {
+        Vec2::new(self.x as f32 + 0.5, self.y as f32 + 0.5) * tile_size
+    }
+}
+
+/// A block of at most `CHUNK_SIZE` x `CHUNK_SIZE` tiles, rendered as one sprite.
+pub struct Chunk {
+    /// The lower left tile.
+    origin: TilePos,
+    width: usize,
+    height: usize,
+    tiles: Vec<Tile>,
+    /// Whether a tile changed since the chunk was rendered last.
+    dirty: bool,
+}
+
+impl Chunk {
+    pub fn tiles(&self) -> &[Tile] {
+        &self.tiles
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Writes the colors of the tiles as RGBA pixels, the top row first.
+    fn render<F: Fn(&Tile) -> Color>(&self, pixels: &mut [u8], color: F) {
+        let channel = |value: f32| (value.max(0.0).min(1.0) * 255.0).round() as u8;
+        for (i, tile) in self.tiles.iter().enumerate() {
+            let (x, y) = (i % self.width, i / self.width);
+            let offset = ((self.height - 1 - y) * self.width + x) * 4;
+            let color = color(tile);
+            pixels[offset..offset + 4].copy_from_slice(&[
+                channel(color.r()),
+                channel(color.g()),
+                channel(color.b()),
+                255,
+            ]);
+        }
+    }
+}
+
+/// All tiles of the universe, stored in chunks.
+/// Mutable access marks the chunks dirty, so only changed chunks get rendered again,
+/// and there is only one entity per chunk instead of one per tile.
+#[derive(Default)]
+pub struct TileGrid {
+    width: usize,
+    height: usize,
+    /// Side length of a tile in pixels, the one of the universe.
+    tile_size: f32,
+    /// Chunks per row.
+    columns: usize,
+    chunks: Vec<Chunk>,
+    /// Both ends of every portal.
+    portals: Vec<(TilePos, TilePos)>,
+}
+
+impl TileGrid {
+    /// A grid of the size of `universe`, each tile created by `tile`.
+    pub fn new<F: FnMut(TilePos) -> Tile>(universe: &Universe, mut tile: F) -> Self {
+        let (width, height) = (universe.width_tiles, universe.height_tiles);
+        let columns = (width + CHUNK_SIZE - 1) / CHUNK_SIZE;
+        let rows = (height + CHUNK_SIZE - 1) / CHUNK_SIZE;
+        let mut chunks = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                let origin = TilePos {
+                    x: column * CHUNK_SIZE,
+                    y: row * CHUNK_SIZE,
+                };
+                let chunk_width = CHUNK_SIZE.min(width - origin.x);
+                let chunk_height = CHUNK_SIZE.min(height - origin.y);
+                let mut tiles = Vec::with_capacity(chunk_width * chunk_height);
+                for y in origin.y..origin.y + chunk_height {
+                    for x in origin.x..origin.x + chunk_width {
+                        tiles.push(tile(TilePos { x, y }));
+                    }
+                }
+                chunks.push(Chunk {
+                    origin,
+                    width: chunk_width,
+                    height: chunk_height,
+                    tiles,
+                    dirty: true,
+                });
+            }
+        }
+        Self {
+            width,
+            height,
+            tile_size: universe.tile_size,
+            columns,
+            chunks,
+            portals: Vec::new(),
+        }
+    }
+
+    /// A grid from the tiles row by row, e.g. of a snapshot.
+    /// `None` if the number of tiles doesn't match the size of `universe`.
+    pub fn from_tiles(universe: &Universe, tiles: &[Tile]) -> Option<Self> {
+        let width = universe.width_tiles;
+        if tiles.len() != width * universe.height_tiles {
+            return None;
+        }
+        Some(Self::new(universe, |pos| {
+            tiles[pos.y * width + pos.x].clone()
+        }))
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn tile_size(&self) -> f32 {
+        self.tile_size
+    }
+
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.chunks
+    }
+
+    pub fn portals(&self) -> &[(TilePos, TilePos)] {
+        &self.portals
+    }
+
+    pub fn set_portals(&mut self, portals: Vec<(TilePos, TilePos)>) {
+        self.portals = portals;
+    }
+
+    /// The index of the chunk containing `pos` and the index of the tile in it.
+    fn locate(&self, pos: TilePos) -> Option<(usize, usize)> {
+        if pos.x >= self.width || pos.y >= self.height {
+            return None;
+        }
+        let index = (pos.y / CHUNK_SIZE) * self.columns + pos.x / CHUNK_SIZE;
+        let chunk = &self.chunks[index];
+        Some((
+            index,
+            (pos.y - chunk.origin.y) * chunk.width + pos.x - chunk.origin.x,
+        ))
+    }
+
+    pub fn get(&self, pos: TilePos) -> Option<&Tile> {
+        let (chunk, tile) = self.locate(pos)?;
+        Some(&self.chunks[chunk].tiles[tile])
+    }
+
+    /// Marks the chunk of the tile dirty.
+    pub fn get_mut(&mut self, pos: TilePos) -> Option<&mut Tile> {
+        let (chunk, tile) = self.locate(pos)?;
+        let chunk = &mut self.chunks[chunk];
+        chunk.dirty = true;
+        Some(&mut chunk.tiles[tile])
+    }
+
+    /// The tile containing the world position `pos`.
+    pub fn at(&self, pos: Vec2) -> Option<&Tile> {
+        if pos.x < 0.0 || pos.y < 0.0 {
+            return None;
+        }
+        self.get(TilePos {
+            x: (pos.x / self.tile_size) as usize,
+            y: (pos.y / self.tile_size) as usize,
+        })
+    }
+
+    /// Adds `amount` to the food of the tile at the world position `pos`, up to its capacity.
+    /// Returns the amount which didn't fit.
+    pub fn fertilize(&mut self, pos: Vec2, amount: f32) -> f32 {
+        let tile = match self.at(pos).map(|tile| tile.pos) {
+            Some(tile) => tile,
+            None => return amount,
+        };
+        match self.get_mut(tile) {
+            Some(tile) => {
+                let before = tile.energy;
+                tile.energy = (tile.energy + amount).min(tile.capacity);
+                amount - (tile.energy - before)
+            }
+            None => amount,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Tile> {
+        self.chunks.iter().flat_map(|chunk| chunk.tiles.iter())
+    }
+
+    /// Runs `update` on every tile, which returns whether it changed the tile.
+    /// Only the chunks with changed tiles are marked dirty.
+    pub fn update<F: FnMut(&mut Tile) -> bool>(&mut self, mut update: F) {
+        for chunk in self.chunks.iter_mut() {
+            let mut changed = false;
+            for tile in chunk.tiles.iter_mut() {
+                changed |= update(tile);
+            }
+            chunk.dirty |= changed;
+        }
+    }
+}
+
+/// A heatmap replacing the colors of the tiles, cycled with `OVERLAY_KEY`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overlay {
+    None,
+    Food,
+    Scent,
+    Pheromone,
+    Density,
+    Temperature,
+}
+
+impl Default for Overlay {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl Overlay {
+    fn next(self) -> Self {
+        match self {
+            Self::None => Self::Food,
+            Self::Food => Self::Scent,
+            Self::Scent => Self::Pheromone,
+            Self::Pheromone => Self::Density,
+            Self::Density => Self::Temperature,
+            Self::Temperature => Self::None,
+        }
+    }
+}
+
+/// Black over red to yellow for values between 0 and 1.
+fn heat(value: f32) -> Color {
+    let value = value.max(0.0).min(1.0);
+    Color::rgb((2.0 * value).min(1.0), (2.0 * value - 1.0).max(0.0), 0.0)
+}
+
+/// The sprite showing the chunk `index` of the `TileGrid`.
+pub struct ChunkSprite {
+    index: usize,
+    texture: Handle<Texture>,
+}
+
+/// The static collider of a wall tile.
+pub struct WallCollider;
+
+/// Everything a `TileBehavior` can act on.
+pub struct TileContext<'a> {
+    pub tick: usize,
+    pub tile: TilePos,
+    pub corgi: Entity,
+    pub energy: &'a mut Energy,
+    pub body: &'a mut RigidBody,
+}
+
+/// Custom behavior of a tile type, triggered by the corgis on it.
+pub trait TileBehavior: Send + Sync + 'static {
+    fn on_enter(&self, _context: &mut TileContext) {}
+    fn on_leave(&self, _context: &mut TileContext) {}
+    fn on_tick(&self, _context: &mut TileContext) {}
+}
+
+/// The behaviors of the tile types, executed by `tile_interactions`.
+#[derive(Default)]
+pub struct TileBehaviors {
+    behaviors: HashMap<TileType, Box<dyn TileBehavior>>,
+}
+
+impl TileBehaviors {
+    /// Replaces the previous behavior of `tile_type`.
+    pub fn register<B: TileBehavior>(&mut self, tile_type: TileType, behavior: B) {
+        self.behaviors.insert(tile_type, Box::new(behavior));
+    }
+
+    pub fn get(&self, tile_type: TileType) -> Option<&dyn TileBehavior> {
+        self.behaviors.get(&tile_type).map(|b| b.as_ref())
+    }
+}
+
+/// The tile a corgi stood on in the last tick.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct OnTile(pub Option<TilePos>);
+
+struct HealingSpring;
+
+impl TileBehavior for HealingSpring {
+    fn on_tick(&self, context: &mut TileContext) {
+        context.energy.add(SPRING_ENERGY);
+    }
+}
+
+/// Moves corgis entering a portal to the other end of it.
+#[derive(Default)]
+struct Portals {
+    links: HashMap<TilePos, TilePos>,
+    tile_size: f32,
+    /// The tick of the last teleport of every corgi.
+    last_used: Mutex<HashMap<Entity, usize>>,
+}
+
+impl Portals {
+    fn new(pairs: &[(TilePos, TilePos)], tile_size: f32) -> Self {
+        let mut portals = Self {
+            tile_size,
+            ..Self::default()
+        };
+        for &(a, b) in pairs {
+            portals.links.insert(a, b);
+            portals.links.insert(b, a);
+        }
+        portals
+    }
+}
+
+impl TileBehavior for Portals {
+    fn on_enter(&self, context: &mut TileContext) {
+        let target = match self.links.get(&context.tile) {
+            Some(target) => target.center(self.tile_size),
+            None => return,
+        };
+        let mut last_used = self.last_used.lock().unwrap();
+        if let Some(&tick) = last_used.get(&context.corgi) {
+            if context.tick < tick + PORTAL_COOLDOWN {
+                return;
+            }
+        }
+        last_used.retain(|_, tick| context.tick < *tick + PORTAL_COOLDOWN);
+        last_used.insert(context.corgi, context.tick);
+
+        let rotation = context.body.position().rotation.angle();
+        context.body.set_position(
+            Isometry2::new(Vector2::new(target.x, target.y), rotation),
+            true,
+        );
+    }
+}
+
+pub struct TilePlugin;
+
+impl Plugin for TilePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let mut behaviors = TileBehaviors::default();
+        behaviors.register(TileType::Spring, HealingSpring);
+
+        app.add_resource(TileGrid::default())
+            .add_resource(Overlay::default())
+            .add_resource(behaviors)
+            .add_startup_system_to_stage(startup_stage::PRE_STARTUP, apply_config.system())
+            .add_startup_system(create_tiles.system())
+            .add_system(tile_interactions.system())
+            .add_system(regrow_food.system())
+            .add_system(swim.system())
+            .add_system(climb.system())
+            // the overlays also work while paused
+            .add_system_to_stage(stage::POST_UPDATE, cycle_overlay.system())
+            .add_system_to_stage(stage::POST_UPDATE, render_chunks.system());
+    }
+
+    fn name(&self) -> &str {
+        "TilePlugin"
+    }
+}
+
+fn random_tile<R: Rng>(rng: &mut R, universe: &Universe) -> TilePos {
+    TilePos {
+        x: rng.gen_range(0..universe.width_tiles),
+        y: rng.gen_range(0..universe.height_tiles),
+    }
+}
+
+/// Straight walls, half of them with a bend, starting at random tiles.
+/// Walls are cut off at the edges of a bounded universe and wrap around in a toroidal one.
+fn generate_walls<R: Rng>(rng: &mut R, count: usize, universe: &Universe) -> HashSet<TilePos> {
+    let directions = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+    let mut walls = HashSet::new();
+    for _ in 0..count {
+        let mut tile = Some(random_tile(rng, universe));
+        let (mut dx, mut dy) = directions[rng.gen_range(0..directions.len())];
+        let legs = if rng.gen_bool(WALL_BEND_PROBABILITY) {
+            2
+        } else {
+            1
+        };
+        for _ in 0..legs {
+            for _ in 0..rng.gen_range(WALL_LENGTH.0..=WALL_LENGTH.1) {
+                let pos = match tile {
+                    Some(pos) => pos,
+                    None => break,
+                };
+                walls.insert(pos);
+                tile = universe.step(pos, dx, dy);
+            }
+            // turn by 90 degrees
+            let turned = if rng.gen() { (dy, -dx) } else { (-dy, dx) };
+            dx = turned.0;
+            dy = turned.1;
+        }
+    }
+    walls
+}
+
+/// Pairs of distant tiles, every tile is used at most once.
+fn generate_portals<R: Rng>(
+    rng: &mut R,
+    pairs: usize,
+    universe: &Universe,
+) -> Vec<(TilePos, TilePos)> {
+    let mut portals: Vec<(TilePos, TilePos)> = Vec::new();
+    for _ in 0..pairs {
+        let a = random_tile(rng, universe);
+        let b = random_tile(rng, universe);
+        let distance = Vec2::new(a.x as f32 - b.x as f32, a.y as f32 - b.y as f32).length();
+        let used = |pos: TilePos| portals.iter().any(|&(a, b)| a == pos || b == pos);
+        if distance >= PORTAL_MIN_DISTANCE && !used(a) && !used(b) {
+            portals.push((a, b));
+        }
+    }
+    portals
+}
+
+/// The universe size of the config overrides the one of the initial scenario.
+fn apply_config(config: Res<SimConfig>, mut scenario: ResMut<Scenario>) {
+    if let Some(width) = config.width_tiles {
+        scenario.width_tiles = width;
+    }
+    if let Some(height) = config.height_tiles {
+        scenario.height_tiles = height;
+    }
+}
+
+/// The initial universe comes from the scenario, its tile size from the config.
+#[allow(clippy::too_many_arguments)]
+pub fn create_tiles(
+    commands: &mut Commands,
+    config: Res<SimConfig>,
+    scenario: Res<Scenario>,
+    mut universe: ResMut<Universe>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut textures: ResMut<Assets<Texture>>,
+    mut grid: ResMut<TileGrid>,
+    mut behaviors: ResMut<TileBehaviors>,
+    mut rng: ResMut<SimRng>,
+) {
+    *universe = scenario.universe(config.tile_size);
+    spawn_tiles(
+        commands,
+        &scenario,
+        &universe,
+        &mut materials,
+        &mut textures,
+        &mut grid,
+        &mut behaviors,
+        &mut rng,
+    );
+}
+
+/// Generates the tiles of `scenario` filling `universe`.
+/// The fertility comes from seeded fractal noise, so there are fertile and barren regions.
+/// The biomes come from two more layers for temperature and moisture,
+/// the wettest tiles are water. The elevation comes from a fourth layer.
+/// Walls get static colliders, so the physics keeps the corgis out of them.
+/// The previous chunk sprites and wall colliders have to be despawned already.
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_tiles(
+    commands: &mut Commands,
+    scenario: &Scenario,
+    universe: &Universe,
+    materials: &mut Assets<ColorMaterial>,
+    textures: &mut Assets<Texture>,
+    grid: &mut TileGrid,
+    behaviors: &mut TileBehaviors,
+    rng: &mut SimRng,
+) {
+    let portals = generate_portals(rng, scenario.portal_pairs, universe);
+    let is_portal = |pos: &TilePos| portals.iter().any(|(a, b)| a == pos || b == pos);
+    let walls = generate_walls(rng, scenario.wall_count, universe);
+    let seed = scenario.terrain_seed.unwrap_or_else(|| rng.gen());
+    let terrain = Perlin::new(seed);
+    let temperature = Perlin::new(seed.wrapping_add(1));
+    let moisture = Perlin::new(seed.wrapping_add(2));
+    let relief = Perlin::new(seed.wrapping_add(3));
+    *grid = TileGrid::new(universe, |pos| {
+        let (fx, fy) = (pos.x as f32, pos.y as f32);
+        let wetness = moisture.fractal(fx, fy, scenario.biome_scale);
+        let warmth = temperature.fractal(fx, fy, scenario.biome_scale);
+        let biome = Biome::from_climate(warmth, wetness);
+        let tile_type = if is_portal(&pos) {
+            TileType::Portal
+        } else if walls.contains(&pos) {
+            TileType::Wall
+        } else if wetness > scenario.water_level {
+            TileType::Water
+        } else if rng.gen_bool(scenario.spring_probability) {
+            TileType::Spring
+        } else {
+            TileType::Grass
+        };
+        let fertility = terrain.fractal(fx, fy, scenario.terrain_scale);
+        let elevation = relief.fractal(fx, fy, scenario.elevation_scale);
+        Tile::new(tile_type, pos, biome, elevation, fertility)
+    });
+    grid.set_portals(portals);
+    spawn_tile_entities(commands, materials, textures, grid, behaviors);
+}
+
+/// Spawns the sprites of the chunks and the colliders of the walls of `grid`
+/// and links its portals.
+/// Every chunk of the grid gets a sprite with its own texture.
+pub fn spawn_tile_entities(
+    commands: &mut Commands,
+    materials: &mut Assets<ColorMaterial>,
+    textures: &mut Assets<Texture>,
+    grid: &TileGrid,
+    behaviors: &mut TileBehaviors,
+) {
+    for (index, chunk) in grid.chunks().iter().enumerate() {
+        let mut texture = Texture::new_fill(
+            Extent3d::new(chunk.width as u32, chunk.height as u32, 1),
+            TextureDimension::D2,
+            &[0, 0, 0, 255],
+            TextureFormat::Rgba8UnormSrgb,
+        );
+        // one texel per tile, the tiles have sharp edges instead of blurring into each other
+        texture.sampler.mag_filter = FilterMode::Nearest;
+        texture.sampler.min_filter = FilterMode::Nearest;
+        let texture = textures.add(texture);
+        let size = Vec2::new(chunk.width as f32, chunk.height as f32) * grid.tile_size;
+        let corner = Vec2::new(chunk.origin.x as f32, chunk.origin.y as f32) * grid.tile_size;
+        commands
+            .spawn(SpriteBundle {
+                sprite: Sprite::new(size),
+                material: materials.add(ColorMaterial::texture(texture.clone())),
+                transform: Transform::from_translation((corner + size / 2.0).extend(-1.0)),
+                ..Default::default()
+            })
+            .with(ChunkSprite { index, texture });
+    }
+    for tile in grid.iter().filter(|tile| tile.tile_type == TileType::Wall) {
+        let center = tile.pos.center(grid.tile_size);
+        let half_size = grid.tile_size / 2.0;
+        commands
+            .spawn((WallCollider,))
+            .with(RigidBodyBuilder::new_static().translation(center.x, center.y))
+            .with(ColliderBuilder::cuboid(half_size, half_size));
+    }
+    behaviors.register(
+        TileType::Portal,
+        Portals::new(grid.portals(), grid.tile_size),
+    );
+}
+
+/// Runs the `TileBehavior`s of the tiles the corgis enter, leave and stand on.
+pub fn tile_interactions(
+    tick: Res<Tick>,
+    behaviors: Res<TileBehaviors>,
+    universe: Res<Universe>,
+    grid: Res<TileGrid>,
+    ledger: Res<EnergyLedger>,
+    mut bodies: ResMut<RigidBodySet>,
+    mut corgis: Query<
+        (
+            Entity,
+            &Transform,
+            &RigidBodyHandleComponent,
+            &mut Energy,
+            &mut OnTile,
+        ),
+        With<Corgi>,
+    >,
+) {
+    let tile_type = |pos: TilePos| grid.get(pos).map(|t| t.tile_type);
+    for (corgi, transform, handle, mut energy, mut on_tile) in corgis.iter_mut() {
+        let body = match bodies.get_mut(handle.handle()) {
+            Some(body) => body,
+            None => continue,
+        };
+        let current = universe.tile_at(transform.translation.truncate());
+        let previous = on_tile.0;
+        on_tile.0 = current;
+        let before = energy.get();
+
+        let mut run = |tile: TilePos, hook: fn(&dyn TileBehavior, &mut TileContext)| {
+            if let Some(behavior) = tile_type(tile).and_then(|t| behaviors.get(t)) {
+                hook(
+                    behavior,
+                    &mut TileContext {
+                        tick: tick.0,
+                        tile,
+                        corgi,
+                        energy: &mut *energy,
+                        body: &mut *body,
+                    },
+                );
+            }
+        };
+
+        if previous != current {
+            if let Some(tile) = previous {
+                run(tile, |b, c| b.on_leave(c));
+            }
+            if let Some(tile) = current {
+                run(tile, |b, c| b.on_enter(c));
+            }
+        }
+        if let Some(tile) = current {
+            run(tile, |b, c| b.on_tick(c));
+        }
+        // the behaviors are free to create or destroy energy
+        ledger.source(energy.get() - before);
+    }
+}
+
+/// Logistic regrowth towards the capacity of every tile,
+/// as fast as the biome, season, weather, light exposure and depletion allow.
+/// The depletion recovers meanwhile.
+fn regrow_food(
+    config: Res<SimConfig>,
+    season: Res<Season>,
+    weather: Res<Weather>,
+    light: Res<Light>,
+    ledger: Res<EnergyLedger>,
+    mut grid: ResMut<TileGrid>,
+) {
+    let mut grown = 0.0;
+    let tile_size = grid.tile_size;
+    grid.update(|tile| {
+        tile.depletion = (tile.depletion - DEPLETION_RECOVERY).max(0.0);
+        if tile.energy >= tile.capacity {
+            return false;
+        }
+        let saturation = 1.0 - tile.energy / tile.capacity;
+        let rate = config.food_regrowth_rate
+            * tile.biome.regrowth()
+            * season.regrowth()
+            * weather.regrowth()
+            * (LIGHT_REGROWTH_BASE + light.exposure(tile.pos.center(tile_size)))
+            * (1.0 - tile.depletion);
+        let growth = rate * tile.energy.max(FOOD_REGROWTH_SEED) * saturation;
+        let shade = tile.food_shade();
+        let before = tile.energy;
+        tile.energy = (tile.energy + growth).min(tile.capacity);
+        grown += tile.energy - before;
+        // most ticks the growth is too small to be visible
+        tile.food_shade() != shade
+    });
+    ledger.source(grown);
+}
+
+/// Water slows the corgis down and costs energy, both less for good swimmers.
+fn swim(
+    grid: Res<TileGrid>,
+    ledger: Res<EnergyLedger>,
+    mut bodies: ResMut<RigidBodySet>,
+    mut corgis: Query<(&OnTile, &RigidBodyHandleComponent, &BodyGene, &mut Energy)>,
+) {
+    for (on_tile, handle, body_gene, mut energy) in corgis.iter_mut() {
+        let in_water = on_tile
+            .0
+            .and_then(|pos| grid.get(pos))
+            .map_or(false, |tile| tile.tile_type == TileType::Water);
+        if !in_water {
+            continue;
+        }
+        let clumsiness = 1.0 - body_gene.swimming;
+        ledger.sink(energy.sub(WATER_ENERGY_COST * clumsiness));
+        if let Some(body) = bodies.get_mut(handle.handle()) {
+            let velocity = *body.linvel() * (1.0 - WATER_DRAG * clumsiness);
+            body.set_linvel(velocity, true);
+        }
+    }
+}
+
+/// Corgis pay energy for every climbed elevation and get some of it back when descending.
+fn climb(
+    grid: Res<TileGrid>,
+    ledger: Res<EnergyLedger>,
+    mut elevations: Local<HashMap<Entity, f32>>,
+    mut corgis: Query<(Entity, &Transform, &mut Energy), With<Corgi>>,
+) {
+    let mut current = HashMap::with_capacity(elevations.len());
+    for (entity, transform, mut energy) in corgis.iter_mut() {
+        let elevation = match grid.at(transform.translation.truncate()) {
+            Some(tile) => tile.elevation,
+            None => continue,
+        };
+        if let Some(previous) = elevations.get(&entity) {
+            let rise = elevation - previous;
+            if rise > 0.0 {
+                ledger.sink(energy.sub(rise * CLIMB_ENERGY));
+            } else {
+                ledger.source(energy.add(-rise * CLIMB_ENERGY * DESCENT_REFUND));
+            }
+        }
+        current.insert(entity, elevation);
+    }
+    // forgets the despawned corgis
+    *elevations = current;
+}
+
+fn cycle_overlay(
+    keys: Res<Input<KeyCode>>,
+    mut overlay: ResMut<Overlay>,
+    mut grid: ResMut<TileGrid>,
+) {
+    if keys.just_pressed(OVERLAY_KEY) {
+        *overlay = overlay.next();
+        // the regular colors have to be rendered again
+        for chunk in grid.chunks.iter_mut() {
+            chunk.dirty = true;
+        }
+        info!("overlay: {:?}", *overlay);
+    }
+}
+
+/// Draws the dirty chunks into their textures with `Tile::display_color`.
+/// The night and the weather tint the whole material instead.
+/// An overlay redraws every chunk every frame, untinted.
+#[allow(clippy::too_many_arguments)]
+fn render_chunks(
+    daylight: Res<Daylight>,
+    weather: Res<Weather>,
+    season: Res<Season>,
+    overlay: Res<Overlay>,
+    scent: Res<ScentField>,
+    pheromones: Res<PheromoneField>,
+    mut grid: ResMut<TileGrid>,
+    mut textures: ResMut<Assets<Texture>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    sprites: Query<(&ChunkSprite, &Handle<ColorMaterial>)>,
+    corgis: Query<&Transform, With<Corgi>>,
+) {
+    let mut density = HashMap::new();
+    if *overlay == Overlay::Density {
+        for transform in corgis.iter() {
+            if let Some(tile) = grid.at(transform.translation.truncate()) {
+                *density.entry(tile.pos).or_insert(0) += 1;
+            }
+        }
+    }
+    let tile_size = grid.tile_size;
+    let color = |tile: &Tile| match *overlay {
+        Overlay::None => tile.display_color(),
+        Overlay::Food => heat(tile.energy / FOOD_CAPACITY),
+        Overlay::Scent => {
+            let concentration = scent.concentration(tile.pos.center(tile_size));
+            heat(concentration / (1.0 + concentration))
+        }
+        Overlay::Pheromone => {
+            let concentration = pheromones.concentration(tile.pos.center(tile_size));
+            heat(concentration / (1.0 + concentration))
+        }
+        Overlay::Density => heat(*density.get(&tile.pos).unwrap_or(&0) as f32 / DENSITY_SATURATION),
+        Overlay::Temperature => heat(tile.temperature(&season)),
+    };
+
+    let light = NIGHT_BRIGHTNESS + (1.0 - NIGHT_BRIGHTNESS) * daylight.0;
+    let tint = match *overlay {
+        Overlay::None => weather.kind.tint() * light,
+        _ => Color::WHITE,
+    };
+    for (sprite, material) in sprites.iter() {
+        // mutable access uploads the material again, even without a change
+        if materials
+            .get(material)
+            .map_or(false, |material| material.color != tint)
+        {
+            if let Some(material) = materials.get_mut(material) {
+                material.color = tint;
+            }
+        }
+        let chunk = match grid.chunks.get_mut(sprite.index) {
+            Some(chunk) if chunk.dirty || *overlay != Overlay::None => chunk,
+            _ => continue,
+        };
+        if let Some(texture) = textures.get_mut(&sprite.texture) {
+            chunk.render(&mut texture.data, &color);
+            chunk.dirty = false;
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use super::{scenario::Scenario, Tick};
+use crate::{
+    corgi::Corgi,
+    rng::{RngStream, SimRng},
+};
+use bevy::prelude::*;
+use bevy_rapier2d::{
+    na::Vector2, physics::RigidBodyHandleComponent, rapier::dynamics::RigidBodySet,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// Range of the ticks a weather lasts.
+const WEATHER_DURATION: (usize, usize) = (1800, 7200);
+/// Probabilities of rain, drought and storm when the weather changes, clear weather otherwise.
+const WEATHER_PROBABILITIES: [(WeatherKind, f64); 3] = [
+    (WeatherKind::Rain, 0.2),
+    (WeatherKind::Drought, 0.15),
+    (WeatherKind::Storm, 0.1),
+];
+/// Factor of the food regrowth while it rains.
+const RAIN_REGROWTH: f32 = 1.5;
+/// Force of a storm on a corgi in its main direction.
+const STORM_FORCE: f32 = 5_000.0;
+/// Force of the random gusts of a storm, added to the main direction.
+const STORM_GUST_FORCE: f32 = 8_000.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WeatherKind {
+    Clear,
+    /// The food regrows faster.
+    Rain,
+    /// The food doesn't regrow at all.
+    Drought,
+    /// Random gusts push the corgis around.
+    Storm,
+}
+
+impl WeatherKind {
+    /// Multiplied with the color of the tiles.
+    pub fn tint(self) -> Color {
+        match self {
+            Self::Clear => Color::WHITE,
+            Self::Rain => Color::rgb(0.8, 0.85, 1.0),
+            Self::Drought => Color::rgb(1.0, 0.9, 0.7),
+            Self::Storm => Color::rgb(0.65, 0.65, 0.7),
+        }
+    }
+}
+
+/// Sent whenever the weather changes.
+#[derive(Clone, Copy, Debug)]
+pub struct WeatherChanged {
+    pub kind: WeatherKind,
+    /// Ticks until the weather changes again.
+    pub duration: usize,
+}
+
+/// The current weather, drawn from an rng seeded by the scenario or the simulation rng.
+pub struct Weather {
+    pub kind: WeatherKind,
+    /// Tick at which the weather changes next.
+    pub until: usize,
+    /// Main direction of the wind during storms.
+    pub wind: Vec2,
+    seed: Option<u64>,
+    rng: StdRng,
+}
+
+impl Default for Weather {
+    fn default() -> Self {
+        Self::new(None, &mut SimRng::default())
+    }
+}
+
+impl Weather {
+    /// Clear weather until the first change, a seed drawn from `sim_rng` if `None`.
+    pub fn new(seed: Option<u64>, sim_rng: &mut SimRng) -> Self {
+        Self {
+            kind: WeatherKind::Clear,
+            until: 0,
+            wind: Vec2::zero(),
+            seed,
+            rng: StdRng::seed_from_u64(seed.unwrap_or_else(|| sim_rng.gen())),
+        }
+    }
+
+    /// Factor of the food regrowth.
+    pub fn regrowth(&self) -> f32 {
+        match self.kind {
+            WeatherKind::Rain => RAIN_REGROWTH,
+            WeatherKind::Drought => 0.0,
+            WeatherKind::Clear | WeatherKind::Storm => 1.0,
+        }
+    }
+
+    fn change(&mut self, tick: usize) -> WeatherChanged {
+        let roll: f64 = self.rng.gen();
+        let mut sum = 0.0;
+        self.kind = WeatherKind::Clear;
+        for &(kind, probability) in WEATHER_PROBABILITIES.iter() {
+            sum += probability;
+            if roll < sum {
+                self.kind = kind;
+                break;
+            }
+        }
+        let angle = self.rng.gen_range(0.0..std::f32::consts::PI * 2.0);
+        self.wind = Vec2::new(angle.cos(), angle.sin());
+        let duration = self.rng.gen_range(WEATHER_DURATION.0..WEATHER_DURATION.1);
+        self.until = tick + duration;
+        WeatherChanged {
+            kind: self.kind,
+            duration,
+        }
+    }
+}
+
+pub struct WeatherPlugin;
+
+impl Plugin for WeatherPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(Weather::default())
+            .add_event::<WeatherChanged>()
+            .add_system(update_weather.system())
+            .add_system(log_weather.system())
+            .add_system(blow_storm.system());
+    }
+
+    fn name(&self) -> &str {
+        "WeatherPlugin"
+    }
+}
+
+/// Draws the next weather once the current one is over.
+/// The weather starts once the simulation rng is seeded,
+/// a scenario with another seed restarts it.
+fn update_weather(
+    tick: Res<Tick>,
+    scenario: Res<Scenario>,
+    mut rng: ResMut<SimRng>,
+    mut weather: ResMut<Weather>,
+    mut events: ResMut<Events<WeatherChanged>>,
+    mut started: Local<bool>,
+) {
+    if !scenario.weather {
+        if weather.kind != WeatherKind::Clear {
+            *weather = Weather::new(scenario.weather_seed, &mut rng);
+            events.send(WeatherChanged {
+                kind: WeatherKind::Clear,
+                duration: 0,
+            });
+        }
+        return;
+    }
+    if !*started || weather.seed != scenario.weather_seed {
+        *weather = Weather::new(scenario.weather_seed, &mut rng);
+        *started = true;
+    }
+    if tick.0 >= weather.until {
+        let changed = weather.change(tick.0);
+        events.send(changed);
+    }
+}
+
+fn log_weather(
+    mut reader: Local<EventReader<WeatherChanged>>,
+    events: Res<Events<WeatherChanged>>,
+) {
+    for event in reader.iter(&events) {
+        info!("weather: {:?} for {} ticks", event.kind, event.duration);
+    }
+}
+
+/// Storms push every corgi in the wind direction with random gusts.
+/// The gusts come from the streams of the corgis,
+/// so the weather stays the same with any population.
+fn blow_storm(
+    weather: Res<Weather>,
+    mut bodies: ResMut<RigidBodySet>,
+    mut query: Query<(&RigidBodyHandleComponent, &mut RngStream), With<Corgi>>,
+) {
+    if weather.kind != WeatherKind::Storm {
+        return;
+    }
+    let wind = weather.wind * STORM_FORCE;
+    for (handle, mut rng) in query.iter_mut() {
+        let gust = Vec2::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
+        let force = wind + gust * STORM_GUST_FORCE;
+        if let Some(body) = bodies.get_mut(handle.handle()) {
+            body.apply_force(Vector2::new(force.x, force.y), true);
+        }
+    }
+}